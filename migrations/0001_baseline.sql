@@ -0,0 +1,35 @@
+-- 最初手工执行的建表脚本，已经部署的数据库里这些表都存在
+create table if not exists accounts
+(
+    id           text                     not null
+        constraint pk_id
+            primary key,
+    display_name text                     not null,
+    role         text                     not null,
+    created_at   timestamp with time zone not null
+);
+
+create table if not exists books
+(
+    id           bigserial,
+    isbn         text,
+    title        text                     not null,
+    authors      text[],
+    publisher    text,
+    publish_date text,
+    state        text,
+    log_id       bigint                   not null,
+    thumbnail    text,
+    created_at   timestamp with time zone not null,
+    deleted_at   timestamp with time zone
+);
+
+create table if not exists change_logs
+(
+    id          bigserial                not null,
+    operator    text                     not null,
+    source_id   bigint                   not null,
+    source_type text                     not null,
+    action      text,
+    operate_at  timestamp with time zone not null
+);
@@ -0,0 +1,53 @@
+-- 书目信息从 books 拆到 titles，books 中的每一行是一本实体书
+create table titles
+(
+    id           bigserial,
+    isbn         text,
+    title        text                     not null,
+    authors      text[],
+    publisher    text,
+    publish_date text,
+    thumbnail    text,
+    created_at   timestamp with time zone not null
+);
+
+create unique index titles_isbn_uindex
+    on titles (isbn);
+
+alter table books
+    add column title_id bigint,
+    add column barcode  text;
+
+-- 同一 ISBN 的书合并为一条书目，书目信息取最早入库的那本
+insert into titles (isbn, title, authors, publisher, publish_date, thumbnail, created_at)
+select distinct on (isbn) isbn, title, authors, publisher, publish_date, thumbnail, created_at
+from books
+where isbn is not null
+order by isbn, created_at, id;
+
+update books b
+set title_id = t.id
+from titles t
+where t.isbn = b.isbn;
+
+-- 没有 ISBN 的书各自一条书目
+update books
+set title_id = nextval(pg_get_serial_sequence('titles', 'id'))
+where isbn is null;
+
+insert into titles (id, isbn, title, authors, publisher, publish_date, thumbnail, created_at)
+select title_id, null, title, authors, publisher, publish_date, thumbnail, created_at
+from books
+where isbn is null;
+
+alter table books
+    alter column title_id set not null,
+    drop column isbn,
+    drop column title,
+    drop column authors,
+    drop column publisher,
+    drop column publish_date,
+    drop column thumbnail;
+
+create unique index books_barcode_uindex
+    on books (barcode);
@@ -0,0 +1,23 @@
+create table loans
+(
+    id          bigserial,
+    book_id     bigint                   not null,
+    borrower    text                     not null,
+    borrowed_at timestamp with time zone not null,
+    due_at      timestamp with time zone not null,
+    returned_at timestamp with time zone,
+    renewals    integer default 0        not null
+);
+
+-- 同一本书同时只能有一条未归还的借阅记录
+create unique index loans_open_uindex
+    on loans (book_id)
+    where returned_at is null;
+
+-- 正在借出的书补一条借阅记录，借阅人和借出时间取最后一条操作记录，按默认的 30 天计算应还日期
+insert into loans (book_id, borrower, borrowed_at, due_at)
+select b.id, cl.operator, cl.operate_at, cl.operate_at + interval '30 days'
+from books b
+         join change_logs cl on cl.id = b.log_id
+where b.state = 'borrowed'
+  and b.deleted_at is null;
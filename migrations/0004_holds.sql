@@ -0,0 +1,15 @@
+create table holds
+(
+    id         bigserial,
+    title_id   bigint                   not null,
+    requester  text                     not null,
+    state      text                     not null,
+    book_id    bigint,
+    created_at timestamp with time zone not null,
+    expires_at timestamp with time zone
+);
+
+-- 同一个人对同一书目只能有一个进行中的预约
+create unique index holds_active_uindex
+    on holds (title_id, requester)
+    where state in ('waiting', 'ready');
@@ -0,0 +1,55 @@
+-- change_logs.action 从中文描述改为操作类型，
+-- 无法识别的记为 unknown，原来的文字保存在 payload.legacy 中
+alter table change_logs
+    add column from_state text,
+    add column to_state   text,
+    add column payload    jsonb;
+
+update change_logs
+set payload  = case when action is not null then jsonb_build_object('legacy', action) end,
+    action   = case
+                   when action in ('新书第一次入库', '新增馆藏副本') then 'stored'
+                   when action like '%确认书籍已经归还%' then 'confirmed'
+                   when action like '%借出书籍' then 'borrowed'
+                   when action like '%续借书籍' then 'renewed'
+                   when action like '%归还书籍' then 'returned'
+                   when action like '%取消预约%' then 'hold_cancelled'
+                   when action like '%预约超时未取书%' then 'hold_expired'
+                   when action = '书籍被标记为遗失' then 'lost'
+                   when action = '书籍状态被重置' then 'reset'
+                   when action = '删除该书籍' then 'deleted'
+                   else 'unknown'
+        end,
+    to_state = case
+                   when action like '%保留' then 'reserved'
+                   when action in ('新书第一次入库', '新增馆藏副本') then 'available'
+                   when action like '%确认书籍已经归还%' then 'available'
+                   when action like '%借出书籍' or action like '%续借书籍' then 'borrowed'
+                   when action like '%归还书籍' then 'returned'
+                   when action like '%取消预约%' or action like '%预约超时未取书%' then 'available'
+                   when action = '书籍被标记为遗失' then 'lost'
+                   when action = '书籍状态被重置' then 'available'
+                   when action = '删除该书籍' then 'deleted'
+        end;
+
+-- 操作前的状态就是同一本书上一条记录操作后的状态
+update change_logs cl
+set from_state = prev.from_state
+from (select id,
+             lag(to_state) over (partition by source_type, source_id order by operate_at, id) as from_state
+      from change_logs) prev
+where prev.id = cl.id;
+
+-- 从原文中取出附加信息，只有无法识别的记录保留原文
+update change_logs
+set payload = case
+                  when payload ->> 'legacy' like '%，为 % 保留'
+                      then jsonb_build_object('reserved_for',
+                                              substring(payload ->> 'legacy' from '，为 (.*) 保留$'))
+                  when payload ->> 'legacy' = '新书第一次入库' then '{"new_title": true}'::jsonb
+                  when payload ->> 'legacy' = '新增馆藏副本' then '{"new_title": false}'::jsonb
+                  when action = 'unknown' then payload
+        end;
+
+alter table change_logs
+    alter column action set not null;
@@ -0,0 +1,45 @@
+alter table titles
+    add constraint titles_pk primary key (id);
+alter table books
+    add constraint books_pk primary key (id);
+alter table change_logs
+    add constraint change_logs_pk primary key (id);
+alter table loans
+    add constraint loans_pk primary key (id);
+alter table holds
+    add constraint holds_pk primary key (id);
+
+-- 旧数据中的操作人可能没有登录过，先补齐账号再加外键
+insert into accounts (id, display_name, role, created_at)
+select distinct cl.operator, cl.operator, 'user', now()
+from change_logs cl
+where not exists(select 1 from accounts a where a.id = cl.operator);
+
+-- 入库时先插入书籍再写操作记录，log_id 的外键推迟到事务提交时检查
+alter table books
+    add constraint books_title_id_fk foreign key (title_id) references titles (id),
+    add constraint books_log_id_fk foreign key (log_id) references change_logs (id)
+        deferrable initially deferred;
+alter table change_logs
+    add constraint change_logs_operator_fk foreign key (operator) references accounts (id);
+alter table loans
+    add constraint loans_book_id_fk foreign key (book_id) references books (id),
+    add constraint loans_borrower_fk foreign key (borrower) references accounts (id);
+alter table holds
+    add constraint holds_title_id_fk foreign key (title_id) references titles (id),
+    add constraint holds_book_id_fk foreign key (book_id) references books (id),
+    add constraint holds_requester_fk foreign key (requester) references accounts (id);
+
+create index books_title_id_index
+    on books (title_id);
+create index books_deleted_at_index
+    on books (deleted_at);
+create index change_logs_source_index
+    on change_logs (source_type, source_id, operate_at);
+create index change_logs_operator_index
+    on change_logs (operator, operate_at);
+create index loans_borrower_index
+    on loans (borrower)
+    where returned_at is null;
+create index holds_title_id_index
+    on holds (title_id, created_at);
@@ -0,0 +1,10 @@
+-- ISBN 检索结果的本地缓存，data 是合并后的书籍信息，raw 按检索服务名保存原始返回
+create table isbn_metadata
+(
+    isbn       text                     not null
+        constraint isbn_metadata_pk
+            primary key,
+    data       jsonb                    not null,
+    raw        jsonb                    not null,
+    fetched_at timestamp with time zone not null
+);
@@ -0,0 +1,20 @@
+-- ISBN 检索服务返回的完整书目信息
+alter table titles
+    add column subtitle     text,
+    add column translators  text[],
+    add column pages        text,
+    add column price        text,
+    add column description  text,
+    add column author_intro text,
+    add column tags         text[],
+    add column douban_id    bigint,
+    add column douban_score double precision;
+
+-- 已经缓存过检索结果的书目直接补齐
+update titles t
+set subtitle    = nullif(m.data ->> 'subname', ''),
+    pages       = nullif(m.data ->> 'pages', ''),
+    price       = nullif(m.data ->> 'price', ''),
+    description = nullif(m.data ->> 'description', '')
+from isbn_metadata m
+where m.isbn = t.isbn;
@@ -0,0 +1,34 @@
+-- 全文检索：书名、副标题、作者、出版社和简介拼成 search_text，用于子串匹配（trigram 索引）；
+-- 中文没有空格分词，search_vector 中每个汉字作为一个词，查询时连续的汉字用 <-> 组成短语
+create extension if not exists pg_trgm;
+
+create or replace function titles_search_text(title text, subtitle text, authors text[], publisher text,
+                                              description text) returns text
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select lower(concat_ws(' ', title, subtitle, array_to_string(authors, ' '), publisher, description))
+$$;
+
+create or replace function cjk_split(s text) returns text
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select regexp_replace(s, '([㐀-鿿豈-﫿])', ' \1 ', 'g')
+$$;
+
+alter table titles
+    add column search_text   text generated always as
+        (titles_search_text(title, subtitle, authors, publisher, description)) stored,
+    add column search_vector tsvector generated always as
+        (to_tsvector('simple', cjk_split(titles_search_text(title, subtitle, authors, publisher, description)))) stored;
+
+create index titles_search_vector_index
+    on titles using gin (search_vector);
+
+create index titles_search_text_trgm_index
+    on titles using gin (search_text gin_trgm_ops);
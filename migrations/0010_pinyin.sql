@@ -0,0 +1,10 @@
+-- 书名和作者的拼音全拼与首字母，由应用在入库和修改书目时写入，已有书目用 backfill-pinyin 命令补齐
+alter table titles
+    add column pinyin          text,
+    add column pinyin_initials text;
+
+create index titles_pinyin_trgm_index
+    on titles using gin (pinyin gin_trgm_ops);
+
+create index titles_pinyin_initials_trgm_index
+    on titles using gin (pinyin_initials gin_trgm_ops);
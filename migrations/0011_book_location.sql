@@ -0,0 +1,14 @@
+-- 馆藏位置，原来只在批量导入的入库记录 payload 中，改为保存在副本上以便筛选
+alter table books
+    add column location text;
+
+update books b
+set location = cl.payload ->> 'location'
+from change_logs cl
+where cl.source_type = 'book'
+  and cl.source_id = b.id
+  and cl.action = 'stored'
+  and cl.payload ->> 'location' is not null;
+
+create index books_location_index
+    on books (location);
@@ -0,0 +1,3 @@
+-- 统计页面按操作类型和月份汇总借阅记录
+create index change_logs_action_index
+    on change_logs (action, operate_at);
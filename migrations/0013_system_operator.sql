@@ -0,0 +1,9 @@
+-- 系统自动执行的操作（如预约超时）记在 system 账号下，不再算作预约人自己的操作
+insert into accounts (id, display_name, role, created_at)
+values ('system', '系统', 'user', now())
+on conflict (id) do nothing;
+
+update change_logs
+set payload  = coalesce(payload, '{}'::jsonb) || jsonb_build_object('requester', operator),
+    operator = 'system'
+where action = 'hold_expired';
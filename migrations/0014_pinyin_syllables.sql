@@ -0,0 +1,4 @@
+-- 全拼改为音节之间用 ' 隔开，清空旧格式的拼音，服务启动时重新生成
+update titles
+set pinyin          = null,
+    pinyin_initials = null;
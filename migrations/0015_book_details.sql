@@ -0,0 +1,50 @@
+-- 书籍列表、详情等共用的查询：副本、书目、最后一条操作记录、副本数、当前借阅和预约保留
+create view book_details as
+select b.id,
+       b.title_id,
+       b.barcode,
+       t.isbn,
+       t.title,
+       coalesce(t.authors, '{}') as authors,
+       t.publisher,
+       t.publish_date,
+       b.created_at,
+       b.state,
+       cl.operator,
+       a.display_name            as operator_name,
+       cl.operate_at,
+       t.thumbnail,
+       b.deleted_at,
+       b.log_id,
+       t.subtitle,
+       t.translators,
+       t.pages,
+       t.price,
+       t.description,
+       t.author_intro,
+       t.tags,
+       t.douban_id,
+       t.douban_score,
+       c.copies,
+       c.available_copies,
+       l.borrower,
+       la.display_name           as borrower_name,
+       l.due_at,
+       l.renewals,
+       h.requester               as reserved_for,
+       ha.display_name           as reserved_for_name,
+       h.expires_at              as reserved_until,
+       b.location
+from books b
+         join titles t on b.title_id = t.id
+         left join change_logs cl on b.log_id = cl.id
+         left join accounts a on a.id = cl.operator
+         left join lateral (select count(*)                                        as copies,
+                                   count(*) filter ( where s.state = 'available' ) as available_copies
+                            from books s
+                            where s.title_id = b.title_id
+                              and s.deleted_at is null) c on true
+         left join loans l on l.book_id = b.id and l.returned_at is null
+         left join accounts la on la.id = l.borrower
+         left join holds h on h.book_id = b.id and h.state = 'ready'
+         left join accounts ha on ha.id = h.requester;
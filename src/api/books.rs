@@ -1,5 +1,7 @@
 use crate::api::auth::{get_account, Role, UserSession};
-use crate::api::entity::BookState;
+pub use crate::api::entity::BookAction;
+use crate::api::entity::{BookState, ChangeAction};
+use crate::api::search::{self, BookQuery, BookSort, Facets, SearchHighlight};
 use leptos::ServerFnError::{Request, ServerError};
 use leptos::*;
 use serde::{Deserialize, Serialize};
@@ -9,13 +11,37 @@ use serde::{Deserialize, Serialize};
 pub fn register_server_functions() {
     let _ = FastStorageBook::register();
     let _ = BookList::register();
+    let _ = TitleList::register();
+    let _ = BookCopies::register();
+    let _ = OverdueBookList::register();
     let _ = BookDetail::register();
     let _ = BorrowBook::register();
     let _ = ReturnBook::register();
+    let _ = RenewLoan::register();
     let _ = ConfirmReturnBook::register();
+    let _ = ReturnQueue::register();
+    let _ = ConfirmReturns::register();
+    let _ = ReportBookProblem::register();
+    let _ = MarkBookLost::register();
+    let _ = ResetBook::register();
+    let _ = DeleteBook::register();
+    let _ = TitleHolds::register();
+    let _ = PlaceHold::register();
+    let _ = CancelHold::register();
+    let _ = BookHistory::register();
+    let _ = ActivityList::register();
+    let _ = RefreshMetadata::register();
+    let _ = LookupIsbn::register();
+    let _ = CreateBook::register();
+    let _ = UpdateBook::register();
+    let _ = RevertEdit::register();
 }
 #[server(FastStorageBook, "/api")]
-pub async fn fast_storage_book(cx: Scope, isbn: String) -> Result<(), ServerFnError> {
+pub async fn fast_storage_book(
+    cx: Scope,
+    isbn: String,
+    barcode: Option<String>,
+) -> Result<(), ServerFnError> {
     let ac = get_account(cx)
         .await?
         .ok_or(Request("Not logged in".to_string()))?;
@@ -23,26 +49,316 @@ pub async fn fast_storage_book(cx: Scope, isbn: String) -> Result<(), ServerFnEr
         return Err(Request("Not admin".to_string()));
     }
     let bms = crate::backend::books::BookMS::from_scope(cx);
-    bms.storage(isbn.as_str(), &ac.uid)
+    let barcode = barcode.filter(|b| !b.trim().is_empty());
+    bms.storage(isbn.as_str(), &barcode, &ac.uid)
         .await
-        .map_err(|e| ServerError(e.to_string()))?;
+        .map_err(|e| match e.downcast::<crate::backend::books::BookError>() {
+            Ok(e) => e.into(),
+            Err(e) => ServerError(format!("{:#}", e)),
+        })?;
+    Ok(())
+}
+
+// 忽略缓存重新查询书目的 ISBN 信息
+#[server(RefreshMetadata, "/api")]
+pub async fn refresh_metadata(cx: Scope, title_id: i64) -> Result<(), ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not logged in".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    bms.refresh_metadata(&title_id, &ac.uid)
+        .await
+        .map_err(|e| ServerError(format!("{:#}", e)))?;
     Ok(())
 }
 
+// 手工编目前查询检索服务，查到的信息用于预填表单；查不到时返回 None
+#[server(LookupIsbn, "/api")]
+pub async fn lookup_isbn(cx: Scope, isbn: String) -> Result<Option<TitleDraft>, ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not logged in".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let data = bms.prefill(&isbn).await.map_err(|e| {
+        match e.downcast::<crate::backend::books::BookError>() {
+            Ok(e) => e.into(),
+            Err(e) => ServerError(format!("{:#}", e)),
+        }
+    })?;
+    Ok(data.map(TitleDraft::from))
+}
+
+// 手工编目：检索服务查不到的图书由管理员填写书目信息。
+// cover 为上传封面的 data URL，优先于 thumbnail（检索服务给出的封面地址）
+#[server(CreateBook, "/api")]
+pub async fn create_book(
+    cx: Scope,
+    title: String,
+    authors: String,
+    publisher: String,
+    publish_date: String,
+    isbn: Option<String>,
+    barcode: Option<String>,
+    thumbnail: Option<String>,
+    cover: Option<String>,
+) -> Result<i64, ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not logged in".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let title = title.trim().to_string();
+    if title.is_empty() {
+        return Err(Request("书名不能为空".to_string()));
+    }
+    let conf = use_context::<crate::backend::conf::Config>(cx)
+        .ok_or(ServerError("No config".to_string()))?;
+    let thumbnail = match cover.filter(|c| !c.is_empty()) {
+        Some(c) => crate::backend::covers::save(&conf.cover_dir, &c)
+            .await
+            .map_err(|e| Request(format!("{:#}", e)))?,
+        None => thumbnail.unwrap_or_default().trim().to_string(),
+    };
+    let data = crate::backend::books::ISBNData {
+        name: title,
+        authors: split_authors(&authors),
+        publishing: publisher.trim().to_string(),
+        published: publish_date.trim().to_string(),
+        photo_url: thumbnail,
+        ..Default::default()
+    };
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let barcode = barcode.filter(|b| !b.trim().is_empty());
+    let title_id = bms
+        .create(&data, isbn.as_deref(), &barcode, &ac.uid)
+        .await
+        .map_err(|e| match e.downcast::<crate::backend::books::BookError>() {
+            Ok(e) => e.into(),
+            Err(e) => ServerError(format!("{:#}", e)),
+        })?;
+    Ok(title_id)
+}
+
+// 手工修改书目信息，空字符串表示清空该字段
+#[server(UpdateBook, "/api")]
+pub async fn update_book(
+    cx: Scope,
+    title_id: i64,
+    title: String,
+    subtitle: String,
+    authors: String,
+    publisher: String,
+    publish_date: String,
+    thumbnail: String,
+) -> Result<(), ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not logged in".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let title = title.trim().to_string();
+    if title.is_empty() {
+        return Err(Request("书名不能为空".to_string()));
+    }
+    let optional = |s: String| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+    let fields = crate::backend::books::TitleFieldsModel {
+        title,
+        subtitle: optional(subtitle),
+        authors: split_authors(&authors),
+        publisher: optional(publisher),
+        publish_date: optional(publish_date),
+        thumbnail: optional(thumbnail),
+    };
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    bms.update_title(&title_id, &fields, &ac.uid).await?;
+    Ok(())
+}
+
+// 撤销一次书目修改
+#[server(RevertEdit, "/api")]
+pub async fn revert_edit(cx: Scope, log_id: i64) -> Result<(), ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not logged in".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    bms.revert_edit(&log_id, &ac.uid).await?;
+    Ok(())
+}
+
+// 多个作者可以用中英文逗号、顿号或者 / 分隔
+pub fn split_authors(authors: &str) -> Vec<String> {
+    authors
+        .split(|c| matches!(c, ',' | '，' | '、' | '/'))
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+// 一页图书列表及当前条件下的分面统计。
+// total 为符合条件的总数，next_cursor 为获取下一页的游标，已经是最后一页时为空
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BookListUI {
+    pub books: Vec<BookUI>,
+    pub facets: Facets,
+    pub total: i64,
+    pub next_cursor: Option<String>,
+}
+
 #[server(BookList, "/api")]
 pub async fn book_list(
     cx: Scope,
+    cursor: Option<String>,
     offset: Option<i64>,
     limit: Option<i64>,
     q: Option<String>,
-) -> Result<Vec<BookUI>, ServerFnError> {
+    state: Option<BookState>,
+    author: Option<String>,
+    publisher: Option<String>,
+    year_from: Option<i32>,
+    year_to: Option<i32>,
+    tag: Option<String>,
+    location: Option<String>,
+    borrower: Option<String>,
+    sort: Option<BookSort>,
+) -> Result<BookListUI, ServerFnError> {
+    let query = BookQuery {
+        q,
+        state,
+        author,
+        publisher,
+        year_from,
+        year_to,
+        tag,
+        location,
+        borrower,
+        sort,
+    };
+    list_books(cx, query, cursor, offset, limit, true).await
+}
+
+// 每个书目只返回一本代表副本，用于首页书架
+#[server(TitleList, "/api")]
+pub async fn title_list(
+    cx: Scope,
+    cursor: Option<String>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+    q: Option<String>,
+    state: Option<BookState>,
+    author: Option<String>,
+    publisher: Option<String>,
+    year_from: Option<i32>,
+    year_to: Option<i32>,
+    tag: Option<String>,
+    location: Option<String>,
+    sort: Option<BookSort>,
+) -> Result<BookListUI, ServerFnError> {
+    let query = BookQuery {
+        q,
+        state,
+        author,
+        publisher,
+        year_from,
+        year_to,
+        tag,
+        location,
+        borrower: None,
+        sort,
+    };
+    list_books(cx, query, cursor, offset, limit, false).await
+}
+
+#[cfg(feature = "ssr")]
+async fn list_books(
+    cx: Scope,
+    query: BookQuery,
+    cursor: Option<String>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+    copies: bool,
+) -> Result<BookListUI, ServerFnError> {
     let limit = limit.unwrap_or(10);
     let offset = offset.unwrap_or(0);
     let ac = get_account(cx).await?;
-    // dbg!(q);
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let cursor = cursor.as_deref();
+    let page = if copies {
+        bms.list(&query, cursor, &limit, &offset).await
+    } else {
+        bms.list_titles(&query, cursor, &limit, &offset).await
+    }
+    .map_err(|e| ServerError(e.to_string()))?;
+    let books = page
+        .books
+        .iter()
+        .map(|b| {
+            let mut b = BookUI::from(b);
+            b.bind_role(&ac);
+            b.bind_search(&query.q);
+            b
+        })
+        .collect();
+    let facets = bms
+        .facets(&query, copies)
+        .await
+        .map_err(|e| ServerError(e.to_string()))?;
+
+    Ok(BookListUI {
+        books,
+        facets,
+        total: page.total,
+        next_cursor: page.next_cursor,
+    })
+}
+
+#[server(BookCopies, "/api")]
+pub async fn book_copies(cx: Scope, title_id: i64) -> Result<Vec<BookUI>, ServerFnError> {
+    let ac = get_account(cx).await?;
     let bms = crate::backend::books::BookMS::from_scope(cx);
     let books = bms
-        .list(&limit, &offset, &q)
+        .copies_of(&title_id)
+        .await
+        .map_err(|e| ServerError(e.to_string()))?
+        .iter()
+        .map(|b| {
+            let mut b = BookUI::from(b);
+            b.bind_role(&ac);
+            b
+        })
+        .collect();
+
+    Ok(books)
+}
+
+#[server(OverdueBookList, "/api")]
+pub async fn overdue_book_list(
+    cx: Scope,
+    offset: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Vec<BookUI>, ServerFnError> {
+    let limit = limit.unwrap_or(10);
+    let offset = offset.unwrap_or(0);
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let ac = Some(ac);
+    let books = bms
+        .overdue(&limit, &offset)
         .await
         .map_err(|e| ServerError(e.to_string()))?
         .iter()
@@ -77,9 +393,7 @@ pub async fn borrow_book(cx: Scope, id: i64) -> Result<(), ServerFnError> {
         .await?
         .ok_or(Request("Not login".to_string()))?;
     let bms = crate::backend::books::BookMS::from_scope(cx);
-    bms.borrow(&id, ac.uid.as_str())
-        .await
-        .map_err(|e| ServerError(e.to_string()))?;
+    bms.borrow(&id, &ac).await?;
     Ok(())
 }
 #[server(ReturnBook, "/api")]
@@ -88,9 +402,16 @@ pub async fn return_book(cx: Scope, id: i64) -> Result<(), ServerFnError> {
         .await?
         .ok_or(Request("Not login".to_string()))?;
     let bms = crate::backend::books::BookMS::from_scope(cx);
-    bms.revert_to(&id, ac.uid.as_str())
-        .await
-        .map_err(|e| ServerError(e.to_string()))?;
+    bms.revert_to(&id, &ac).await?;
+    Ok(())
+}
+#[server(RenewLoan, "/api")]
+pub async fn renew_loan(cx: Scope, id: i64) -> Result<(), ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    bms.renew(&id, &ac).await?;
     Ok(())
 }
 #[server(ConfirmReturnBook, "/api")]
@@ -102,26 +423,314 @@ pub async fn confirm_return_book(cx: Scope, id: i64) -> Result<(), ServerFnError
         return Err(Request("Not admin".to_string()));
     }
     let bms = crate::backend::books::BookMS::from_scope(cx);
-    bms.confirm(&id, &ac.uid)
+    bms.confirm(&id, &ac).await?;
+    Ok(())
+}
+// 已归还、等待确认的书籍，最早归还的排在前面
+#[server(ReturnQueue, "/api")]
+pub async fn return_queue(cx: Scope) -> Result<Vec<BookUI>, ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let ac = Some(ac);
+    let books = bms
+        .return_queue()
+        .await
+        .map_err(|e| ServerError(e.to_string()))?
+        .iter()
+        .map(|b| {
+            let mut b = BookUI::from(b);
+            b.bind_role(&ac);
+            b
+        })
+        .collect();
+
+    Ok(books)
+}
+
+// 批量确认归还，ids 为逗号分隔的书籍 id
+#[server(ConfirmReturns, "/api")]
+pub async fn confirm_returns(cx: Scope, ids: String) -> Result<(), ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let ids = parse_ids(&ids).ok_or(Request("书籍 id 格式错误".to_string()))?;
+    if ids.is_empty() {
+        return Err(Request("请先选择要确认归还的书籍".to_string()));
+    }
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    bms.confirm_all(&ids, &ac).await?;
+    Ok(())
+}
+
+// 确认归还时发现问题，problem 为 lost 或 damaged
+#[server(ReportBookProblem, "/api")]
+pub async fn report_book_problem(
+    cx: Scope,
+    id: i64,
+    problem: String,
+    note: String,
+) -> Result<(), ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let action = match problem.as_str() {
+        "lost" => BookAction::Lost,
+        "damaged" => BookAction::Damage,
+        _ => return Err(Request(format!("未知的问题类型 {}", problem))),
+    };
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    bms.report_problem(&id, action, &note, &ac).await?;
+    Ok(())
+}
+
+// 逗号分隔的 id 列表，忽略空白和空项
+pub fn parse_ids(ids: &str) -> Option<Vec<i64>> {
+    ids.split(',')
+        .map(|id| id.trim())
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse::<i64>().ok())
+        .collect()
+}
+
+#[server(MarkBookLost, "/api")]
+pub async fn mark_book_lost(cx: Scope, id: i64) -> Result<(), ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    bms.lost(&id, &ac).await?;
+    Ok(())
+}
+#[server(ResetBook, "/api")]
+pub async fn reset_book(cx: Scope, id: i64) -> Result<(), ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    bms.reset(&id, &ac).await?;
+    Ok(())
+}
+#[server(DeleteBook, "/api")]
+pub async fn delete_book(cx: Scope, id: i64) -> Result<(), ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    bms.delete(&id, &ac).await?;
+    Ok(())
+}
+
+// 书籍所属书目的预约队列
+#[server(TitleHolds, "/api")]
+pub async fn title_holds(cx: Scope, id: i64) -> Result<HoldQueueUI, ServerFnError> {
+    let ac = get_account(cx).await?;
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let book = bms
+        .get_one_by_id(&id)
         .await
         .map_err(|e| ServerError(e.to_string()))?;
+    let uid = ac.map(|a| a.uid).unwrap_or("".to_string());
+    let holds: Vec<HoldUI> = bms
+        .holds(&book.title_id)
+        .await
+        .map_err(|e| ServerError(e.to_string()))?
+        .into_iter()
+        .enumerate()
+        .map(|(i, h)| HoldUI {
+            id: h.id,
+            position: i as i64 + 1,
+            mine: h.requester == uid,
+            requester_name: h.requester_name,
+            ready: h.state == crate::backend::holds::HoldStateModel::Ready,
+            expires_at: h.expires_at,
+        })
+        .collect();
+    let can_place = uid != ""
+        && book.available_copies == 0
+        && book.borrower.as_ref() != Some(&uid)
+        && !holds.iter().any(|h| h.mine);
+
+    Ok(HoldQueueUI { holds, can_place })
+}
+#[server(PlaceHold, "/api")]
+pub async fn place_hold(cx: Scope, id: i64) -> Result<(), ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    bms.place_hold(&id, &ac).await?;
+    Ok(())
+}
+#[server(CancelHold, "/api")]
+pub async fn cancel_hold(cx: Scope, id: i64) -> Result<(), ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    bms.cancel_hold(&id, &ac).await?;
     Ok(())
 }
 
+// 一本书的操作记录，最新的在前
+#[server(BookHistory, "/api")]
+pub async fn book_history(
+    cx: Scope,
+    id: i64,
+    offset: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Vec<ChangeLogUI>, ServerFnError> {
+    let limit = limit.unwrap_or(10);
+    let offset = offset.unwrap_or(0);
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    let admin = ac.role == Role::Admin;
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let logs = bms
+        .history(&id, &limit, &offset)
+        .await?
+        .into_iter()
+        .map(|l| {
+            let mut l = ChangeLogUI::from(l);
+            l.revertible = admin && l.is_title_edit();
+            if !admin {
+                l.redact();
+            }
+            l
+        })
+        .collect();
+    Ok(logs)
+}
+
+// 全馆的操作记录，可以按操作人和书籍筛选
+#[server(ActivityList, "/api")]
+pub async fn activity_list(
+    cx: Scope,
+    operator: Option<String>,
+    book_id: Option<i64>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Vec<ChangeLogUI>, ServerFnError> {
+    let limit = limit.unwrap_or(10);
+    let offset = offset.unwrap_or(0);
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let filter = crate::backend::books::ActivityFilter {
+        operator: operator.filter(|o| !o.trim().is_empty()),
+        book_id,
+        ..Default::default()
+    };
+    let logs = bms
+        .activity(&filter, &limit, &offset)
+        .await?
+        .into_iter()
+        .map(ChangeLogUI::from)
+        .collect();
+    Ok(logs)
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::backend::books::BookError> for ServerFnError {
+    fn from(value: crate::backend::books::BookError) -> Self {
+        use crate::backend::books::BookError;
+        match value {
+            BookError::Database(_) => ServerError(value.to_string()),
+            _ => Request(value.to_string()),
+        }
+    }
+}
+
+// 上传封面图片的大小上限
+pub const MAX_COVER_BYTES: usize = 1024 * 1024;
+
+// 手工编目表单的预填信息
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TitleDraft {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub publisher: String,
+    pub publish_date: String,
+    pub thumbnail: String,
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::backend::books::ISBNData> for TitleDraft {
+    fn from(value: crate::backend::books::ISBNData) -> Self {
+        Self {
+            title: value.name,
+            authors: value.authors,
+            publisher: value.publishing,
+            publish_date: value.published,
+            thumbnail: value.photo_url,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BookUI {
     pub id: i64,
+    pub title_id: i64,
+    pub barcode: String,
     pub isbn: String,
     pub title: String,
     pub authors: Vec<String>,
     pub publisher: String,
+    pub publish_date: String,
     pub import_at: time::OffsetDateTime,
     pub state: BookState,
     pub operator: String,
     pub operator_name: String,
     pub operate_at: time::OffsetDateTime,
     pub thumbnail: String,
+    pub subtitle: String,
+    pub translators: Vec<String>,
+    pub pages: String,
+    pub price: String,
+    pub description: String,
+    pub author_intro: String,
+    pub tags: Vec<String>,
+    pub douban_score: Option<f64>,
+    pub copies: i64,
+    pub available_copies: i64,
+    pub borrower: String,
+    pub borrower_name: String,
+    pub due_at: Option<time::OffsetDateTime>,
+    pub overdue: bool,
+    pub renewals: i32,
+    pub reserved_for: String,
+    pub reserved_for_name: String,
+    pub reserved_until: Option<time::OffsetDateTime>,
     pub actions: Vec<BookAction>,
+    // 当前用户可以维护书目信息
+    pub manageable: bool,
+    // 搜索结果中匹配的片段
+    pub highlight: Option<SearchHighlight>,
+    pub location: String,
 }
 
 #[cfg(feature = "ssr")]
@@ -129,17 +738,44 @@ impl From<crate::backend::books::BookModel> for BookUI {
     fn from(value: crate::backend::books::BookModel) -> Self {
         Self {
             id: value.id,
+            title_id: value.title_id,
+            barcode: value.barcode.unwrap_or("".to_string()),
             isbn: value.isbn.unwrap_or("".to_string()),
             title: value.title,
             authors: value.authors,
             publisher: value.publisher.unwrap_or("".to_string()),
+            publish_date: value.publish_date.unwrap_or("".to_string()),
             import_at: value.created_at,
             state: value.state.into(),
             operator: value.operator,
             operator_name: value.operator_name,
             operate_at: value.operate_at,
             thumbnail: value.thumbnail.unwrap_or("".to_string()),
+            subtitle: value.subtitle.unwrap_or("".to_string()),
+            translators: value.translators.unwrap_or(vec![]),
+            pages: value.pages.unwrap_or("".to_string()),
+            price: value.price.unwrap_or("".to_string()),
+            description: value.description.unwrap_or("".to_string()),
+            author_intro: value.author_intro.unwrap_or("".to_string()),
+            tags: value.tags.unwrap_or(vec![]),
+            douban_score: value.douban_score,
+            copies: value.copies,
+            available_copies: value.available_copies,
+            borrower: value.borrower.unwrap_or("".to_string()),
+            borrower_name: value.borrower_name.unwrap_or("".to_string()),
+            due_at: value.due_at,
+            overdue: value
+                .due_at
+                .map(|d| d < time::OffsetDateTime::now_utc())
+                .unwrap_or(false),
+            renewals: value.renewals.unwrap_or(0),
+            reserved_for: value.reserved_for.unwrap_or("".to_string()),
+            reserved_for_name: value.reserved_for_name.unwrap_or("".to_string()),
+            reserved_until: value.reserved_until,
             actions: vec![],
+            manageable: false,
+            highlight: None,
+            location: value.location.unwrap_or("".to_string()),
         }
     }
 }
@@ -151,42 +787,141 @@ impl From<&crate::backend::books::BookModel> for BookUI {
 }
 
 impl BookUI {
+    // 按搜索词标出书名、作者和简介中匹配的部分
+    pub fn bind_search(&mut self, q: &Option<String>) {
+        let terms = match q {
+            Some(q) => search::terms(q),
+            None => return,
+        };
+        if terms.is_empty() {
+            return;
+        }
+        self.highlight = Some(SearchHighlight {
+            title: search::highlight(&self.title, &terms),
+            authors: search::highlight(&self.authors.join(", "), &terms),
+            snippet: search::snippet(&self.description, &terms, 60),
+        });
+    }
+
     pub fn bind_role(&mut self, current_user: &Option<UserSession>) {
-        let current_uid = current_user
+        let holder = if self.state == BookState::Reserved {
+            &self.reserved_for
+        } else {
+            &self.borrower
+        };
+        self.actions = BookAction::permitted(&self.state, holder, current_user);
+        self.manageable = current_user
             .as_ref()
-            .map(|u| u.uid.clone())
-            .unwrap_or("".to_string());
-        let role = current_user
-            .as_ref()
-            .map(|u| u.role.clone())
-            .unwrap_or(Role::User);
-        let mut actions = vec![];
-        if self.state == BookState::Available && current_uid != "" {
-            actions.push(BookAction::Borrow);
-        }
-        if self.state == BookState::Borrowed && current_uid == self.operator {
-            actions.push(BookAction::Return);
-        }
-        if self.state == BookState::Returned && role == Role::Admin {
-            actions.push(BookAction::Confirm);
-        }
-        if self.state == BookState::Lost && role == Role::Admin {
-            actions.push(BookAction::Reset);
+            .map(|u| u.role == Role::Admin)
+            .unwrap_or(false);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HoldUI {
+    pub id: i64,
+    // 在队列中的位置，从 1 开始
+    pub position: i64,
+    pub requester_name: String,
+    // 已经有书籍为预约人保留，等待取书
+    pub ready: bool,
+    pub expires_at: Option<time::OffsetDateTime>,
+    pub mine: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HoldQueueUI {
+    pub holds: Vec<HoldUI>,
+    pub can_place: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChangeLogUI {
+    pub id: i64,
+    // source_type 为 title 时是书目 id
+    pub book_id: i64,
+    pub source_type: String,
+    pub operator: String,
+    pub operator_name: String,
+    pub action: ChangeAction,
+    pub from_state: Option<BookState>,
+    pub to_state: Option<BookState>,
+    pub payload: Option<serde_json::Value>,
+    pub operate_at: time::OffsetDateTime,
+    // 管理员可以撤销书目修改
+    pub revertible: bool,
+}
+
+impl ChangeLogUI {
+    // 普通用户看不到报告问题时填写的说明，以及书目修改前后的值
+    pub fn redact(&mut self) {
+        if let Some(serde_json::Value::Object(p)) = self.payload.as_mut() {
+            for key in ["note", "before", "after"] {
+                p.remove(key);
+            }
         }
-        if role == Role::Admin {
-            actions.push(BookAction::Lost);
-            actions.push(BookAction::Delete);
+    }
+
+    pub fn is_title_edit(&self) -> bool {
+        self.source_type == "title"
+            && self.action == ChangeAction::Edited
+            && self
+                .payload
+                .as_ref()
+                .map(|p| p.get("before").is_some())
+                .unwrap_or(false)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::backend::books::ChangeLogModel> for ChangeLogUI {
+    fn from(value: crate::backend::books::ChangeLogModel) -> Self {
+        Self {
+            id: value.id,
+            book_id: value.source_id,
+            source_type: value.source_type,
+            operator_name: value.operator_name.unwrap_or(value.operator.clone()),
+            operator: value.operator,
+            action: value.action.into(),
+            from_state: value.from_state.map(BookState::from),
+            to_state: value.to_state.map(BookState::from),
+            payload: value.payload,
+            operate_at: value.operate_at,
+            revertible: false,
         }
-        self.actions = actions;
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
-pub enum BookAction {
-    Borrow,
-    Return,
-    Confirm,
-    Lost,
-    Reset,
-    Delete,
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ids() {
+        assert_eq!(parse_ids("3, 1,,2"), Some(vec![3, 1, 2]));
+        assert_eq!(parse_ids(""), Some(vec![]));
+        assert_eq!(parse_ids("1,a"), None);
+    }
+
+    #[test]
+    fn redact() {
+        let mut l = ChangeLogUI {
+            id: 1,
+            book_id: 1,
+            source_type: "book".to_string(),
+            operator: "songsong".to_string(),
+            operator_name: "songsong".to_string(),
+            action: ChangeAction::Damaged,
+            from_state: Some(BookState::Returned),
+            to_state: Some(BookState::Damaged),
+            payload: Some(serde_json::json!({ "note": "封面破损", "reserved_for": "songsong" })),
+            operate_at: time::OffsetDateTime::UNIX_EPOCH,
+            revertible: false,
+        };
+        l.redact();
+        assert_eq!(
+            l.payload,
+            Some(serde_json::json!({ "reserved_for": "songsong" }))
+        );
+    }
 }
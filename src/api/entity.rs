@@ -1,3 +1,4 @@
+use crate::api::auth::{Role, UserSession};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -21,7 +22,9 @@ pub enum BookState {
     Available,
     Borrowed,
     Returned,
+    Reserved,
     Lost,
+    Damaged,
     Deleted,
     Unknown,
 }
@@ -31,7 +34,9 @@ impl BookState {
             BookState::Available => "可借阅".to_string(),
             BookState::Borrowed => "已借出".to_string(),
             BookState::Returned => "已归还".to_string(),
+            BookState::Reserved => "预约保留中".to_string(),
             BookState::Lost => "遗失".to_string(),
+            BookState::Damaged => "损坏".to_string(),
             BookState::Deleted => "已删除".to_string(),
             BookState::Unknown => "未知".to_string(),
         }
@@ -41,11 +46,38 @@ impl BookState {
             "可借阅" => BookState::Available,
             "已借出" => BookState::Borrowed,
             "已归还" => BookState::Returned,
+            "预约保留中" => BookState::Reserved,
             "遗失" => BookState::Lost,
+            "损坏" => BookState::Damaged,
             "已删除" => BookState::Deleted,
             _ => BookState::Unknown,
         }
     }
+    // 与数据库中 books.state 的取值一致，用于筛选条件
+    pub fn code(&self) -> &'static str {
+        match self {
+            BookState::Available => "available",
+            BookState::Borrowed => "borrowed",
+            BookState::Returned => "returned",
+            BookState::Reserved => "reserved",
+            BookState::Lost => "lost",
+            BookState::Damaged => "damaged",
+            BookState::Deleted => "deleted",
+            BookState::Unknown => "unknown",
+        }
+    }
+    pub fn from_code(s: &str) -> Self {
+        match s {
+            "available" => BookState::Available,
+            "borrowed" => BookState::Borrowed,
+            "returned" => BookState::Returned,
+            "reserved" => BookState::Reserved,
+            "lost" => BookState::Lost,
+            "damaged" => BookState::Damaged,
+            "deleted" => BookState::Deleted,
+            _ => BookState::Unknown,
+        }
+    }
 }
 #[cfg(feature = "ssr")]
 impl From<crate::backend::books::BookStateModel> for BookState {
@@ -54,7 +86,9 @@ impl From<crate::backend::books::BookStateModel> for BookState {
             crate::backend::books::BookStateModel::Available => BookState::Available,
             crate::backend::books::BookStateModel::Borrowed => BookState::Borrowed,
             crate::backend::books::BookStateModel::Returned => BookState::Returned,
+            crate::backend::books::BookStateModel::Reserved => BookState::Reserved,
             crate::backend::books::BookStateModel::Lost => BookState::Lost,
+            crate::backend::books::BookStateModel::Damaged => BookState::Damaged,
             crate::backend::books::BookStateModel::Deleted => BookState::Deleted,
             crate::backend::books::BookStateModel::Unknown => BookState::Unknown,
         }
@@ -66,8 +100,370 @@ impl From<&crate::backend::books::BookStateModel> for BookState {
         value.clone().into()
     }
 }
+#[cfg(feature = "ssr")]
+impl From<BookState> for crate::backend::books::BookStateModel {
+    fn from(value: BookState) -> Self {
+        match value {
+            BookState::Available => crate::backend::books::BookStateModel::Available,
+            BookState::Borrowed => crate::backend::books::BookStateModel::Borrowed,
+            BookState::Returned => crate::backend::books::BookStateModel::Returned,
+            BookState::Reserved => crate::backend::books::BookStateModel::Reserved,
+            BookState::Lost => crate::backend::books::BookStateModel::Lost,
+            BookState::Damaged => crate::backend::books::BookStateModel::Damaged,
+            BookState::Deleted => crate::backend::books::BookStateModel::Deleted,
+            BookState::Unknown => crate::backend::books::BookStateModel::Unknown,
+        }
+    }
+}
 impl fmt::Display for BookState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.to_string())
     }
 }
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum BookAction {
+    Borrow,
+    Return,
+    Renew,
+    Confirm,
+    Lost,
+    // 归还时发现书籍损坏
+    Damage,
+    Reset,
+    Delete,
+}
+
+// 操作被拒绝的原因
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum ActionDenied {
+    InvalidState(BookState),
+    NotLoggedIn,
+    NotBorrower,
+    NotReservedFor,
+    NotAdmin,
+}
+
+impl BookAction {
+    // 界面上按钮展示的顺序
+    pub const ALL: [BookAction; 8] = [
+        BookAction::Borrow,
+        BookAction::Return,
+        BookAction::Renew,
+        BookAction::Confirm,
+        BookAction::Reset,
+        BookAction::Lost,
+        BookAction::Damage,
+        BookAction::Delete,
+    ];
+
+    pub fn to_string(&self) -> String {
+        match self {
+            BookAction::Borrow => "借阅".to_string(),
+            BookAction::Return => "归还".to_string(),
+            BookAction::Renew => "续借".to_string(),
+            BookAction::Confirm => "确认归还".to_string(),
+            BookAction::Lost => "标记遗失".to_string(),
+            BookAction::Damage => "标记损坏".to_string(),
+            BookAction::Reset => "重置".to_string(),
+            BookAction::Delete => "删除".to_string(),
+        }
+    }
+
+    // 状态转移表，不允许的转移返回 None
+    pub fn next_state(&self, from: &BookState) -> Option<BookState> {
+        match (self, from) {
+            (BookAction::Borrow, BookState::Available | BookState::Reserved) => {
+                Some(BookState::Borrowed)
+            }
+            (BookAction::Return, BookState::Borrowed) => Some(BookState::Returned),
+            (BookAction::Renew, BookState::Borrowed) => Some(BookState::Borrowed),
+            (BookAction::Confirm, BookState::Returned) => Some(BookState::Available),
+            (
+                BookAction::Lost,
+                BookState::Available
+                | BookState::Borrowed
+                | BookState::Returned
+                | BookState::Reserved,
+            ) => Some(BookState::Lost),
+            (BookAction::Damage, BookState::Returned) => Some(BookState::Damaged),
+            (BookAction::Reset, BookState::Lost | BookState::Damaged | BookState::Unknown) => {
+                Some(BookState::Available)
+            }
+            (BookAction::Delete, s) if *s != BookState::Deleted => Some(BookState::Deleted),
+            _ => None,
+        }
+    }
+
+    // 前端展示按钮和后端执行操作共用这一份规则，holder 是书籍当前的借阅人，或者书籍为之保留的预约人
+    pub fn check(
+        &self,
+        state: &BookState,
+        holder: &str,
+        uid: &str,
+        role: &Role,
+    ) -> Result<BookState, ActionDenied> {
+        let next = self
+            .next_state(state)
+            .ok_or(ActionDenied::InvalidState(state.clone()))?;
+        if uid == "" {
+            return Err(ActionDenied::NotLoggedIn);
+        }
+        match self {
+            BookAction::Borrow => {
+                // 预约保留期间只有预约人可以借阅
+                if *state == BookState::Reserved && uid != holder {
+                    return Err(ActionDenied::NotReservedFor);
+                }
+            }
+            BookAction::Return | BookAction::Renew => {
+                if uid != holder {
+                    return Err(ActionDenied::NotBorrower);
+                }
+            }
+            _ => {
+                if role != &Role::Admin {
+                    return Err(ActionDenied::NotAdmin);
+                }
+            }
+        }
+        Ok(next)
+    }
+
+    pub fn permitted(
+        state: &BookState,
+        holder: &str,
+        current_user: &Option<UserSession>,
+    ) -> Vec<BookAction> {
+        let uid = current_user
+            .as_ref()
+            .map(|u| u.uid.clone())
+            .unwrap_or("".to_string());
+        let role = current_user
+            .as_ref()
+            .map(|u| u.role.clone())
+            .unwrap_or(Role::User);
+        BookAction::ALL
+            .iter()
+            .filter(|a| a.check(state, holder, &uid, &role).is_ok())
+            .cloned()
+            .collect()
+    }
+}
+impl fmt::Display for BookAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_string())
+    }
+}
+
+impl fmt::Display for ActionDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionDenied::InvalidState(s) => write!(f, "书籍当前状态为「{}」", s),
+            ActionDenied::NotLoggedIn => write!(f, "请先登录"),
+            ActionDenied::NotBorrower => write!(f, "只有借阅人才能操作"),
+            ActionDenied::NotReservedFor => write!(f, "这本书正在为预约的同事保留"),
+            ActionDenied::NotAdmin => write!(f, "需要管理员权限"),
+        }
+    }
+}
+
+// 操作记录的类型，界面上的文字由前端根据类型拼出来
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum ChangeAction {
+    Stored,
+    Borrowed,
+    Renewed,
+    Returned,
+    Confirmed,
+    HoldCancelled,
+    HoldExpired,
+    Lost,
+    Damaged,
+    Reset,
+    Deleted,
+    Edited,
+    Unknown,
+}
+
+impl ChangeAction {
+    pub fn to_string(&self) -> String {
+        match self {
+            ChangeAction::Stored => "入库".to_string(),
+            ChangeAction::Borrowed => "借出书籍".to_string(),
+            ChangeAction::Renewed => "续借书籍".to_string(),
+            ChangeAction::Returned => "归还书籍".to_string(),
+            ChangeAction::Confirmed => "确认书籍已经归还".to_string(),
+            ChangeAction::HoldCancelled => "取消预约".to_string(),
+            ChangeAction::HoldExpired => "预约超时未取书".to_string(),
+            ChangeAction::Lost => "将书籍标记为遗失".to_string(),
+            ChangeAction::Damaged => "将书籍标记为损坏".to_string(),
+            ChangeAction::Reset => "重置书籍状态".to_string(),
+            ChangeAction::Deleted => "删除书籍".to_string(),
+            ChangeAction::Edited => "修改书籍信息".to_string(),
+            ChangeAction::Unknown => "未知操作".to_string(),
+        }
+    }
+}
+impl fmt::Display for ChangeAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_string())
+    }
+}
+impl From<&BookAction> for ChangeAction {
+    fn from(value: &BookAction) -> Self {
+        match value {
+            BookAction::Borrow => ChangeAction::Borrowed,
+            BookAction::Return => ChangeAction::Returned,
+            BookAction::Renew => ChangeAction::Renewed,
+            BookAction::Confirm => ChangeAction::Confirmed,
+            BookAction::Lost => ChangeAction::Lost,
+            BookAction::Damage => ChangeAction::Damaged,
+            BookAction::Reset => ChangeAction::Reset,
+            BookAction::Delete => ChangeAction::Deleted,
+        }
+    }
+}
+#[cfg(feature = "ssr")]
+impl From<crate::backend::books::ChangeActionModel> for ChangeAction {
+    fn from(value: crate::backend::books::ChangeActionModel) -> Self {
+        use crate::backend::books::ChangeActionModel;
+        match value {
+            ChangeActionModel::Stored => ChangeAction::Stored,
+            ChangeActionModel::Borrowed => ChangeAction::Borrowed,
+            ChangeActionModel::Renewed => ChangeAction::Renewed,
+            ChangeActionModel::Returned => ChangeAction::Returned,
+            ChangeActionModel::Confirmed => ChangeAction::Confirmed,
+            ChangeActionModel::HoldCancelled => ChangeAction::HoldCancelled,
+            ChangeActionModel::HoldExpired => ChangeAction::HoldExpired,
+            ChangeActionModel::Lost => ChangeAction::Lost,
+            ChangeActionModel::Damaged => ChangeAction::Damaged,
+            ChangeActionModel::Reset => ChangeAction::Reset,
+            ChangeActionModel::Deleted => ChangeAction::Deleted,
+            ChangeActionModel::Edited => ChangeAction::Edited,
+            ChangeActionModel::Unknown => ChangeAction::Unknown,
+        }
+    }
+}
+#[cfg(feature = "ssr")]
+impl From<ChangeAction> for crate::backend::books::ChangeActionModel {
+    fn from(value: ChangeAction) -> Self {
+        use crate::backend::books::ChangeActionModel;
+        match value {
+            ChangeAction::Stored => ChangeActionModel::Stored,
+            ChangeAction::Borrowed => ChangeActionModel::Borrowed,
+            ChangeAction::Renewed => ChangeActionModel::Renewed,
+            ChangeAction::Returned => ChangeActionModel::Returned,
+            ChangeAction::Confirmed => ChangeActionModel::Confirmed,
+            ChangeAction::HoldCancelled => ChangeActionModel::HoldCancelled,
+            ChangeAction::HoldExpired => ChangeActionModel::HoldExpired,
+            ChangeAction::Lost => ChangeActionModel::Lost,
+            ChangeAction::Damaged => ChangeActionModel::Damaged,
+            ChangeAction::Reset => ChangeActionModel::Reset,
+            ChangeAction::Deleted => ChangeActionModel::Deleted,
+            ChangeAction::Edited => ChangeActionModel::Edited,
+            ChangeAction::Unknown => ChangeActionModel::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transition() {
+        let borrow = BookAction::Borrow;
+        assert_eq!(
+            Ok(BookState::Borrowed),
+            borrow.check(&BookState::Available, "", "usera", &Role::User)
+        );
+        assert_eq!(
+            Err(ActionDenied::InvalidState(BookState::Borrowed)),
+            borrow.check(&BookState::Borrowed, "usera", "user01", &Role::User)
+        );
+        assert_eq!(
+            Err(ActionDenied::NotReservedFor),
+            borrow.check(&BookState::Reserved, "usera", "user01", &Role::User)
+        );
+        assert_eq!(
+            Ok(BookState::Borrowed),
+            borrow.check(&BookState::Reserved, "usera", "usera", &Role::User)
+        );
+        assert_eq!(
+            Err(ActionDenied::NotLoggedIn),
+            borrow.check(&BookState::Available, "", "", &Role::User)
+        );
+
+        let ret = BookAction::Return;
+        assert_eq!(
+            Ok(BookState::Returned),
+            ret.check(&BookState::Borrowed, "usera", "usera", &Role::User)
+        );
+        assert_eq!(
+            Err(ActionDenied::NotBorrower),
+            ret.check(&BookState::Borrowed, "usera", "user01", &Role::Admin)
+        );
+
+        assert_eq!(
+            Ok(BookState::Borrowed),
+            BookAction::Renew.check(&BookState::Borrowed, "usera", "usera", &Role::User)
+        );
+        assert_eq!(
+            Err(ActionDenied::NotBorrower),
+            BookAction::Renew.check(&BookState::Borrowed, "usera", "user01", &Role::User)
+        );
+
+        assert_eq!(
+            Err(ActionDenied::NotAdmin),
+            BookAction::Confirm.check(&BookState::Returned, "usera", "usera", &Role::User)
+        );
+        assert_eq!(
+            Ok(BookState::Available),
+            BookAction::Confirm.check(&BookState::Returned, "usera", "admin", &Role::Admin)
+        );
+        assert_eq!(None, BookAction::Delete.next_state(&BookState::Deleted));
+
+        assert_eq!(
+            Ok(BookState::Damaged),
+            BookAction::Damage.check(&BookState::Returned, "usera", "admin", &Role::Admin)
+        );
+        assert_eq!(None, BookAction::Damage.next_state(&BookState::Available));
+        assert_eq!(
+            Some(BookState::Available),
+            BookAction::Reset.next_state(&BookState::Damaged)
+        );
+        assert_eq!(
+            Some(BookState::Available),
+            BookAction::Reset.next_state(&BookState::Lost)
+        );
+    }
+
+    #[test]
+    fn change_action() {
+        for a in BookAction::ALL.iter() {
+            assert_ne!(ChangeAction::Unknown, ChangeAction::from(a));
+        }
+        assert_eq!(
+            ChangeAction::Confirmed,
+            ChangeAction::from(&BookAction::Confirm)
+        );
+    }
+
+    #[test]
+    fn permitted() {
+        let admin = Some(UserSession {
+            uid: "admin".to_string(),
+            display_name: "admin".to_string(),
+            role: Role::Admin,
+        });
+        assert_eq!(
+            vec![BookAction::Borrow, BookAction::Lost, BookAction::Delete],
+            BookAction::permitted(&BookState::Available, "", &admin)
+        );
+        assert_eq!(
+            Vec::<BookAction>::new(),
+            BookAction::permitted(&BookState::Available, "", &None)
+        );
+    }
+}
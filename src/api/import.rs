@@ -0,0 +1,208 @@
+use crate::api::auth::{get_account, Role};
+use leptos::ServerFnError::{Request, ServerError};
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+pub fn register_server_functions() {
+    let _ = PreviewImport::register();
+    let _ = CommitImport::register();
+}
+
+// 导入文件中各字段所在的列，值为表头名称，为空表示文件中没有这一列
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ColumnMapping {
+    pub isbn: String,
+    pub barcode: String,
+    pub title: String,
+    pub location: String,
+    pub donor: String,
+}
+
+impl ColumnMapping {
+    // 没有指定列映射时按常见的表头名称猜测
+    pub fn detect(headers: &[String]) -> Self {
+        let find = |names: &[&str]| {
+            headers
+                .iter()
+                .find(|h| {
+                    let h = h.trim().to_lowercase();
+                    names.iter().any(|n| h == *n)
+                })
+                .cloned()
+                .unwrap_or_default()
+        };
+        Self {
+            isbn: find(&["isbn", "isbn13", "isbn-13", "书号"]),
+            barcode: find(&["barcode", "条码", "馆藏条码"]),
+            title: find(&["title", "书名", "题名"]),
+            location: find(&["location", "位置", "馆藏位置", "存放位置"]),
+            donor: find(&["donor", "捐赠人", "捐赠者"]),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.isbn.is_empty() && self.title.is_empty()
+    }
+}
+
+// 导入预览和导入结果中每一行的处理情况
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum ImportStatus {
+    // 新书目，检索服务查到了书目信息
+    New,
+    // 已有书目，作为新副本入库
+    Copy,
+    // 检索服务查不到，按文件中的书名手工编目
+    Manual,
+    // 条码重复，跳过
+    Duplicate,
+    // 无法入库，跳过
+    Failed,
+    // 已经入库
+    Stored,
+}
+
+impl ImportStatus {
+    pub fn to_string(&self) -> String {
+        match self {
+            ImportStatus::New => "新书目".to_string(),
+            ImportStatus::Copy => "新增副本".to_string(),
+            ImportStatus::Manual => "手工编目".to_string(),
+            ImportStatus::Duplicate => "重复".to_string(),
+            ImportStatus::Failed => "失败".to_string(),
+            ImportStatus::Stored => "已入库".to_string(),
+        }
+    }
+
+    // 提交导入时会入库的行
+    pub fn importable(&self) -> bool {
+        matches!(
+            self,
+            ImportStatus::New | ImportStatus::Copy | ImportStatus::Manual
+        )
+    }
+}
+
+impl std::fmt::Display for ImportStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_string())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImportRowUI {
+    // 文件中的行号，表头为第 1 行
+    pub line: usize,
+    pub isbn: String,
+    pub barcode: String,
+    pub title: String,
+    pub status: ImportStatus,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImportResultUI {
+    pub headers: Vec<String>,
+    pub mapping: ColumnMapping,
+    pub rows: Vec<ImportRowUI>,
+}
+
+// 预览导入结果，不写入数据库。
+// content 为浏览器读取文件得到的 data URL，按 file_name 的扩展名识别 CSV 或 XLSX
+#[server(PreviewImport, "/api")]
+pub async fn preview_import(
+    cx: Scope,
+    file_name: String,
+    content: String,
+    isbn_column: Option<String>,
+    barcode_column: Option<String>,
+    title_column: Option<String>,
+    location_column: Option<String>,
+    donor_column: Option<String>,
+) -> Result<ImportResultUI, ServerFnError> {
+    let mapping = ColumnMapping {
+        isbn: isbn_column.unwrap_or_default(),
+        barcode: barcode_column.unwrap_or_default(),
+        title: title_column.unwrap_or_default(),
+        location: location_column.unwrap_or_default(),
+        donor: donor_column.unwrap_or_default(),
+    };
+    run_import(cx, &file_name, &content, mapping, false).await
+}
+
+// 提交导入，所有可以入库的行在一个事务中入库
+#[server(CommitImport, "/api")]
+pub async fn commit_import(
+    cx: Scope,
+    file_name: String,
+    content: String,
+    isbn_column: Option<String>,
+    barcode_column: Option<String>,
+    title_column: Option<String>,
+    location_column: Option<String>,
+    donor_column: Option<String>,
+) -> Result<ImportResultUI, ServerFnError> {
+    let mapping = ColumnMapping {
+        isbn: isbn_column.unwrap_or_default(),
+        barcode: barcode_column.unwrap_or_default(),
+        title: title_column.unwrap_or_default(),
+        location: location_column.unwrap_or_default(),
+        donor: donor_column.unwrap_or_default(),
+    };
+    run_import(cx, &file_name, &content, mapping, true).await
+}
+
+#[cfg(feature = "ssr")]
+async fn run_import(
+    cx: Scope,
+    file_name: &str,
+    content: &str,
+    mapping: ColumnMapping,
+    commit: bool,
+) -> Result<ImportResultUI, ServerFnError> {
+    use crate::backend::import;
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not logged in".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let sheet =
+        import::read_data_url(file_name, content).map_err(|e| Request(format!("{:#}", e)))?;
+    let mapping = if mapping.is_empty() {
+        ColumnMapping::detect(&sheet.headers)
+    } else {
+        mapping
+    };
+    let rows = import::map_rows(&sheet, &mapping).map_err(|e| Request(format!("{:#}", e)))?;
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let rows = bms
+        .import(&rows, &ac.uid, commit)
+        .await
+        .map_err(|e| ServerError(format!("{:#}", e)))?;
+    Ok(ImportResultUI {
+        headers: sheet.headers,
+        mapping,
+        rows,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detect_mapping() {
+        let headers: Vec<String> = ["序号", "ISBN", "书名", "馆藏条码", "捐赠人"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        let mapping = ColumnMapping::detect(&headers);
+        assert_eq!(mapping.isbn, "ISBN");
+        assert_eq!(mapping.title, "书名");
+        assert_eq!(mapping.barcode, "馆藏条码");
+        assert_eq!(mapping.donor, "捐赠人");
+        assert_eq!(mapping.location, "");
+    }
+}
@@ -0,0 +1,146 @@
+// ISBN 校验和规范化，前端表单和后端入库共用
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum IsbnError {
+    Empty,
+    InvalidChar(char),
+    InvalidLength(usize),
+    Checksum,
+}
+
+impl fmt::Display for IsbnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsbnError::Empty => write!(f, "请输入 ISBN"),
+            IsbnError::InvalidChar(c) => write!(f, "ISBN 中不能包含「{}」", c),
+            IsbnError::InvalidLength(n) => write!(f, "ISBN 应为 10 位或 13 位，实际为 {} 位", n),
+            IsbnError::Checksum => write!(f, "ISBN 校验位不正确，请检查是否输错"),
+        }
+    }
+}
+
+impl std::error::Error for IsbnError {}
+
+// 去掉 ISBN 前缀、空格和连字符，校验后统一转换为 ISBN-13
+pub fn normalize(input: &str) -> Result<String, IsbnError> {
+    let s = input.trim();
+    let s = s
+        .strip_prefix("ISBN")
+        .or(s.strip_prefix("isbn"))
+        .unwrap_or(s)
+        .trim_start_matches(|c| c == ':' || c == '：' || c == ' ');
+    let mut digits = String::new();
+    for c in s.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            'x' | 'X' => digits.push('X'),
+            '-' | ' ' | '\u{2010}' | '\u{2013}' => {}
+            c => return Err(IsbnError::InvalidChar(c)),
+        }
+    }
+    match digits.len() {
+        0 => Err(IsbnError::Empty),
+        10 => {
+            if !digits[..9].chars().all(|c| c.is_ascii_digit()) {
+                return Err(IsbnError::InvalidChar('X'));
+            }
+            if check_digit_10(&digits[..9]) != digits.chars().last().unwrap() {
+                return Err(IsbnError::Checksum);
+            }
+            Ok(to_isbn13(&digits))
+        }
+        13 => {
+            if digits.contains('X') {
+                return Err(IsbnError::InvalidChar('X'));
+            }
+            if check_digit_13(&digits[..12]) != digits.chars().last().unwrap() {
+                return Err(IsbnError::Checksum);
+            }
+            Ok(digits)
+        }
+        n => Err(IsbnError::InvalidLength(n)),
+    }
+}
+
+// ISBN-13 对应的 ISBN-10，只有 978 开头的才有
+pub fn to_isbn10(isbn13: &str) -> Option<String> {
+    let body = isbn13.strip_prefix("978")?;
+    if body.len() != 10 {
+        return None;
+    }
+    let body = &body[..9];
+    Some(format!("{}{}", body, check_digit_10(body)))
+}
+
+// 同一本书可能出现的两种写法，用于搜索
+pub fn variants(input: &str) -> Vec<String> {
+    match normalize(input) {
+        Ok(isbn13) => {
+            let mut v = vec![isbn13.clone()];
+            v.extend(to_isbn10(&isbn13));
+            v
+        }
+        Err(_) => vec![],
+    }
+}
+
+fn to_isbn13(isbn10: &str) -> String {
+    let body = format!("978{}", &isbn10[..9]);
+    let check = check_digit_13(&body);
+    format!("{}{}", body, check)
+}
+
+fn check_digit_10(body: &str) -> char {
+    let sum: u32 = body
+        .chars()
+        .zip((2..=10).rev())
+        .map(|(c, w)| c.to_digit(10).unwrap() * w)
+        .sum();
+    match (11 - sum % 11) % 11 {
+        10 => 'X',
+        d => char::from_digit(d, 10).unwrap(),
+    }
+}
+
+fn check_digit_13(body: &str) -> char {
+    let sum: u32 = body
+        .chars()
+        .enumerate()
+        .map(|(i, c)| c.to_digit(10).unwrap() * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    char::from_digit((10 - sum % 10) % 10, 10).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalize_isbn() {
+        assert_eq!(normalize("9787302547648"), Ok("9787302547648".to_string()));
+        assert_eq!(
+            normalize(" ISBN: 978-7-302-54764-8 "),
+            Ok("9787302547648".to_string())
+        );
+        assert_eq!(normalize("0-262-03384-4"), Ok("9780262033848".to_string()));
+        assert_eq!(normalize("080442957X"), Ok("9780804429573".to_string()));
+        assert_eq!(normalize("9787302547649"), Err(IsbnError::Checksum));
+        assert_eq!(normalize("0262033845"), Err(IsbnError::Checksum));
+        assert_eq!(normalize("97873025476"), Err(IsbnError::InvalidLength(11)));
+        assert_eq!(normalize("978730254764a"), Err(IsbnError::InvalidChar('a')));
+        assert_eq!(normalize("  "), Err(IsbnError::Empty));
+    }
+
+    #[test]
+    fn isbn10() {
+        assert_eq!(to_isbn10("9780262033848"), Some("0262033844".to_string()));
+        assert_eq!(to_isbn10("9790000000001"), None);
+        assert_eq!(
+            variants("0262033844"),
+            vec!["9780262033848".to_string(), "0262033844".to_string()]
+        );
+        assert!(variants("运筹学").is_empty());
+    }
+}
@@ -1,9 +1,17 @@
 pub mod auth;
 pub mod books;
 pub mod entity;
+pub mod import;
+pub mod isbn;
+pub mod my;
+pub mod search;
+pub mod stats;
 
 #[cfg(feature = "ssr")]
 pub fn register_server_functions() {
     let _ = books::register_server_functions();
     let _ = auth::register_server_functions();
+    let _ = import::register_server_functions();
+    let _ = my::register_server_functions();
+    let _ = stats::register_server_functions();
 }
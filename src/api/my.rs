@@ -0,0 +1,142 @@
+use crate::api::auth::get_account;
+use crate::api::books::BookUI;
+use crate::api::entity::ChangeAction;
+use leptos::ServerFnError::{Request, ServerError};
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+pub fn register_server_functions() {
+    let _ = MyBooks::register();
+    let _ = MyHistory::register();
+}
+
+// 个人主页上当前借阅相关的书籍
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MyBooksUI {
+    // 正在借阅
+    pub borrowed: Vec<BookUI>,
+    // 已归还，等待管理员确认
+    pub returned: Vec<BookUI>,
+    pub holds: Vec<MyHoldUI>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MyHoldUI {
+    pub id: i64,
+    pub title: String,
+    pub thumbnail: String,
+    pub book_id: Option<i64>,
+    // 在队列中的位置，从 1 开始
+    pub position: i64,
+    // 已经有书籍为自己保留，等待取书
+    pub ready: bool,
+    pub created_at: time::OffsetDateTime,
+    pub expires_at: Option<time::OffsetDateTime>,
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::backend::my::MyHoldModel> for MyHoldUI {
+    fn from(value: crate::backend::my::MyHoldModel) -> Self {
+        Self {
+            id: value.id,
+            title: value.title,
+            thumbnail: value.thumbnail.unwrap_or("".to_string()),
+            book_id: value.book_id,
+            position: value.position,
+            ready: value.state == crate::backend::holds::HoldStateModel::Ready,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BorrowRecordUI {
+    pub id: i64,
+    pub book_id: i64,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub borrowed_at: time::OffsetDateTime,
+    // 还在借阅中时为空
+    pub ended_at: Option<time::OffsetDateTime>,
+    pub ended_by: Option<ChangeAction>,
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::backend::my::BorrowRecordModel> for BorrowRecordUI {
+    fn from(value: crate::backend::my::BorrowRecordModel) -> Self {
+        Self {
+            id: value.id,
+            book_id: value.book_id,
+            title: value.title,
+            authors: value.authors,
+            borrowed_at: value.borrowed_at,
+            ended_at: value.ended_at,
+            ended_by: value.ended_by.map(ChangeAction::from),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BorrowHistoryUI {
+    pub records: Vec<BorrowRecordUI>,
+    pub total: i64,
+}
+
+// 当前登录用户借着的书、等待确认归还的书和预约
+#[server(MyBooks, "/api")]
+pub async fn my_books(cx: Scope) -> Result<MyBooksUI, ServerFnError> {
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let borrowed = bms
+        .borrowed_by(&ac.uid)
+        .await
+        .map_err(|e| ServerError(e.to_string()))?;
+    let returned = bms
+        .returned_by(&ac.uid)
+        .await
+        .map_err(|e| ServerError(e.to_string()))?;
+    let holds = bms
+        .holds_of(&ac.uid)
+        .await
+        .map_err(|e| ServerError(e.to_string()))?;
+    let ac = Some(ac);
+    let bind = |b: crate::backend::books::BookModel| {
+        let mut b = BookUI::from(b);
+        b.bind_role(&ac);
+        b
+    };
+
+    Ok(MyBooksUI {
+        borrowed: borrowed.into_iter().map(bind).collect(),
+        returned: returned.into_iter().map(bind).collect(),
+        holds: holds.into_iter().map(MyHoldUI::from).collect(),
+    })
+}
+
+// 当前登录用户的借阅历史，最近借出的在前
+#[server(MyHistory, "/api")]
+pub async fn my_history(
+    cx: Scope,
+    offset: Option<i64>,
+    limit: Option<i64>,
+) -> Result<BorrowHistoryUI, ServerFnError> {
+    let limit = limit.unwrap_or(10);
+    let offset = offset.unwrap_or(0);
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let (records, total) = bms
+        .borrowing_history(&ac.uid, &limit, &offset)
+        .await
+        .map_err(|e| ServerError(e.to_string()))?;
+
+    Ok(BorrowHistoryUI {
+        records: records.into_iter().map(BorrowRecordUI::from).collect(),
+        total,
+    })
+}
@@ -0,0 +1,420 @@
+use crate::api::entity::BookState;
+use serde::{Deserialize, Serialize};
+
+// 图书列表的排序方式
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum BookSort {
+    // 按搜索相关度，没有搜索词时等同于 Newest
+    Relevance,
+    Newest,
+    // 按书名拼音
+    Title,
+    MostBorrowed,
+}
+
+impl BookSort {
+    pub fn to_string(&self) -> String {
+        match self {
+            BookSort::Relevance => "相关度".to_string(),
+            BookSort::Newest => "最新入库".to_string(),
+            BookSort::Title => "书名".to_string(),
+            BookSort::MostBorrowed => "借阅最多".to_string(),
+        }
+    }
+    pub fn code(&self) -> &'static str {
+        match self {
+            BookSort::Relevance => "relevance",
+            BookSort::Newest => "newest",
+            BookSort::Title => "title",
+            BookSort::MostBorrowed => "borrowed",
+        }
+    }
+    pub fn from_code(s: &str) -> Option<Self> {
+        match s {
+            "relevance" => Some(BookSort::Relevance),
+            "newest" => Some(BookSort::Newest),
+            "title" => Some(BookSort::Title),
+            "borrowed" => Some(BookSort::MostBorrowed),
+            _ => None,
+        }
+    }
+    pub fn all() -> [BookSort; 4] {
+        [
+            BookSort::Relevance,
+            BookSort::Newest,
+            BookSort::Title,
+            BookSort::MostBorrowed,
+        ]
+    }
+}
+
+// 可以筛选的字段
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum FacetField {
+    State,
+    Author,
+    Publisher,
+    Year,
+    Tag,
+    Location,
+    Borrower,
+}
+
+impl FacetField {
+    pub fn to_string(&self) -> String {
+        match self {
+            FacetField::State => "状态".to_string(),
+            FacetField::Author => "作者".to_string(),
+            FacetField::Publisher => "出版社".to_string(),
+            FacetField::Year => "出版年份".to_string(),
+            FacetField::Tag => "标签".to_string(),
+            FacetField::Location => "位置".to_string(),
+            FacetField::Borrower => "借阅人".to_string(),
+        }
+    }
+}
+
+// 图书列表的查询条件，所有条件同时满足
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct BookQuery {
+    pub q: Option<String>,
+    pub state: Option<BookState>,
+    pub author: Option<String>,
+    pub publisher: Option<String>,
+    // 出版年份范围，包含两端
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+    pub tag: Option<String>,
+    pub location: Option<String>,
+    // 借阅人账号或姓名
+    pub borrower: Option<String>,
+    pub sort: Option<BookSort>,
+}
+
+impl BookQuery {
+    // 没有指定排序时，有搜索词按相关度，否则按入库时间
+    pub fn sort(&self) -> BookSort {
+        match (self.sort, &self.q) {
+            (Some(BookSort::Relevance), None) => BookSort::Newest,
+            (Some(s), _) => s,
+            (None, Some(_)) => BookSort::Relevance,
+            (None, None) => BookSort::Newest,
+        }
+    }
+
+    // 按选中的分面值增加筛选条件，出版年份选中的是某一年
+    pub fn with(&self, field: FacetField, value: &str) -> Self {
+        let mut query = self.clone();
+        let value = Some(value.to_string());
+        match field {
+            FacetField::State => query.state = value.map(|v| BookState::from_code(&v)),
+            FacetField::Author => query.author = value,
+            FacetField::Publisher => query.publisher = value,
+            FacetField::Year => {
+                query.year_from = value.and_then(|v| v.parse().ok());
+                query.year_to = query.year_from;
+            }
+            FacetField::Tag => query.tag = value,
+            FacetField::Location => query.location = value,
+            FacetField::Borrower => query.borrower = value,
+        }
+        query
+    }
+
+    pub fn without(&self, field: FacetField) -> Self {
+        let mut query = self.clone();
+        match field {
+            FacetField::State => query.state = None,
+            FacetField::Author => query.author = None,
+            FacetField::Publisher => query.publisher = None,
+            FacetField::Year => {
+                query.year_from = None;
+                query.year_to = None;
+            }
+            FacetField::Tag => query.tag = None,
+            FacetField::Location => query.location = None,
+            FacetField::Borrower => query.borrower = None,
+        }
+        query
+    }
+
+    // 当前生效的筛选条件，用于显示可以逐个去掉的筛选标签
+    pub fn chips(&self) -> Vec<(FacetField, String)> {
+        let year = match (self.year_from, self.year_to) {
+            (Some(f), Some(t)) if f == t => Some(f.to_string()),
+            (Some(f), Some(t)) => Some(format!("{}–{}", f, t)),
+            (Some(f), None) => Some(format!("{} 年以后", f)),
+            (None, Some(t)) => Some(format!("{} 年以前", t)),
+            (None, None) => None,
+        };
+        [
+            (
+                FacetField::State,
+                self.state.as_ref().map(|s| s.to_string()),
+            ),
+            (FacetField::Author, self.author.clone()),
+            (FacetField::Publisher, self.publisher.clone()),
+            (FacetField::Year, year),
+            (FacetField::Tag, self.tag.clone()),
+            (FacetField::Location, self.location.clone()),
+            (FacetField::Borrower, self.borrower.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(f, v)| v.map(|v| (f, v)))
+        .collect()
+    }
+}
+
+// 某个分面值及符合当前条件的数量
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+
+// 在当前查询条件下各字段的取值分布，状态的取值为 BookState::code
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Facets {
+    pub states: Vec<FacetCount>,
+    pub authors: Vec<FacetCount>,
+    pub publishers: Vec<FacetCount>,
+    pub years: Vec<FacetCount>,
+    pub tags: Vec<FacetCount>,
+    pub locations: Vec<FacetCount>,
+}
+
+impl Facets {
+    pub fn groups(&self) -> Vec<(FacetField, Vec<FacetCount>)> {
+        vec![
+            (FacetField::State, self.states.clone()),
+            (FacetField::Author, self.authors.clone()),
+            (FacetField::Publisher, self.publishers.clone()),
+            (FacetField::Year, self.years.clone()),
+            (FacetField::Tag, self.tags.clone()),
+            (FacetField::Location, self.locations.clone()),
+        ]
+    }
+}
+
+// 一段文字，matched 为 true 时是与搜索词匹配的部分
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Fragment {
+    pub text: String,
+    pub matched: bool,
+}
+
+// 搜索结果中需要高亮的字段
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct SearchHighlight {
+    pub title: Vec<Fragment>,
+    pub authors: Vec<Fragment>,
+    // 简介中匹配的片段，没有匹配时为空
+    pub snippet: Vec<Fragment>,
+}
+
+// 中日韩统一表意文字，与迁移中 cjk_split 的范围一致
+pub fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{9fff}' | '\u{f900}'..='\u{faff}')
+}
+
+// 按空白拆分搜索词，统一转成小写
+pub fn terms(q: &str) -> Vec<String> {
+    q.split_whitespace()
+        .map(|t| t.to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// 标出文字中每个字符是否与某个搜索词匹配，不区分大小写
+fn mark(chars: &[char], terms: &[String]) -> Vec<bool> {
+    let lowered: Vec<char> = chars.iter().map(|c| lower(*c)).collect();
+    let mut marks = vec![false; chars.len()];
+    for term in terms {
+        let term: Vec<char> = term.chars().collect();
+        if term.is_empty() || term.len() > lowered.len() {
+            continue;
+        }
+        for i in 0..=lowered.len() - term.len() {
+            if lowered[i..i + term.len()] == term[..] {
+                marks[i..i + term.len()].iter_mut().for_each(|m| *m = true);
+            }
+        }
+    }
+    marks
+}
+
+fn fragments(chars: &[char], marks: &[bool]) -> Vec<Fragment> {
+    let mut result: Vec<Fragment> = vec![];
+    for (c, m) in chars.iter().zip(marks) {
+        match result.last_mut() {
+            Some(f) if f.matched == *m => f.text.push(*c),
+            _ => result.push(Fragment {
+                text: c.to_string(),
+                matched: *m,
+            }),
+        }
+    }
+    result
+}
+
+// 把文字按是否匹配搜索词切成若干段
+pub fn highlight(text: &str, terms: &[String]) -> Vec<Fragment> {
+    let chars: Vec<char> = text.chars().collect();
+    let marks = mark(&chars, terms);
+    fragments(&chars, &marks)
+}
+
+// 把搜索词转成 to_tsquery('simple', ...) 的查询串：
+// 连续的字母数字作为一个词并允许前缀匹配，每个汉字单独作为一个词，
+// 同一个搜索词内的各部分用 <-> 要求相邻，多个搜索词之间用 & 连接
+pub fn tsquery(q: &str) -> Option<String> {
+    let mut parts = vec![];
+    for term in terms(q) {
+        let mut tokens = vec![];
+        let mut word = String::new();
+        for c in term.chars() {
+            if c.is_alphanumeric() && !is_cjk(c) {
+                word.push(c);
+                continue;
+            }
+            if !word.is_empty() {
+                tokens.push(format!("'{}':*", word));
+                word.clear();
+            }
+            if is_cjk(c) {
+                tokens.push(format!("'{}'", c));
+            }
+        }
+        if !word.is_empty() {
+            tokens.push(format!("'{}':*", word));
+        }
+        if !tokens.is_empty() {
+            parts.push(format!("({})", tokens.join(" <-> ")));
+        }
+    }
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(" & "))
+    }
+}
+
+// LIKE 子串匹配用的模式，转义其中的通配符
+pub fn like_pattern(q: &str) -> String {
+    let escaped = q
+        .trim()
+        .to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+// 截取第一个匹配位置附近 width 个字符，没有匹配时返回空
+pub fn snippet(text: &str, terms: &[String], width: usize) -> Vec<Fragment> {
+    let chars: Vec<char> = text.chars().collect();
+    let marks = mark(&chars, terms);
+    let first = match marks.iter().position(|m| *m) {
+        Some(i) => i,
+        None => return vec![],
+    };
+    let start = first.saturating_sub(width / 4);
+    let end = (start + width).min(chars.len());
+    let mut result = fragments(&chars[start..end], &marks[start..end]);
+    let ellipsis = |matched| Fragment {
+        text: "…".to_string(),
+        matched,
+    };
+    if start > 0 {
+        result.insert(0, ellipsis(false));
+    }
+    if end < chars.len() {
+        result.push(ellipsis(false));
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn highlight_terms() {
+        let terms = terms("rust 编程");
+        assert_eq!(terms, vec!["rust", "编程"]);
+        let f = highlight("Rust 编程之道", &terms);
+        assert_eq!(
+            f,
+            vec![
+                Fragment {
+                    text: "Rust".to_string(),
+                    matched: true
+                },
+                Fragment {
+                    text: " ".to_string(),
+                    matched: false
+                },
+                Fragment {
+                    text: "编程".to_string(),
+                    matched: true
+                },
+                Fragment {
+                    text: "之道".to_string(),
+                    matched: false
+                },
+            ]
+        );
+        assert_eq!(highlight("运筹学", &terms).len(), 1);
+    }
+
+    #[test]
+    fn query_chips() {
+        let query = BookQuery::default()
+            .with(FacetField::State, "available")
+            .with(FacetField::Year, "2021")
+            .with(FacetField::Author, "张汉东");
+        assert_eq!(query.state, Some(BookState::Available));
+        assert_eq!((query.year_from, query.year_to), (Some(2021), Some(2021)));
+        assert_eq!(
+            query.chips(),
+            vec![
+                (FacetField::State, "可借阅".to_string()),
+                (FacetField::Author, "张汉东".to_string()),
+                (FacetField::Year, "2021".to_string()),
+            ]
+        );
+        let query = query.without(FacetField::Year);
+        assert_eq!(query.chips().len(), 2);
+        assert_eq!(query.sort(), BookSort::Newest);
+        let query = BookQuery {
+            q: Some("rust".to_string()),
+            ..query
+        };
+        assert_eq!(query.sort(), BookSort::Relevance);
+    }
+
+    #[test]
+    fn build_tsquery() {
+        assert_eq!(
+            tsquery("Rust 编程").unwrap(),
+            "('rust':*) & ('编' <-> '程')"
+        );
+        assert_eq!(tsquery("c++编程").unwrap(), "('c':* <-> '编' <-> '程')");
+        assert_eq!(tsquery("'; drop").unwrap(), "('drop':*)");
+        assert_eq!(tsquery(" !! "), None);
+        assert_eq!(like_pattern("100%_a"), "%100\\%\\_a%");
+    }
+
+    #[test]
+    fn snippet_window() {
+        let text = "本书是在第四版的基础上修订而成的，吸收了广大读者的意见，做了局部调整和修改。";
+        let s = snippet(text, &terms("读者"), 12);
+        assert_eq!(s.first().unwrap().text, "…");
+        assert!(s.iter().any(|f| f.matched && f.text == "读者"));
+        assert!(snippet(text, &terms("rust"), 12).is_empty());
+    }
+}
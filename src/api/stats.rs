@@ -0,0 +1,124 @@
+use crate::api::auth::{get_account, Role};
+use crate::api::entity::BookState;
+use leptos::ServerFnError::{Request, ServerError};
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+pub fn register_server_functions() {
+    let _ = LibraryStats::register();
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateCountUI {
+    pub state: BookState,
+    pub count: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TopTitleUI {
+    pub title_id: i64,
+    pub title: String,
+    pub book_id: i64,
+    pub borrows: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TopBorrowerUI {
+    pub uid: String,
+    pub name: String,
+    pub borrows: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MonthCountUI {
+    // 格式为 2023-01
+    pub month: String,
+    pub count: i64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LibraryStatsUI {
+    pub states: Vec<StateCountUI>,
+    pub awaiting_confirmation: i64,
+    pub overdue: i64,
+    pub top_titles: Vec<TopTitleUI>,
+    pub top_borrowers: Vec<TopBorrowerUI>,
+    pub acquisitions: Vec<MonthCountUI>,
+    pub borrows: Vec<MonthCountUI>,
+}
+
+impl LibraryStatsUI {
+    // 未删除的书籍总数
+    pub fn total(&self) -> i64 {
+        self.states.iter().map(|s| s.count).sum()
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::backend::stats::LibraryStatsModel> for LibraryStatsUI {
+    fn from(value: crate::backend::stats::LibraryStatsModel) -> Self {
+        let months = |m: Vec<crate::backend::stats::MonthCountModel>| {
+            m.into_iter()
+                .map(|m| MonthCountUI {
+                    month: m.month,
+                    count: m.count,
+                })
+                .collect()
+        };
+        Self {
+            states: value
+                .states
+                .into_iter()
+                .map(|s| StateCountUI {
+                    state: s.state.into(),
+                    count: s.count,
+                })
+                .collect(),
+            awaiting_confirmation: value.awaiting_confirmation,
+            overdue: value.overdue,
+            top_titles: value
+                .top_titles
+                .into_iter()
+                .map(|t| TopTitleUI {
+                    title_id: t.title_id,
+                    title: t.title,
+                    book_id: t.book_id,
+                    borrows: t.borrows,
+                })
+                .collect(),
+            top_borrowers: value
+                .top_borrowers
+                .into_iter()
+                .map(|b| TopBorrowerUI {
+                    uid: b.uid,
+                    name: b.name,
+                    borrows: b.borrows,
+                })
+                .collect(),
+            acquisitions: months(value.acquisitions),
+            borrows: months(value.borrows),
+        }
+    }
+}
+
+// 管理员统计页面，months 为按月统计的月数，默认最近 12 个月
+#[server(LibraryStats, "/api")]
+pub async fn library_stats(
+    cx: Scope,
+    months: Option<i32>,
+) -> Result<LibraryStatsUI, ServerFnError> {
+    let months = months.unwrap_or(12).clamp(1, 60);
+    let ac = get_account(cx)
+        .await?
+        .ok_or(Request("Not login".to_string()))?;
+    if ac.role != Role::Admin {
+        return Err(Request("Not admin".to_string()));
+    }
+    let bms = crate::backend::books::BookMS::from_scope(cx);
+    let stats = bms
+        .stats(&10, &months)
+        .await
+        .map_err(|e| ServerError(e.to_string()))?;
+    Ok(stats.into())
+}
@@ -25,7 +25,25 @@ pub async fn account_info_from_cookies(cx: leptos::Scope) -> Option<AccountInfo>
         None => return None,
     };
     debug!("request parts: {:?}", &rp);
-    let h = match rp.headers.get(http::header::COOKIE) {
+    let conf = match use_context::<Config>(cx) {
+        Some(rp) => rp,
+        None => return None,
+    };
+    debug!("conf: {:?}", &conf);
+    let pool = match crate::backend::db::from_scope(cx) {
+        Ok(pool) => pool,
+        Err(_) => return None,
+    };
+    account_info_from_headers(&rp.headers, &conf, &pool).await
+}
+
+// 不经过 server function 的 axum 路由（例如导出）用请求头中的 cookie 识别用户
+pub async fn account_info_from_headers(
+    headers: &http::HeaderMap,
+    conf: &Config,
+    pool: &PgPool,
+) -> Option<AccountInfo> {
+    let h = match headers.get(http::header::COOKIE) {
         Some(r) => String::from_utf8_lossy(r.as_bytes()).to_string(),
         None => return None,
     };
@@ -44,21 +62,12 @@ pub async fn account_info_from_cookies(cx: leptos::Scope) -> Option<AccountInfo>
     .to_string();
     debug!("token: {:?}", &token);
 
-    let conf = match use_context::<Config>(cx) {
-        Some(rp) => rp,
-        None => return None,
-    };
-    debug!("conf: {:?}", &conf);
-    let pool = match crate::backend::db::from_scope(cx) {
-        Ok(pool) => pool,
-        Err(_) => return None,
-    };
     match decode::<Claims>(
         &token,
         &DecodingKey::from_secret(conf.session_secret.as_bytes()),
         &Validation::default(),
     ) {
-        Ok(token) => match get_account_by_id(&pool, &token.claims.sub).await {
+        Ok(token) => match get_account_by_id(pool, &token.claims.sub).await {
             Ok(ac) => Some(ac),
             Err(_) => {
                 debug!("数据库查找用户失败");
@@ -1,3 +1,10 @@
+use crate::api::auth::{Role, UserSession};
+use crate::api::entity::{ActionDenied, BookAction, BookState, ChangeAction};
+use crate::api::isbn::{self, IsbnError};
+use crate::backend::conf::{Config, IsbnProviderConf, LoanPolicy};
+use crate::backend::holds::HoldModel;
+use crate::backend::loans::LoanDenied;
+use crate::backend::{holds, isbn_cache, loans, pinyin};
 use anyhow::Result;
 use leptos_reactive::use_context;
 #[cfg(feature = "ssr")]
@@ -5,19 +12,27 @@ use sqlx::PgPool;
 use std::sync::Arc;
 
 #[cfg(feature = "ssr")]
-pub async fn init(pg_pool: &PgPool, api_key: &str) -> Result<BookMS> {
-    let bms = BookMS::new(pg_pool, api_key);
+pub async fn init(pg_pool: &PgPool, conf: &Config) -> Result<BookMS> {
+    let bms = BookMS::new(
+        pg_pool,
+        isbn_providers(&conf.isbn_chain()),
+        &conf.loan,
+        conf.isbn_cache_days,
+    );
     Ok(bms)
 }
 
 use serde::{Deserialize, Serialize};
-use sqlx::Row;
-use time::OffsetDateTime;
-use tracing::trace;
+use sqlx::{Postgres, Transaction};
+use time::{Duration, OffsetDateTime};
+use tracing::{debug, trace, warn};
 
+// books 中的每一行是一本实体书（馆藏副本），书目信息保存在 titles 中，同一 ISBN 的多本书共享一条书目
 #[derive(Debug, sqlx::FromRow, Clone)]
 pub struct BookModel {
     pub id: i64,
+    pub title_id: i64,
+    pub barcode: Option<String>,
     pub isbn: Option<String>,
     pub title: String,
     pub authors: Vec<String>,
@@ -29,10 +44,34 @@ pub struct BookModel {
     pub created_at: OffsetDateTime,
     pub deleted_at: Option<OffsetDateTime>,
 
+    pub subtitle: Option<String>,
+    pub translators: Option<Vec<String>>,
+    pub pages: Option<String>,
+    pub price: Option<String>,
+    pub description: Option<String>,
+    pub author_intro: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub douban_id: Option<i64>,
+    pub douban_score: Option<f64>,
+
     pub operator: String,
     pub operator_name: String,
     pub operate_at: OffsetDateTime,
+
+    pub copies: i64,
+    pub available_copies: i64,
+
+    pub borrower: Option<String>,
+    pub borrower_name: Option<String>,
+    pub due_at: Option<OffsetDateTime>,
+    pub renewals: Option<i32>,
+
+    pub reserved_for: Option<String>,
+    pub reserved_for_name: Option<String>,
+    pub reserved_until: Option<OffsetDateTime>,
+    pub location: Option<String>,
 }
+
 #[derive(PartialEq, Debug, Clone, sqlx::Type)]
 #[sqlx(type_name = "text")]
 #[sqlx(rename_all = "lowercase")]
@@ -40,38 +79,159 @@ pub enum BookStateModel {
     Available,
     Borrowed,
     Returned,
+    Reserved,
     Lost,
+    Damaged,
     Deleted,
     Unknown,
 }
 
-struct ChangeLogModel {
-    id: i64,
-    operator: String,
-    source_id: i64,
-    source_type: String,
-    state: String,
-    action: String,
-    operate_at: OffsetDateTime,
+impl BookStateModel {
+    // 数据库中保存的状态值，导出时使用
+    pub fn code(&self) -> &'static str {
+        match self {
+            BookStateModel::Available => "available",
+            BookStateModel::Borrowed => "borrowed",
+            BookStateModel::Returned => "returned",
+            BookStateModel::Reserved => "reserved",
+            BookStateModel::Lost => "lost",
+            BookStateModel::Damaged => "damaged",
+            BookStateModel::Deleted => "deleted",
+            BookStateModel::Unknown => "unknown",
+        }
+    }
 }
 
+// change_logs.action 中保存的操作类型
+#[derive(PartialEq, Debug, Clone, sqlx::Type)]
+#[sqlx(type_name = "text")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ChangeActionModel {
+    Stored,
+    Borrowed,
+    Renewed,
+    Returned,
+    Confirmed,
+    HoldCancelled,
+    HoldExpired,
+    Lost,
+    Damaged,
+    Reset,
+    Deleted,
+    Edited,
+    Unknown,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BookError {
+    #[error("书籍不存在")]
+    NotFound,
+    #[error("{0}")]
+    InvalidIsbn(IsbnError),
+    #[error("ISBN {0} 已有书目，请使用快速入库添加副本")]
+    TitleExists(String),
+    #[error("{action}失败：{reason}")]
+    Denied {
+        action: BookAction,
+        reason: ActionDenied,
+    },
+    #[error("借阅失败：{0}")]
+    LoanPolicy(LoanDenied),
+    #[error("续借失败：最多只能续借 {0} 次")]
+    RenewLimit(i32),
+    #[error("续借失败：还有同事在排队预约这本书")]
+    RenewHeld,
+    #[error("预约失败：还有可借阅的副本，直接借阅即可")]
+    HoldUnneeded,
+    #[error("预约失败：你正在借阅这本书")]
+    HoldBorrowing,
+    #[error("预约失败：你已经在排队了")]
+    HoldDuplicated,
+    #[error("预约不存在")]
+    HoldNotFound,
+    #[error("这条记录不是书目修改，无法撤销")]
+    EditNotRevertible,
+    #[error("数据库操作失败: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+pub type BookResult<T> = std::result::Result<T, BookError>;
+
+// 可以手工修改的书目字段。修改记录的 payload 中保存有变化字段修改前后的值，撤销时据此恢复
+#[derive(Debug, sqlx::FromRow, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TitleFieldsModel {
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub authors: Vec<String>,
+    pub publisher: Option<String>,
+    pub publish_date: Option<String>,
+    pub thumbnail: Option<String>,
+}
+
+// 一条操作记录，operator_name 取自 accounts，账号不存在时为空。
+// payload 保存操作的附加信息，例如为谁保留了书籍；无法识别类型的旧数据，原始文字在 legacy 中
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct ChangeLogModel {
+    pub id: i64,
+    pub operator: String,
+    pub operator_name: Option<String>,
+    pub source_id: i64,
+    pub source_type: String,
+    pub action: ChangeActionModel,
+    pub from_state: Option<BookStateModel>,
+    pub to_state: Option<BookStateModel>,
+    pub payload: Option<serde_json::Value>,
+    pub operate_at: OffsetDateTime,
+}
+
+// 查询操作记录的条件，为 None 的条件不生效
+#[derive(Debug, Clone, Default)]
+pub struct ActivityFilter {
+    pub operator: Option<String>,
+    pub book_id: Option<i64>,
+    pub since: Option<OffsetDateTime>,
+    pub until: Option<OffsetDateTime>,
+}
+
+// 系统自动执行的操作（如预约超时）的操作人，账号由迁移脚本创建
+pub const SYSTEM_OPERATOR: &str = "system";
+
 #[derive(Clone, Debug)]
 pub struct BookMS {
     pg: PgPool,
-    api_key: String,
+    providers: Vec<Arc<dyn IsbnProvider>>,
+    loan: LoanPolicy,
+    isbn_cache_days: i64,
 }
 
 impl BookMS {
     pub fn from_scope(cx: leptos::Scope) -> Arc<Self> {
         use_context::<Arc<Self>>(cx).unwrap()
     }
+
+    // 导入、检索等拆在其它模块中的查询共用同一个连接池
+    pub(crate) fn pool(&self) -> &PgPool {
+        &self.pg
+    }
+
+    pub(crate) fn loan(&self) -> &LoanPolicy {
+        &self.loan
+    }
 }
 
 impl BookMS {
-    pub fn new(pg: &PgPool, api_key: &str) -> Self {
+    pub fn new(
+        pg: &PgPool,
+        providers: Vec<Arc<dyn IsbnProvider>>,
+        loan: &LoanPolicy,
+        isbn_cache_days: i64,
+    ) -> Self {
         Self {
             pg: pg.clone(),
-            api_key: api_key.to_string(),
+            providers,
+            loan: loan.clone(),
+            isbn_cache_days,
         }
     }
     pub async fn get_one_by_id(
@@ -80,25 +240,31 @@ impl BookMS {
     ) -> Result<BookModel, Box<dyn std::error::Error>> {
         let book = sqlx::query_as!(
             BookModel,
-            r#"SELECT b.id,
-       b.isbn,
-       b.title,
-       b.authors,
-       b.publisher,
-       b.created_at,
-       b.state as "state: BookStateModel",
-       cl.operator,
-       a.display_name as operator_name,
-       cl.operate_at,
-       b.thumbnail, b.deleted_at, b.log_id, b.publish_date
-    FROM books b
-             LEFT JOIN change_logs cl on b.log_id = cl.id
-             LEFT JOIN accounts a on a.id = cl.operator
-    WHERE b.id = $1
-      AND b.deleted_at is null
-    ORDER BY b.created_at desc
-    LIMIT 1"#,
-            &book_id
+            r#"SELECT id AS "id!",
+       title_id AS "title_id!",
+       barcode,
+       isbn,
+       title AS "title!",
+       authors AS "authors!",
+       publisher,
+       publish_date,
+       created_at AS "created_at!",
+       state AS "state!: BookStateModel",
+       operator AS "operator!",
+       operator_name AS "operator_name!",
+       operate_at AS "operate_at!",
+       thumbnail, deleted_at, log_id AS "log_id!",
+       subtitle, translators, pages, price, description,
+       author_intro, tags, douban_id, douban_score,
+       copies AS "copies!", available_copies AS "available_copies!",
+       borrower, borrower_name, due_at, renewals,
+       reserved_for, reserved_for_name, reserved_until,
+       location
+FROM book_details
+WHERE id = $1
+  AND deleted_at is null
+LIMIT 1"#,
+            book_id
         )
         .fetch_one(&self.pg)
         .await?;
@@ -106,283 +272,1078 @@ impl BookMS {
         Ok(book)
     }
 
-    // 获取图书列表
-    pub async fn list(
-        &self,
-        limit: &i64,
-        offset: &i64,
-        q: &Option<String>,
-    ) -> Result<Vec<BookModel>> {
-        let books: Vec<BookModel> = match q {
-            Some(q) => {
-                let q = format!("%{}%", q);
-                sqlx::query_as!(
-                    BookModel,
-                    r#"SELECT b.id,
-       b.isbn,
-       b.title,
-       b.authors,
-       b.publisher,
-       b.created_at,
-       b.state as "state: BookStateModel",
-       cl.operator,
-       a.display_name as operator_name,
-       cl.operate_at,
-       b.thumbnail, b.deleted_at, b.log_id, b.publish_date
-FROM books b
-         LEFT JOIN change_logs cl on b.log_id = cl.id
-         LEFT JOIN accounts a on a.id = cl.operator
-WHERE b.deleted_at is null
-AND (b.title LIKE $3
-         OR b.isbn LIKE $3)
-ORDER BY b.created_at desc
+    // 已超过应还日期仍未归还的书籍，最早到期的排在前面
+    pub async fn overdue(&self, limit: &i64, offset: &i64) -> Result<Vec<BookModel>> {
+        let books = sqlx::query_as!(
+            BookModel,
+            r#"SELECT id AS "id!",
+       title_id AS "title_id!",
+       barcode,
+       isbn,
+       title AS "title!",
+       authors AS "authors!",
+       publisher,
+       publish_date,
+       created_at AS "created_at!",
+       state AS "state!: BookStateModel",
+       operator AS "operator!",
+       operator_name AS "operator_name!",
+       operate_at AS "operate_at!",
+       thumbnail, deleted_at, log_id AS "log_id!",
+       subtitle, translators, pages, price, description,
+       author_intro, tags, douban_id, douban_score,
+       copies AS "copies!", available_copies AS "available_copies!",
+       borrower, borrower_name, due_at, renewals,
+       reserved_for, reserved_for_name, reserved_until,
+       location
+FROM book_details
+WHERE deleted_at is null
+  AND due_at < $3
+ORDER BY due_at
 LIMIT $1 OFFSET $2"#,
-                    &limit,
-                    &offset,
-                    q
-                )
-                .fetch_all(&self.pg)
-                .await?
-            }
+            limit,
+            offset,
+            OffsetDateTime::now_utc()
+        )
+        .fetch_all(&self.pg)
+        .await?;
+
+        Ok(books)
+    }
+
+    // 已归还、等待管理员确认的书籍，最早归还的排在前面。书籍最后一条记录就是这次归还
+    pub async fn return_queue(&self) -> Result<Vec<BookModel>> {
+        let books = sqlx::query_as!(
+            BookModel,
+            r#"SELECT id AS "id!",
+       title_id AS "title_id!",
+       barcode,
+       isbn,
+       title AS "title!",
+       authors AS "authors!",
+       publisher,
+       publish_date,
+       created_at AS "created_at!",
+       state AS "state!: BookStateModel",
+       operator AS "operator!",
+       operator_name AS "operator_name!",
+       operate_at AS "operate_at!",
+       thumbnail, deleted_at, log_id AS "log_id!",
+       subtitle, translators, pages, price, description,
+       author_intro, tags, douban_id, douban_score,
+       copies AS "copies!", available_copies AS "available_copies!",
+       borrower, borrower_name, due_at, renewals,
+       reserved_for, reserved_for_name, reserved_until,
+       location
+FROM book_details
+WHERE deleted_at is null
+  AND state = 'returned'
+ORDER BY operate_at, id"#
+        )
+        .fetch_all(&self.pg)
+        .await?;
+
+        Ok(books)
+    }
+
+    // 同一书目下的所有副本
+    pub async fn copies_of(&self, title_id: &i64) -> Result<Vec<BookModel>> {
+        let books = sqlx::query_as!(
+            BookModel,
+            r#"SELECT id AS "id!",
+       title_id AS "title_id!",
+       barcode,
+       isbn,
+       title AS "title!",
+       authors AS "authors!",
+       publisher,
+       publish_date,
+       created_at AS "created_at!",
+       state AS "state!: BookStateModel",
+       operator AS "operator!",
+       operator_name AS "operator_name!",
+       operate_at AS "operate_at!",
+       thumbnail, deleted_at, log_id AS "log_id!",
+       subtitle, translators, pages, price, description,
+       author_intro, tags, douban_id, douban_score,
+       copies AS "copies!", available_copies AS "available_copies!",
+       borrower, borrower_name, due_at, renewals,
+       reserved_for, reserved_for_name, reserved_until,
+       location
+FROM book_details
+WHERE title_id = $1
+  AND deleted_at is null
+ORDER BY id"#,
+            title_id
+        )
+        .fetch_all(&self.pg)
+        .await?;
+
+        Ok(books)
+    }
+
+    // 入库一本实体书，ISBN 已有书目时直接作为新副本挂到该书目下。ISBN 统一按 ISBN-13 保存，
+    // 以前按 ISBN-10 保存的书目也能匹配上
+    pub async fn storage(
+        &self,
+        isbn: &str,
+        barcode: &Option<String>,
+        operator: &str,
+    ) -> Result<()> {
+        let isbn = isbn::normalize(isbn).map_err(BookError::InvalidIsbn)?;
+        let isbn = isbn.as_str();
+        let now = OffsetDateTime::now_utc();
+        let mut tc = self.pg.begin().await?;
+        let (title_id, new_title) = match title_by_isbn(&mut tc, isbn).await? {
+            Some(id) => (id, false),
             None => {
-                sqlx::query_as!(
-                    BookModel,
-                    r#"SELECT b.id,
-       b.isbn,
-       b.title,
-       b.authors,
-       b.publisher,
-       b.created_at,
-       b.state as "state: BookStateModel",
-       cl.operator,
-       a.display_name as operator_name,
-       cl.operate_at,
-       b.thumbnail, b.deleted_at, b.log_id, b.publish_date
-FROM books b
-         LEFT JOIN change_logs cl on b.log_id = cl.id
-         LEFT JOIN accounts a on a.id = cl.operator
-WHERE b.deleted_at is null
-ORDER BY b.created_at desc
-LIMIT $1 OFFSET $2"#,
-                    &limit,
-                    &offset
-                )
-                .fetch_all(&self.pg)
-                .await?
+                // 检索服务可能很慢，查询时不占着事务的连接，查到后在新的事务中插入
+                tc.rollback().await?;
+                let data = self.lookup_isbn(isbn).await?;
+                tc = self.pg.begin().await?;
+                // 同一 ISBN 被并发入库时，没有插入成功的一方挂到先插入的书目下
+                match insert_title(&mut tc, Some(isbn), &data, now).await? {
+                    Some(id) => (id, true),
+                    None => (
+                        title_by_isbn(&mut tc, isbn)
+                            .await?
+                            .ok_or(BookError::NotFound)?,
+                        false,
+                    ),
+                }
             }
         };
-
-        Ok(books)
+        trace!("title id: {:?}", title_id);
+        let payload = serde_json::json!({ "new_title": new_title });
+        insert_copy(
+            &mut tc, title_id, barcode, None, operator, payload, &self.loan,
+        )
+        .await?;
+        tc.commit().await?;
+        Ok(())
     }
-    // pub async fn delete(&self, book_id: &i64, who: &str) -> Result<(), Box<dyn std::error::Error>> {
-    //     let mut client = self.pg.get().await?;
-    //     let tc = client.transaction().await?;
-    //     let oid: i64 = tc
-    //         .query_one(
-    //             "INSERT INTO change_logs (operator, source_id, source_type, action, operate_at)
-    //                         VALUES ($1, $2, $3, $4, $5) RETURNING id",
-    //             &[
-    //                 &who,
-    //                 &book_id,
-    //                 &"book",
-    //                 &"删除该书籍",
-    //                 &time::OffsetDateTime::now_utc(),
-    //             ],
-    //         )
-    //         .await?
-    //         .get(0);
-    //     tc.execute(
-    //         "UPDATE books SET state = $1, log_id = $2, deleted_at = $3 WHERE id = $4",
-    //         &[
-    //             &BookState::Deleted.to_string(),
-    //             &oid,
-    //             &time::OffsetDateTime::now_utc(),
-    //             &book_id,
-    //         ],
-    //     )
-    //     .await?;
-    //     tc.commit().await?;
-    //     Ok(())
-    // }
-    pub async fn storage(&self, isbn: &str, operator: &str) -> Result<()> {
-        let isbn = get_book_by_isbn(isbn, &self.api_key).await?;
-        let bk = BookModel {
-            id: 0,
-            isbn: Some(isbn.code.to_string()),
-            title: isbn.name.to_string(),
-            authors: isbn.authors,
-            publisher: Some(isbn.publishing.to_string()),
-            publish_date: Some(isbn.published.to_string()),
-            log_id: 0,
-            thumbnail: Some(isbn.photo_url.to_string()),
-            created_at: OffsetDateTime::now_utc(),
-            deleted_at: None,
-            state: BookStateModel::Available,
-
-            operator: "".to_string(),
-            operator_name: "".to_string(),
-            operate_at: OffsetDateTime::now_utc(),
+
+    // 手工编目：检索服务查不到的图书由管理员填写书目信息，同时入库一本副本
+    pub async fn create(
+        &self,
+        data: &ISBNData,
+        isbn: Option<&str>,
+        barcode: &Option<String>,
+        operator: &str,
+    ) -> Result<i64> {
+        let isbn = match isbn.map(str::trim).filter(|i| !i.is_empty()) {
+            Some(i) => Some(isbn::normalize(i).map_err(BookError::InvalidIsbn)?),
+            None => None,
         };
+        let now = OffsetDateTime::now_utc();
         let mut tc = self.pg.begin().await?;
-        let bid:i64 = sqlx::query!(r#"INSERT INTO books (isbn, title, authors, publisher, publish_date, state, log_id, thumbnail, created_at)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-RETURNING id
-        "#,  bk.isbn, bk.title, &bk.authors,
-            bk.publisher,
-            bk.publish_date,
-            bk.state as _,
-            bk.log_id,
-            bk.thumbnail,
-            bk.created_at).fetch_one(&mut tc).await?.id;
-        trace!("book id: {:?}", bid);
-        let oid = sqlx::query!(r#"INSERT INTO change_logs (operator, source_id, source_type, action, operate_at) VALUES ($1, $2, $3, $4, $5) RETURNING id"#,
-                                    &operator, &bid , &"book", &"新书第一次入库", &bk.created_at).fetch_one(&mut tc).await?.id;
-        trace!("operator id: {}", oid);
-        sqlx::query!("UPDATE books SET log_id = $1 WHERE id = $2", &oid, &bid)
-            .execute(&mut tc)
-            .await?;
+        if let Some(isbn) = &isbn {
+            if title_by_isbn(&mut tc, isbn).await?.is_some() {
+                return Err(BookError::TitleExists(isbn.clone()).into());
+            }
+        }
+        let title_id = insert_title(&mut tc, isbn.as_deref(), data, now)
+            .await?
+            .ok_or_else(|| BookError::TitleExists(isbn.clone().unwrap_or_default()))?;
+        let payload = serde_json::json!({ "new_title": true, "manual": true });
+        insert_copy(
+            &mut tc, title_id, barcode, None, operator, payload, &self.loan,
+        )
+        .await?;
         tc.commit().await?;
-        Ok(())
+        Ok(title_id)
+    }
+
+    // 手工编目前先查一次检索服务，查到的信息用来预填表单。
+    // 检索服务只返回了部分信息（例如没有书名）时也用来预填，但不写入缓存
+    pub async fn prefill(&self, isbn: &str) -> Result<Option<ISBNData>> {
+        let isbn = isbn::normalize(isbn).map_err(BookError::InvalidIsbn)?;
+        let cached = isbn_cache::get(&self.pg, &isbn).await?;
+        if let Some(c) = cached.as_ref().filter(|c| self.is_fresh(c)) {
+            return Ok(Some(c.data.0.clone()));
+        }
+        let (merged, raw, errors) = self.query_providers(&isbn).await;
+        match merged {
+            Some(data) if !data.name.is_empty() => {
+                isbn_cache::put(&self.pg, &isbn, &data, &serde_json::Value::Object(raw)).await?;
+                Ok(Some(data))
+            }
+            // 查不到完整信息时优先使用过期的缓存
+            partial => {
+                debug!("ISBN {} 没有查到完整信息: {}", isbn, errors.join("；"));
+                Ok(cached.map(|c| c.data.0).or(partial))
+            }
+        }
+    }
+
+    fn is_fresh(&self, cached: &isbn_cache::IsbnMetadataModel) -> bool {
+        cached.fetched_at + Duration::days(self.isbn_cache_days) > OffsetDateTime::now_utc()
+    }
+
+    // 先查本地缓存，缓存过期或者没有时查询检索服务；检索服务都失败时退回到过期的缓存
+    pub async fn lookup_isbn(&self, isbn: &str) -> Result<ISBNData> {
+        let cached = isbn_cache::get(&self.pg, isbn).await?;
+        if let Some(c) = cached.as_ref().filter(|c| self.is_fresh(c)) {
+            return Ok(c.data.0.clone());
+        }
+        match self.fetch_isbn(isbn).await {
+            Ok(data) => Ok(data),
+            Err(e) => match cached {
+                Some(c) => {
+                    warn!("ISBN {} 检索失败，使用过期的缓存: {:#}", isbn, e);
+                    Ok(c.data.0)
+                }
+                None => Err(e),
+            },
+        }
     }
 
-    pub async fn borrow(&self, book_id: &i64, who: &str) -> Result<()> {
+    // 依次查询配置的 ISBN 检索服务，后面的结果用来补齐前面缺少的字段，信息齐全后不再继续查询。
+    // 查到的结果写入缓存
+    async fn fetch_isbn(&self, isbn: &str) -> Result<ISBNData> {
+        let (merged, raw, errors) = self.query_providers(isbn).await;
+        let data = merged
+            .filter(|m| !m.name.is_empty())
+            .ok_or(anyhow::anyhow!(
+                "没有查到 ISBN {} 的书籍信息（{}）",
+                isbn,
+                errors.join("；")
+            ))?;
+        isbn_cache::put(&self.pg, isbn, &data, &serde_json::Value::Object(raw)).await?;
+        Ok(data)
+    }
+
+    // 返回合并后的结果（可能缺少书名）、各检索服务的原始数据和失败原因
+    async fn query_providers(
+        &self,
+        isbn: &str,
+    ) -> (
+        Option<ISBNData>,
+        serde_json::Map<String, serde_json::Value>,
+        Vec<String>,
+    ) {
+        let mut merged: Option<ISBNData> = None;
+        let mut raw = serde_json::Map::new();
+        let mut errors = vec![];
+        for provider in self.providers.iter() {
+            match provider.lookup(isbn).await {
+                Ok(found) => {
+                    raw.insert(provider.name().to_string(), found.raw);
+                    match merged.as_mut() {
+                        Some(m) => m.merge(found.data),
+                        None => merged = Some(found.data),
+                    }
+                }
+                Err(e) => {
+                    warn!("{} 查询 ISBN {} 失败: {:#}", provider.name(), isbn, e);
+                    errors.push(format!("{}: {:#}", provider.name(), e));
+                }
+            }
+            if merged.as_ref().map(|m| m.is_complete()).unwrap_or(false) {
+                break;
+            }
+        }
+        (merged, raw, errors)
+    }
+
+    // 忽略缓存重新查询书目的 ISBN 信息，更新书目
+    pub async fn refresh_metadata(&self, title_id: &i64, operator: &str) -> Result<()> {
+        let isbn = sqlx::query_scalar!("SELECT isbn FROM titles WHERE id = $1", title_id)
+            .fetch_optional(&self.pg)
+            .await?
+            .ok_or(BookError::NotFound)?;
+        let isbn = isbn
+            .filter(|i| !i.is_empty())
+            .ok_or(anyhow::anyhow!("该书目没有 ISBN"))?;
+        let data = self.fetch_isbn(&isbn).await?;
         let mut tc = self.pg.begin().await?;
+        // 书名、作者等可以手工修改的字段记录修改前后的值，和手工修改一样可以撤销
+        let before = Self::lock_title(&mut tc, title_id).await?;
+        let after = TitleFieldsModel {
+            title: data.name.clone(),
+            subtitle: non_empty(&data.subname).map(String::from),
+            authors: data.authors.clone(),
+            publisher: non_empty(&data.publishing).map(String::from),
+            publish_date: non_empty(&data.published).map(String::from),
+            thumbnail: non_empty(&data.photo_url).map(String::from),
+        };
+        let mark = serde_json::json!({ "refreshed": isbn });
+        Self::edit_title(&mut tc, title_id, &before, &after, operator, Some(mark)).await?;
+        sqlx::query!(
+            r#"UPDATE titles
+SET translators  = $1,
+    pages        = $2,
+    price        = $3,
+    description  = $4,
+    author_intro = $5,
+    tags         = $6,
+    douban_id    = $7,
+    douban_score = $8
+WHERE id = $9"#,
+            &data.translators,
+            non_empty(&data.pages),
+            non_empty(&data.price),
+            non_empty(&data.description),
+            non_empty(&data.author_intro),
+            &data.tags,
+            data.douban_id,
+            data.douban_score,
+            title_id
+        )
+        .execute(&mut tc)
+        .await?;
+        tc.commit().await?;
+        Ok(())
+    }
 
-        let oid: i64 = sqlx::query(
-            "INSERT INTO change_logs (operator, source_id, source_type, action, operate_at)
-                            VALUES ($1, $2, $3, $4, $5) RETURNING id",
+    // 在事务内锁定书籍，按状态转移表校验当前状态和借阅人，返回操作后书籍应处于的状态
+    async fn lock(
+        tc: &mut Transaction<'_, Postgres>,
+        book_id: &i64,
+        action: &BookAction,
+        who: &UserSession,
+    ) -> BookResult<BookStateModel> {
+        let row = sqlx::query!(
+            r#"SELECT b.state AS "state?: BookStateModel", coalesce(l.borrower, h.requester) AS holder
+FROM books b
+         LEFT JOIN loans l on l.book_id = b.id and l.returned_at is null
+         LEFT JOIN holds h on h.book_id = b.id and h.state = 'ready'
+WHERE b.id = $1
+  AND b.deleted_at is null
+FOR UPDATE OF b"#,
+            book_id
         )
-        .bind(who)
-        .bind(book_id)
-        .bind("book")
-        .bind(format!("{} 借出书籍", who))
-        .bind(time::OffsetDateTime::now_utc())
-        .fetch_one(&mut tc)
+        .fetch_optional(&mut *tc)
         .await?
-        .get(0);
+        .ok_or(BookError::NotFound)?;
+        let (state, holder) = (row.state, row.holder);
+        let state: BookState = state.unwrap_or(BookStateModel::Unknown).into();
+        let next = action
+            .check(
+                &state,
+                &holder.unwrap_or("".to_string()),
+                &who.uid,
+                &who.role,
+            )
+            .map_err(|reason| BookError::Denied {
+                action: action.clone(),
+                reason,
+            })?;
+        Ok(next.into())
+    }
 
+    // 写入操作记录并更新书籍状态，操作前的状态从 books 中读取
+    async fn record(
+        tc: &mut Transaction<'_, Postgres>,
+        book_id: &i64,
+        next: &BookStateModel,
+        operator: &str,
+        action: ChangeActionModel,
+        payload: Option<serde_json::Value>,
+    ) -> BookResult<()> {
+        let oid = sqlx::query_scalar!(
+            r#"INSERT INTO change_logs (operator, source_id, source_type, action, from_state, to_state, payload, operate_at)
+SELECT $1, $2, $3, $4, b.state, $5, $6, $7
+FROM books b
+WHERE b.id = $2
+RETURNING id"#,
+            operator,
+            book_id,
+            "book",
+            action as _,
+            next.clone() as _,
+            payload,
+            OffsetDateTime::now_utc()
+        )
+        .fetch_one(&mut *tc)
+        .await?;
+
+        let deleted_at = if *next == BookStateModel::Deleted {
+            Some(OffsetDateTime::now_utc())
+        } else {
+            None
+        };
         sqlx::query!(
-            "UPDATE books SET state = $1, log_id = $2 WHERE id = $3 and deleted_at is null",
-            BookStateModel::Borrowed as _,
+            "UPDATE books SET state = $1, log_id = $2, deleted_at = $3 WHERE id = $4",
+            next.clone() as _,
             oid,
+            deleted_at,
             book_id
         )
-        .execute(&mut tc)
+        .execute(&mut *tc)
+        .await?;
+        Ok(())
+    }
+
+    // 按状态转移表变更书籍状态，payload 保存在操作记录中
+    async fn transit(
+        &self,
+        book_id: &i64,
+        action: BookAction,
+        who: &UserSession,
+        payload: Option<serde_json::Value>,
+    ) -> BookResult<()> {
+        let mut tc = self.pg.begin().await?;
+        self.transit_in(&mut tc, book_id, action, who, payload)
+            .await?;
+        tc.commit().await?;
+        Ok(())
+    }
+
+    // 在调用方的事务内变更书籍状态，调用方可以先做额外的校验
+    async fn transit_in(
+        &self,
+        tc: &mut Transaction<'_, Postgres>,
+        book_id: &i64,
+        action: BookAction,
+        who: &UserSession,
+        payload: Option<serde_json::Value>,
+    ) -> BookResult<()> {
+        let next = Self::lock(tc, book_id, &action, who).await?;
+        // 借出前按借阅规则检查借阅人的在借数量和逾期情况
+        if next == BookStateModel::Borrowed {
+            loans::check(tc, &who.uid, role_of(who), &self.loan)
+                .await?
+                .map_err(BookError::LoanPolicy)?;
+        } else {
+            // 离开借出状态时结束借阅，离开保留状态时预约回到队列
+            loans::close(tc, book_id).await?;
+            holds::release(tc, book_id).await?;
+        }
+        let (next, payload) = self.reserve_if_waiting(tc, book_id, next, payload).await?;
+        let change = ChangeAction::from(&action).into();
+        Self::record(tc, book_id, &next, &who.uid, change, payload).await?;
+        // 借出时开始一次借阅并完成借阅人的预约
+        if next == BookStateModel::Borrowed {
+            let days = self.loan.days_for(role_of(who));
+            loans::open(tc, book_id, &who.uid, days).await?;
+            holds::fulfill(tc, book_id, &who.uid).await?;
+        }
+        Ok(())
+    }
+
+    // 续借，延长当前借阅的应还日期
+    pub async fn renew(&self, book_id: &i64, who: &UserSession) -> BookResult<()> {
+        let mut tc = self.pg.begin().await?;
+        let next = Self::lock(&mut tc, book_id, &BookAction::Renew, who).await?;
+        if holds::has_waiting(&mut tc, book_id, &who.uid).await? {
+            return Err(BookError::RenewHeld);
+        }
+        let days = self.loan.days_for(role_of(who));
+        loans::renew(&mut tc, book_id, days, self.loan.max_renewals)
+            .await?
+            .ok_or(BookError::RenewLimit(self.loan.max_renewals))?;
+        Self::record(
+            &mut tc,
+            book_id,
+            &next,
+            &who.uid,
+            ChangeActionModel::Renewed,
+            None,
+        )
         .await?;
         tc.commit().await?;
         Ok(())
     }
 
+    pub async fn borrow(&self, book_id: &i64, who: &UserSession) -> BookResult<()> {
+        self.transit(book_id, BookAction::Borrow, who, None).await
+    }
+
     // 归还图书
-    pub async fn revert_to(&self, book_id: &i64, who: &str) -> Result<()> {
+    pub async fn revert_to(&self, book_id: &i64, who: &UserSession) -> BookResult<()> {
+        self.transit(book_id, BookAction::Return, who, None).await
+    }
+
+    // 管理员确认书籍已经归还，有同事在排队预约时为排在最前面的人保留
+    pub async fn confirm(&self, book_id: &i64, who: &UserSession) -> BookResult<()> {
         let mut tc = self.pg.begin().await?;
-        let oid: i64 = sqlx::query(
-            "INSERT INTO change_logs (operator, source_id, source_type, action, operate_at)
-                            VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        self.confirm_in(&mut tc, book_id, who).await?;
+        tc.commit().await?;
+        Ok(())
+    }
+
+    // 批量确认归还，在同一个事务中完成，任何一本不能确认时全部不生效
+    pub async fn confirm_all(&self, book_ids: &[i64], who: &UserSession) -> BookResult<()> {
+        // 按 id 顺序加锁，避免与其它批量操作互相等待
+        let mut ids = book_ids.to_vec();
+        ids.sort();
+        ids.dedup();
+        let mut tc = self.pg.begin().await?;
+        for id in &ids {
+            self.confirm_in(&mut tc, id, who).await?;
+        }
+        tc.commit().await?;
+        Ok(())
+    }
+
+    async fn confirm_in(
+        &self,
+        tc: &mut Transaction<'_, Postgres>,
+        book_id: &i64,
+        who: &UserSession,
+    ) -> BookResult<()> {
+        let next = Self::lock(tc, book_id, &BookAction::Confirm, who).await?;
+        let (next, payload) = self.reserve_if_waiting(tc, book_id, next, None).await?;
+        Self::record(
+            tc,
+            book_id,
+            &next,
+            &who.uid,
+            ChangeActionModel::Confirmed,
+            payload,
         )
-        .bind(who)
-        .bind(book_id)
-        .bind("book")
-        .bind(format!("{} 归还书籍", who))
-        .bind(OffsetDateTime::now_utc())
-        .fetch_one(&mut tc)
+        .await?;
+        Ok(())
+    }
+
+    // 确认归还时发现问题，将书籍标记为遗失或损坏，说明保存在操作记录中
+    pub async fn report_problem(
+        &self,
+        book_id: &i64,
+        action: BookAction,
+        note: &str,
+        who: &UserSession,
+    ) -> BookResult<()> {
+        let payload = Some(note.trim())
+            .filter(|n| !n.is_empty())
+            .map(|n| serde_json::json!({ "note": n }));
+        let mut tc = self.pg.begin().await?;
+        // 只能对等待确认归还的书报告问题，其它状态下的遗失、损坏走各自的操作
+        let state = sqlx::query_scalar!(
+            r#"SELECT state AS "state?: BookStateModel" FROM books WHERE id = $1 AND deleted_at is null FOR UPDATE"#,
+            book_id
+        )
+        .fetch_optional(&mut tc)
         .await?
-        .get(0);
+        .ok_or(BookError::NotFound)?;
+        let state = state.unwrap_or(BookStateModel::Unknown);
+        if state != BookStateModel::Returned {
+            return Err(BookError::Denied {
+                action,
+                reason: ActionDenied::InvalidState(state.into()),
+            });
+        }
+        self.transit_in(&mut tc, book_id, action, who, payload)
+            .await?;
+        tc.commit().await?;
+        Ok(())
+    }
 
+    pub async fn lost(&self, book_id: &i64, who: &UserSession) -> BookResult<()> {
+        self.transit(book_id, BookAction::Lost, who, None).await
+    }
+
+    pub async fn reset(&self, book_id: &i64, who: &UserSession) -> BookResult<()> {
+        self.transit(book_id, BookAction::Reset, who, None).await
+    }
+
+    pub async fn delete(&self, book_id: &i64, who: &UserSession) -> BookResult<()> {
+        self.transit(book_id, BookAction::Delete, who, None).await
+    }
+
+    // 书目的预约队列
+    pub async fn holds(&self, title_id: &i64) -> Result<Vec<HoldModel>> {
+        Ok(holds::queue(&self.pg, title_id).await?)
+    }
+
+    // 对书籍所属书目排队预约，只有所有副本都不可借阅时才需要预约
+    pub async fn place_hold(&self, book_id: &i64, who: &UserSession) -> BookResult<()> {
+        let mut tc = self.pg.begin().await?;
+        let row = sqlx::query!(
+            r#"SELECT b.title_id AS "title_id!",
+       exists(SELECT 1
+              FROM books s
+              WHERE s.title_id = b.title_id
+                AND s.deleted_at is null
+                AND s.state = 'available') AS "available!",
+       exists(SELECT 1
+              FROM books s
+                       JOIN loans l on l.book_id = s.id and l.returned_at is null
+              WHERE s.title_id = b.title_id
+                AND l.borrower = $2) AS "borrowing!",
+       exists(SELECT 1
+              FROM holds h
+              WHERE h.title_id = b.title_id
+                AND h.requester = $2
+                AND h.state in ('waiting', 'ready')) AS "holding!"
+FROM books b
+WHERE b.id = $1
+  AND b.deleted_at is null"#,
+            book_id,
+            &who.uid
+        )
+        .fetch_optional(&mut tc)
+        .await?
+        .ok_or(BookError::NotFound)?;
+        let (title_id, available, borrowing, holding) =
+            (row.title_id, row.available, row.borrowing, row.holding);
+        if available {
+            return Err(BookError::HoldUnneeded);
+        }
+        if borrowing {
+            return Err(BookError::HoldBorrowing);
+        }
+        if holding {
+            return Err(BookError::HoldDuplicated);
+        }
+        holds::place(&mut tc, &title_id, &who.uid).await?;
+        tc.commit().await?;
+        Ok(())
+    }
+
+    // 取消预约，已经为预约人保留的书籍转给下一位排队的同事
+    pub async fn cancel_hold(&self, hold_id: &i64, who: &UserSession) -> BookResult<()> {
+        let mut tc = self.pg.begin().await?;
+        // 先锁定为预约人保留的书籍再取消预约，和借阅、过期处理一样先锁书籍后改预约
         sqlx::query!(
-            "UPDATE books SET state = $1, log_id = $2 WHERE id = $3 and deleted_at is null",
-            BookStateModel::Returned as _,
-            oid,
-            book_id
+            r#"SELECT b.id
+FROM holds h
+         JOIN books b on b.id = h.book_id
+WHERE h.id = $1
+  AND h.requester = $2
+    FOR UPDATE OF b"#,
+            hold_id,
+            &who.uid
         )
-        .execute(&mut tc)
+        .fetch_optional(&mut tc)
         .await?;
+        let hold = holds::cancel(&mut tc, hold_id, &who.uid)
+            .await?
+            .ok_or(BookError::HoldNotFound)?;
+        if let Some(book_id) = hold.book_id {
+            // 加锁之后预约才被保留的，转交前补上书籍的锁
+            sqlx::query!("SELECT id FROM books WHERE id = $1 FOR UPDATE", book_id)
+                .fetch_optional(&mut tc)
+                .await?;
+            self.pass_on(
+                &mut tc,
+                &book_id,
+                &who.uid,
+                ChangeActionModel::HoldCancelled,
+                None,
+            )
+            .await?;
+        }
+        tc.commit().await?;
+        Ok(())
+    }
+
+    // 超过取书期限的保留自动转给下一位排队的同事，没有人排队时书籍回到可借阅
+    pub async fn expire_holds(&self) -> Result<()> {
+        for book_id in holds::overdue_books(&self.pg).await? {
+            let mut tc = self.pg.begin().await?;
+            sqlx::query!("SELECT id FROM books WHERE id = $1 FOR UPDATE", book_id)
+                .fetch_optional(&mut tc)
+                .await?;
+            if let Some(hold) = holds::expire(&mut tc, &book_id).await? {
+                self.pass_on(
+                    &mut tc,
+                    &book_id,
+                    SYSTEM_OPERATOR,
+                    ChangeActionModel::HoldExpired,
+                    Some(&hold.requester),
+                )
+                .await?;
+            }
+            tc.commit().await?;
+        }
+        Ok(())
+    }
+
+    // 手工修改书目信息，修改前后有变化的字段记录在操作记录中
+    pub async fn update_title(
+        &self,
+        title_id: &i64,
+        fields: &TitleFieldsModel,
+        operator: &str,
+    ) -> BookResult<()> {
+        let mut tc = self.pg.begin().await?;
+        let before = Self::lock_title(&mut tc, title_id).await?;
+        Self::edit_title(&mut tc, title_id, &before, fields, operator, None).await?;
         tc.commit().await?;
         Ok(())
     }
 
-    // 管理员确认书籍已经归还
-    pub async fn confirm(&self, book_id: &i64, who: &str) -> Result<()> {
+    // 撤销一次书目修改：把那次修改过的字段恢复为修改前的值，撤销本身也记录为一次修改
+    pub async fn revert_edit(&self, log_id: &i64, operator: &str) -> BookResult<()> {
+        let log = sqlx::query!(
+            r#"SELECT source_id, payload
+FROM change_logs
+WHERE id = $1
+  AND source_type = 'title'
+  AND action = $2"#,
+            log_id,
+            ChangeActionModel::Edited as _
+        )
+        .fetch_optional(&self.pg)
+        .await?;
+        let (title_id, restore) = log
+            .and_then(|l| Some((l.source_id, l.payload?.get("before")?.as_object()?.clone())))
+            .ok_or(BookError::EditNotRevertible)?;
         let mut tc = self.pg.begin().await?;
-        let oid: i64 = sqlx::query(
-            "INSERT INTO change_logs (operator, source_id, source_type, action, operate_at)
-                            VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        let before = Self::lock_title(&mut tc, &title_id).await?;
+        let mut after = match serde_json::to_value(&before) {
+            Ok(serde_json::Value::Object(m)) => m,
+            _ => return Err(BookError::EditNotRevertible),
+        };
+        after.extend(restore);
+        let after: TitleFieldsModel = serde_json::from_value(serde_json::Value::Object(after))
+            .map_err(|_| BookError::EditNotRevertible)?;
+        let mark = serde_json::json!({ "reverted": log_id });
+        Self::edit_title(&mut tc, &title_id, &before, &after, operator, Some(mark)).await?;
+        tc.commit().await?;
+        Ok(())
+    }
+
+    async fn lock_title(
+        tc: &mut Transaction<'_, Postgres>,
+        title_id: &i64,
+    ) -> BookResult<TitleFieldsModel> {
+        sqlx::query_as!(
+            TitleFieldsModel,
+            r#"SELECT title, subtitle, coalesce(authors, '{}') AS "authors!", publisher, publish_date, thumbnail
+FROM titles
+WHERE id = $1
+    FOR UPDATE"#,
+            title_id
         )
-        .bind(who)
-        .bind(book_id)
-        .bind("book")
-        .bind(format!("{} 确认书籍已经归还", who))
-        .bind(OffsetDateTime::now_utc())
-        .fetch_one(&mut tc)
+        .fetch_optional(&mut *tc)
         .await?
-        .get(0);
+        .ok_or(BookError::NotFound)
+    }
+
+    // 字段没有变化时不更新也不记录
+    async fn edit_title(
+        tc: &mut Transaction<'_, Postgres>,
+        title_id: &i64,
+        before: &TitleFieldsModel,
+        after: &TitleFieldsModel,
+        operator: &str,
+        mark: Option<serde_json::Value>,
+    ) -> BookResult<()> {
+        let mut payload = match title_diff(before, after) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        // 撤销、重新查询等来源标记合并到 payload 中
+        if let (Some(p), Some(serde_json::Value::Object(m))) = (payload.as_object_mut(), mark) {
+            p.extend(m);
+        }
+        let py = pinyin::index(&after.title, &after.authors);
         sqlx::query!(
-            "UPDATE books SET state = $1, log_id = $2 WHERE id = $3 and deleted_at is null",
-            BookStateModel::Available as _,
-            oid,
-            book_id
+            r#"UPDATE titles
+SET title        = $1,
+    subtitle     = $2,
+    authors      = $3,
+    publisher    = $4,
+    publish_date = $5,
+    thumbnail    = $6,
+    pinyin          = $7,
+    pinyin_initials = $8
+WHERE id = $9"#,
+            after.title,
+            after.subtitle,
+            &after.authors,
+            after.publisher,
+            after.publish_date,
+            after.thumbnail,
+            py.full,
+            py.initials,
+            title_id
         )
-        .execute(&mut tc)
+        .execute(&mut *tc)
+        .await?;
+        sqlx::query!(
+            r#"INSERT INTO change_logs (operator, source_id, source_type, action, payload, operate_at)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
+            operator,
+            title_id,
+            "title",
+            ChangeActionModel::Edited as _,
+            payload,
+            OffsetDateTime::now_utc()
+        )
+        .execute(&mut *tc)
         .await?;
-        tc.commit().await?;
         Ok(())
     }
-    // pub async fn lost(&self, book_id: &i64, who: &str) -> Result<(), Box<dyn std::error::Error>> {
-    //     let mut client = self.pg.get().await?;
-    //     let tc = client.transaction().await?;
-    //     let oid: i64 = tc
-    //         .query_one(
-    //             "INSERT INTO change_logs (operator, source_id, source_type, action, operate_at)
-    //                         VALUES ($1, $2, $3, $4, $5) RETURNING id",
-    //             &[
-    //                 &who,
-    //                 &book_id,
-    //                 &"book",
-    //                 &"书籍被标记为遗失",
-    //                 &time::OffsetDateTime::now_utc(),
-    //             ],
-    //         )
-    //         .await?
-    //         .get(0);
-    //     tc.execute(
-    //         "UPDATE books SET state = $1, log_id = $2 WHERE id = $3 and deleted_at is null",
-    //         &[&BookState::Lost.to_string(), &oid, &book_id],
-    //     )
-    //     .await?;
-    //     tc.commit().await?;
-    //     Ok(())
-    // }
-
-    // pub async fn reset(&self, book_id: &i64, who: &str) -> Result<(), Box<dyn std::error::Error>> {
-    //     let mut client = self.pg.get().await?;
-    //     let tc = client.transaction().await?;
-    //     let oid: i64 = tc
-    //         .query_one(
-    //             "INSERT INTO change_logs (operator, source_id, source_type, action, operate_at)
-    //                         VALUES ($1, $2, $3, $4, $5) RETURNING id",
-    //             &[
-    //                 &who,
-    //                 &book_id,
-    //                 &"book",
-    //                 &"书籍状态被重置",
-    //                 &time::OffsetDateTime::now_utc(),
-    //             ],
-    //         )
-    //         .await?
-    //         .get(0);
-    //     tc.execute(
-    //         "UPDATE books SET state = $1, log_id = $2 WHERE id = $3 and deleted_at is null",
-    //         &[&BookState::Available.to_string(), &oid, &book_id],
-    //     )
-    //     .await?;
-    //     tc.commit().await?;
-    //     Ok(())
-    // }
+
+    // 一本书的全部操作记录，包括所属书目的修改，最新的在前
+    pub async fn history(
+        &self,
+        book_id: &i64,
+        limit: &i64,
+        offset: &i64,
+    ) -> BookResult<Vec<ChangeLogModel>> {
+        let filter = ActivityFilter {
+            book_id: Some(*book_id),
+            ..Default::default()
+        };
+        self.activity(&filter, limit, offset).await
+    }
+
+    // 按操作人、书籍和时间范围查询操作记录，最新的在前
+    pub async fn activity(
+        &self,
+        filter: &ActivityFilter,
+        limit: &i64,
+        offset: &i64,
+    ) -> BookResult<Vec<ChangeLogModel>> {
+        let logs = sqlx::query_as!(
+            ChangeLogModel,
+            r#"SELECT cl.id,
+       cl.operator,
+       a.display_name AS "operator_name?",
+       cl.source_id,
+       cl.source_type,
+       cl.action AS "action: ChangeActionModel",
+       cl.from_state AS "from_state: BookStateModel",
+       cl.to_state AS "to_state: BookStateModel",
+       cl.payload,
+       cl.operate_at
+FROM change_logs cl
+         LEFT JOIN accounts a ON a.id = cl.operator
+WHERE ($1::text is null OR cl.operator = $1)
+  AND ($2::bigint is null
+    OR (cl.source_type = 'book' AND cl.source_id = $2)
+    OR (cl.source_type = 'title' AND cl.source_id = (SELECT title_id FROM books WHERE id = $2)))
+  AND ($3::timestamptz is null OR cl.operate_at >= $3)
+  AND ($4::timestamptz is null OR cl.operate_at < $4)
+ORDER BY cl.operate_at DESC, cl.id DESC
+LIMIT $5 OFFSET $6"#,
+            filter.operator,
+            filter.book_id,
+            filter.since,
+            filter.until,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pg)
+        .await?;
+        Ok(logs)
+    }
+
+    // 书籍不再为原预约人保留时，交给队列中的下一位，或者回到可借阅
+    async fn pass_on(
+        &self,
+        tc: &mut Transaction<'_, Postgres>,
+        book_id: &i64,
+        operator: &str,
+        action: ChangeActionModel,
+        requester: Option<&str>,
+    ) -> BookResult<()> {
+        let payload = requester.map(|r| serde_json::json!({ "requester": r }));
+        let (next, payload) = self
+            .reserve_if_waiting(tc, book_id, BookStateModel::Available, payload)
+            .await?;
+        Self::record(tc, book_id, &next, operator, action, payload).await
+    }
+
+    // 书籍将要回到可借阅时，有同事在排队就改为给排在最前面的人保留，保留给谁记录在 payload 中
+    async fn reserve_if_waiting(
+        &self,
+        tc: &mut Transaction<'_, Postgres>,
+        book_id: &i64,
+        next: BookStateModel,
+        payload: Option<serde_json::Value>,
+    ) -> BookResult<(BookStateModel, Option<serde_json::Value>)> {
+        if next != BookStateModel::Available {
+            return Ok((next, payload));
+        }
+        let hold = match holds::reserve_next(tc, book_id, &self.loan).await? {
+            Some(hold) => hold,
+            None => return Ok((next, payload)),
+        };
+        let mut payload = match payload {
+            Some(serde_json::Value::Object(p)) => p,
+            _ => serde_json::Map::new(),
+        };
+        payload.insert("reserved_for".to_string(), hold.requester.into());
+        Ok((
+            BookStateModel::Reserved,
+            Some(serde_json::Value::Object(payload)),
+        ))
+    }
+}
+
+// 只保留修改前后有变化的字段：{"before": {...}, "after": {...}}，没有变化时返回 None
+fn title_diff(before: &TitleFieldsModel, after: &TitleFieldsModel) -> Option<serde_json::Value> {
+    let b = serde_json::to_value(before).ok()?;
+    let a = serde_json::to_value(after).ok()?;
+    let (b, a) = (b.as_object()?, a.as_object()?);
+    let mut old = serde_json::Map::new();
+    let mut new = serde_json::Map::new();
+    for (k, v) in a {
+        let prev = b.get(k).cloned().unwrap_or_default();
+        if &prev != v {
+            old.insert(k.clone(), prev);
+            new.insert(k.clone(), v.clone());
+        }
+    }
+    if new.is_empty() {
+        None
+    } else {
+        Some(serde_json::json!({ "before": old, "after": new }))
+    }
+}
+
+// 按 ISBN 查找书目，以前按 ISBN-10 保存的书目也能匹配上
+pub async fn title_by_isbn(
+    tc: &mut Transaction<'_, Postgres>,
+    isbn: &str,
+) -> sqlx::Result<Option<i64>> {
+    sqlx::query_scalar!(
+        "SELECT id FROM titles WHERE isbn = ANY ($1) LIMIT 1",
+        &isbn::variants(isbn)
+    )
+    .fetch_optional(&mut *tc)
+    .await
 }
+
+// 新建书目，返回书目 id。该 ISBN 已有书目（例如被并发入库抢先插入）时不插入，返回 None
+pub async fn insert_title(
+    tc: &mut Transaction<'_, Postgres>,
+    isbn: Option<&str>,
+    data: &ISBNData,
+    now: OffsetDateTime,
+) -> sqlx::Result<Option<i64>> {
+    let py = pinyin::index(&data.name, &data.authors);
+    sqlx::query_scalar!(
+        r#"INSERT INTO titles (isbn, title, authors, publisher, publish_date, thumbnail, created_at,
+                    subtitle, translators, pages, price, description, author_intro, tags,
+                    douban_id, douban_score, pinyin, pinyin_initials)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+ON CONFLICT (isbn) DO NOTHING
+RETURNING id"#,
+        isbn,
+        data.name,
+        &data.authors,
+        non_empty(&data.publishing),
+        non_empty(&data.published),
+        non_empty(&data.photo_url),
+        now,
+        non_empty(&data.subname),
+        &data.translators,
+        non_empty(&data.pages),
+        non_empty(&data.price),
+        non_empty(&data.description),
+        non_empty(&data.author_intro),
+        &data.tags,
+        data.douban_id,
+        data.douban_score,
+        py.full,
+        py.initials
+    )
+    .fetch_optional(&mut *tc)
+    .await
+}
+
+// 入库一本副本并记录入库操作，返回副本 id。书目有同事在排队预约时，新副本直接为排在最前面的人保留
+pub async fn insert_copy(
+    tc: &mut Transaction<'_, Postgres>,
+    title_id: i64,
+    barcode: &Option<String>,
+    location: Option<&str>,
+    operator: &str,
+    mut payload: serde_json::Value,
+    loan: &LoanPolicy,
+) -> sqlx::Result<i64> {
+    let now = OffsetDateTime::now_utc();
+    let bid = sqlx::query_scalar!(
+        r#"INSERT INTO books (title_id, barcode, state, log_id, created_at, location)
+VALUES ($1, $2, $3, $4, $5, $6)
+RETURNING id"#,
+        title_id,
+        barcode.as_deref(),
+        BookStateModel::Available as _,
+        0_i64,
+        now,
+        location
+    )
+    .fetch_one(&mut *tc)
+    .await?;
+    trace!("book id: {:?}", bid);
+    let state = match holds::reserve_next(tc, &bid, loan).await? {
+        Some(hold) => {
+            if let Some(p) = payload.as_object_mut() {
+                p.insert("reserved_for".to_string(), hold.requester.into());
+            }
+            BookStateModel::Reserved
+        }
+        None => BookStateModel::Available,
+    };
+    let oid = sqlx::query_scalar!(
+        r#"INSERT INTO change_logs (operator, source_id, source_type, action, to_state, payload, operate_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7)
+RETURNING id"#,
+        operator,
+        bid,
+        "book",
+        ChangeActionModel::Stored as _,
+        state.clone() as _,
+        payload,
+        now
+    )
+    .fetch_one(&mut *tc)
+    .await?;
+    trace!("operator id: {}", oid);
+    sqlx::query!(
+        "UPDATE books SET state = $1, log_id = $2 WHERE id = $3",
+        state as _,
+        oid,
+        bid
+    )
+    .execute(&mut *tc)
+    .await?;
+    Ok(bid)
+}
+
+// 借阅规则配置中使用的角色名
+fn role_of(who: &UserSession) -> &'static str {
+    match who.role {
+        Role::Admin => "admin",
+        Role::User => "user",
+    }
+}
+
+// ISBN 检索服务
+#[async_trait::async_trait]
+pub trait IsbnProvider: Send + Sync + std::fmt::Debug {
+    fn name(&self) -> &'static str;
+    async fn lookup(&self, isbn: &str) -> Result<IsbnLookup>;
+}
+
+// 一次检索的结果，raw 是检索服务的原始返回
+#[derive(Debug, Clone)]
+pub struct IsbnLookup {
+    pub data: ISBNData,
+    pub raw: serde_json::Value,
+}
+
+// 按配置顺序创建 ISBN 检索服务
+pub fn isbn_providers(conf: &[IsbnProviderConf]) -> Vec<Arc<dyn IsbnProvider>> {
+    conf.iter()
+        .map(|c| -> Arc<dyn IsbnProvider> {
+            match c {
+                IsbnProviderConf::Jike { api_key } => Arc::new(JikeProvider {
+                    api_key: api_key.clone().unwrap_or("".to_string()),
+                }),
+                IsbnProviderConf::OpenLibrary => Arc::new(OpenLibraryProvider),
+                IsbnProviderConf::Google { api_key } => Arc::new(GoogleBooksProvider {
+                    api_key: api_key.clone(),
+                }),
+                IsbnProviderConf::File { path } => Arc::new(FileProvider { path: path.clone() }),
+            }
+        })
+        .collect()
+}
+
 // ISBN response
 //
-#[derive(Serialize, Deserialize, Debug)]
-struct ISBNData {
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ISBNData {
     pub id: i64,
     pub name: String,
     pub subname: String,
@@ -395,6 +1356,55 @@ struct ISBNData {
     pub photo_url: String,
     pub price: String,
     pub description: String,
+    pub translators: Vec<String>,
+    pub author_intro: String,
+    pub tags: Vec<String>,
+    pub douban_id: Option<i64>,
+    pub douban_score: Option<f64>,
+}
+
+impl ISBNData {
+    // 用另一个检索服务的结果补齐缺少的字段
+    fn merge(&mut self, other: ISBNData) {
+        let fill = |a: &mut String, b: String| {
+            if a.is_empty() {
+                *a = b
+            }
+        };
+        if self.id == 0 {
+            self.id = other.id;
+        }
+        fill(&mut self.name, other.name);
+        fill(&mut self.subname, other.subname);
+        if self.authors.iter().all(|a| a.is_empty()) {
+            self.authors = other.authors;
+        }
+        fill(&mut self.publishing, other.publishing);
+        fill(&mut self.published, other.published);
+        fill(&mut self.code, other.code);
+        fill(&mut self.pages, other.pages);
+        fill(&mut self.photo_url, other.photo_url);
+        fill(&mut self.price, other.price);
+        fill(&mut self.description, other.description);
+        if self.translators.is_empty() {
+            self.translators = other.translators;
+        }
+        fill(&mut self.author_intro, other.author_intro);
+        if self.tags.is_empty() {
+            self.tags = other.tags;
+        }
+        self.douban_id = self.douban_id.or(other.douban_id);
+        self.douban_score = self.douban_score.or(other.douban_score);
+    }
+
+    // 入库需要的信息都有了
+    fn is_complete(&self) -> bool {
+        !self.name.is_empty()
+            && self.authors.iter().any(|a| !a.is_empty())
+            && !self.publishing.is_empty()
+            && !self.published.is_empty()
+            && !self.photo_url.is_empty()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -412,61 +1422,386 @@ struct ISBNDataRaw {
     photo_url: Option<String>,
     price: String,
     description: String,
+    #[serde(default)]
+    translator: Option<String>,
+    #[serde(default)]
+    douban: Option<i64>,
+    #[serde(rename = "doubanScore", default)]
+    douban_score: Option<f64>,
+    #[serde(rename = "authorIntro", default)]
+    author_intro: Option<String>,
+    #[serde(default)]
+    tags: Option<serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct Root {
     pub ret: i64,
     pub msg: String,
-    pub data: ISBNDataRaw,
+    pub data: Option<ISBNDataRaw>,
+}
+
+// 极客 API，https://api.jike.xyz
+#[derive(Debug)]
+pub struct JikeProvider {
+    api_key: String,
 }
-async fn get_book_by_isbn(isbn: &str, api_key: &str) -> Result<ISBNData> {
+
+#[async_trait::async_trait]
+impl IsbnProvider for JikeProvider {
+    fn name(&self) -> &'static str {
+        "jike"
+    }
+    async fn lookup(&self, isbn: &str) -> Result<IsbnLookup> {
+        get_book_by_isbn(isbn, &self.api_key).await
+    }
+}
+
+async fn get_book_by_isbn(isbn: &str, api_key: &str) -> Result<IsbnLookup> {
     let url = format!(
         "https://api.jike.xyz/situ/book/isbn/{}?apikey={}",
         isbn, api_key
     );
-    let resp = reqwest::get(&url)
+    let raw = reqwest::get(&url)
         .await
         .map_err(|e| anyhow::Error::new(e).context("请求ISBN检索服务失败"))?
-        .json::<Root>()
+        .json::<serde_json::Value>()
         .await
         .map_err(|e| anyhow::Error::new(e).context("ISBN检索服务返回数据格式错误"))?;
-    Ok(ISBNData {
-        id: resp.data.id,
-        name: resp.data.name,
-        subname: resp.data.subname,
-        authors: resp
-            .data
-            .author
-            .unwrap_or("".to_string())
-            .split("/")
-            .map(|v| v.trim().to_string())
+    let resp = serde_json::from_value::<Root>(raw.clone())
+        .map_err(|e| anyhow::Error::new(e).context("ISBN检索服务返回数据格式错误"))?;
+    let data = match (resp.ret, resp.data) {
+        (0, Some(data)) => data,
+        _ => return Err(anyhow::anyhow!("ISBN检索服务返回错误: {}", resp.msg)),
+    };
+    let data = ISBNData {
+        id: data.id,
+        name: data.name,
+        subname: data.subname,
+        authors: split_names(&data.author.unwrap_or("".to_string())),
+        publishing: data.publishing,
+        published: data.published,
+        code: data.code,
+        pages: data.pages,
+        photo_url: data.photo_url.unwrap_or("".to_string()),
+        price: data.price,
+        description: data.description,
+        translators: split_names(&data.translator.unwrap_or("".to_string())),
+        author_intro: data.author_intro.unwrap_or("".to_string()),
+        tags: data.tags.map(|t| parse_tags(&t)).unwrap_or(vec![]),
+        douban_id: data.douban.filter(|d| *d > 0),
+        // 没有评分时返回 0
+        douban_score: data.douban_score.filter(|s| *s > 0.0),
+    };
+    Ok(IsbnLookup { data, raw })
+}
+
+// 极客 API 中多个作者、译者用 / 分隔
+fn split_names(names: &str) -> Vec<String> {
+    names
+        .split("/")
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+// 标签可能是字符串数组、{"name": ...} 数组或者逗号分隔的字符串
+fn parse_tags(tags: &serde_json::Value) -> Vec<String> {
+    match tags {
+        serde_json::Value::Array(items) => items
+            .iter()
+            .filter_map(|t| match t {
+                serde_json::Value::String(s) => Some(s.clone()),
+                serde_json::Value::Object(o) => o
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .map(|n| n.to_string()),
+                _ => None,
+            })
+            .collect(),
+        serde_json::Value::String(s) => s
+            .split(|c| c == ',' || c == '，' || c == ' ')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
             .collect(),
-        publishing: resp.data.publishing,
-        published: resp.data.published,
-        code: resp.data.code,
-        pages: resp.data.pages,
-        photo_url: resp.data.photo_url.unwrap_or("".to_string()),
-        price: resp.data.price,
-        description: resp.data.description,
-    })
+        _ => vec![],
+    }
+}
+
+// 空字符串存为 NULL
+fn non_empty(s: &str) -> Option<&str> {
+    if s.trim().is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+// Open Library，https://openlibrary.org/dev/docs/api/books
+#[derive(Debug)]
+pub struct OpenLibraryProvider;
+
+#[derive(Deserialize, Debug)]
+struct OpenLibraryBook {
+    title: String,
+    subtitle: Option<String>,
+    #[serde(default)]
+    authors: Vec<OpenLibraryName>,
+    #[serde(default)]
+    publishers: Vec<OpenLibraryName>,
+    publish_date: Option<String>,
+    number_of_pages: Option<i64>,
+    cover: Option<OpenLibraryCover>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenLibraryName {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenLibraryCover {
+    medium: Option<String>,
+    large: Option<String>,
+}
+
+impl OpenLibraryBook {
+    fn into_isbn_data(self, isbn: &str) -> ISBNData {
+        ISBNData {
+            id: isbn.parse().unwrap_or(0),
+            name: self.title,
+            subname: self.subtitle.unwrap_or("".to_string()),
+            authors: self.authors.into_iter().map(|a| a.name).collect(),
+            publishing: self
+                .publishers
+                .into_iter()
+                .map(|p| p.name)
+                .collect::<Vec<_>>()
+                .join(" / "),
+            published: self.publish_date.unwrap_or("".to_string()),
+            code: isbn.to_string(),
+            pages: self
+                .number_of_pages
+                .map(|p| p.to_string())
+                .unwrap_or("".to_string()),
+            photo_url: self
+                .cover
+                .and_then(|c| c.medium.or(c.large))
+                .unwrap_or("".to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl IsbnProvider for OpenLibraryProvider {
+    fn name(&self) -> &'static str {
+        "openlibrary"
+    }
+    async fn lookup(&self, isbn: &str) -> Result<IsbnLookup> {
+        let url = format!(
+            "https://openlibrary.org/api/books?bibkeys=ISBN:{}&format=json&jscmd=data",
+            isbn
+        );
+        let mut resp = reqwest::get(&url)
+            .await
+            .map_err(|e| anyhow::Error::new(e).context("请求 Open Library 失败"))?
+            .json::<serde_json::Map<String, serde_json::Value>>()
+            .await
+            .map_err(|e| anyhow::Error::new(e).context("Open Library 返回数据格式错误"))?;
+        let raw = resp
+            .remove(&format!("ISBN:{}", isbn))
+            .ok_or(anyhow::anyhow!("Open Library 没有这本书"))?;
+        let book = serde_json::from_value::<OpenLibraryBook>(raw.clone())
+            .map_err(|e| anyhow::Error::new(e).context("Open Library 返回数据格式错误"))?;
+        Ok(IsbnLookup {
+            data: book.into_isbn_data(isbn),
+            raw,
+        })
+    }
+}
+
+// Google Books，https://developers.google.com/books/docs/v1/using
+#[derive(Debug)]
+pub struct GoogleBooksProvider {
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GoogleVolumes {
+    #[serde(default)]
+    items: Vec<GoogleVolume>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GoogleVolume {
+    volume_info: GoogleVolumeInfo,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GoogleVolumeInfo {
+    title: String,
+    subtitle: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    publisher: Option<String>,
+    published_date: Option<String>,
+    description: Option<String>,
+    page_count: Option<i64>,
+    image_links: Option<GoogleImageLinks>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GoogleImageLinks {
+    thumbnail: Option<String>,
+    small_thumbnail: Option<String>,
+}
+
+impl GoogleVolumeInfo {
+    fn into_isbn_data(self, isbn: &str) -> ISBNData {
+        ISBNData {
+            id: isbn.parse().unwrap_or(0),
+            name: self.title,
+            subname: self.subtitle.unwrap_or("".to_string()),
+            authors: self.authors,
+            publishing: self.publisher.unwrap_or("".to_string()),
+            published: self.published_date.unwrap_or("".to_string()),
+            code: isbn.to_string(),
+            pages: self
+                .page_count
+                .map(|p| p.to_string())
+                .unwrap_or("".to_string()),
+            photo_url: self
+                .image_links
+                .and_then(|l| l.thumbnail.or(l.small_thumbnail))
+                .unwrap_or("".to_string()),
+            description: self.description.unwrap_or("".to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl IsbnProvider for GoogleBooksProvider {
+    fn name(&self) -> &'static str {
+        "google"
+    }
+    async fn lookup(&self, isbn: &str) -> Result<IsbnLookup> {
+        let mut url = format!(
+            "https://www.googleapis.com/books/v1/volumes?q=isbn:{}",
+            isbn
+        );
+        if let Some(key) = &self.api_key {
+            url.push_str(&format!("&key={}", key));
+        }
+        let raw = reqwest::get(&url)
+            .await
+            .map_err(|e| anyhow::Error::new(e).context("请求 Google Books 失败"))?
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|e| anyhow::Error::new(e).context("Google Books 返回数据格式错误"))?;
+        let resp = serde_json::from_value::<GoogleVolumes>(raw.clone())
+            .map_err(|e| anyhow::Error::new(e).context("Google Books 返回数据格式错误"))?;
+        let volume = resp
+            .items
+            .into_iter()
+            .next()
+            .ok_or(anyhow::anyhow!("Google Books 没有这本书"))?;
+        Ok(IsbnLookup {
+            data: volume.volume_info.into_isbn_data(isbn),
+            raw,
+        })
+    }
+}
+
+// 本地 JSON 文件，内容是 ISBN 到 ISBNData 的映射，每次查询时读取
+#[derive(Debug)]
+pub struct FileProvider {
+    path: String,
+}
+
+#[async_trait::async_trait]
+impl IsbnProvider for FileProvider {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+    async fn lookup(&self, isbn: &str) -> Result<IsbnLookup> {
+        let contents = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|e| anyhow::Error::new(e).context(format!("读取 {} 失败", self.path)))?;
+        let mut books: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&contents)?;
+        let raw = books
+            .remove(isbn)
+            .ok_or(anyhow::anyhow!("{} 中没有这本书", self.path))?;
+        let mut data = serde_json::from_value::<ISBNData>(raw.clone())?;
+        if data.code.is_empty() {
+            data.code = isbn.to_string();
+        }
+        Ok(IsbnLookup { data, raw })
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::api::search::BookQuery;
     use crate::backend::conf::parse_conf;
+    use crate::backend::query;
+    use sqlx::postgres::{PgPoolOptions, PgTransactionManager};
+    use sqlx::TransactionManager;
+
+    // 测试只用一个连接，连接建立后先开启一个不提交的事务，BookMS 内部的事务都变成其中的 savepoint。
+    // 测试结束连接关闭时全部回滚，不在库里留下数据
+    async fn new_pool(conf: &Config) -> Result<PgPool> {
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .after_connect(|conn, _| PgTransactionManager::begin(conn))
+            .connect(&conf.pg_dsn)
+            .await?;
+        Ok(pool)
+    }
 
     async fn new_bms() -> Result<BookMS> {
-        let conf = parse_conf("./config.toml");
-        let pool = PgPool::connect(&conf.pg_dsn).await?;
-        let bms = BookMS::new(&pool, &conf.isbn_api_key);
+        let conf = parse_conf("./config.toml")?;
+        let pool = new_pool(&conf).await?;
+        let bms = BookMS::new(
+            &pool,
+            isbn_providers(&conf.isbn_chain()),
+            &conf.loan,
+            conf.isbn_cache_days,
+        );
         Ok(bms)
     }
 
+    // 手工编目一本书，返回书目 id 和入库的副本
+    async fn new_book(bms: &BookMS, name: &str, authors: &[&str]) -> (i64, BookModel) {
+        let data = ISBNData {
+            name: name.to_string(),
+            authors: authors.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+        let title_id = bms.create(&data, None, &None, "songsong").await.unwrap();
+        let book = bms.copies_of(&title_id).await.unwrap().remove(0);
+        (title_id, book)
+    }
+
+    fn admin() -> UserSession {
+        UserSession {
+            uid: "songsong".to_string(),
+            display_name: "songsong".to_string(),
+            role: Role::Admin,
+        }
+    }
+
     #[tokio::test]
     async fn isbn() {
-        let conf = parse_conf("./config.toml");
+        let conf = parse_conf("./config.toml").unwrap();
         let isbn = "9787121390746";
         let resp = get_book_by_isbn(&isbn, &conf.isbn_api_key).await.unwrap();
         println!("{:?}", resp);
@@ -477,12 +1812,204 @@ mod test {
     #[tokio::test]
     async fn storage() {
         let bms = new_bms().await.unwrap();
-        bms.storage("9787302547648", "songsong").await.unwrap();
+        bms.storage("9787302547648", &None, "songsong")
+            .await
+            .unwrap();
+    }
+    #[tokio::test]
+    async fn create() {
+        let bms = new_bms().await.unwrap();
+        let (title_id, book) = new_book(&bms, "内部技术报告", &["songsong"]).await;
+        assert_eq!(book.title, "内部技术报告");
+        assert_eq!(bms.copies_of(&title_id).await.unwrap().len(), 1);
+    }
+    #[test]
+    fn diff_title_fields() {
+        let before = TitleFieldsModel {
+            title: "运筹学".to_string(),
+            publisher: Some("清华大学出版社".to_string()),
+            ..Default::default()
+        };
+        assert!(title_diff(&before, &before).is_none());
+        let after = TitleFieldsModel {
+            publish_date: Some("2021-1".to_string()),
+            authors: vec!["《运筹学》教材编写组".to_string()],
+            ..before.clone()
+        };
+        let diff = title_diff(&before, &after).unwrap();
+        assert_eq!(
+            diff,
+            serde_json::json!({
+                "before": {"authors": [], "publish_date": null},
+                "after": {"authors": ["《运筹学》教材编写组"], "publish_date": "2021-1"},
+            })
+        );
     }
     #[tokio::test]
     async fn list() {
         let bms = new_bms().await.unwrap();
-        let books = bms.list(&10, &0, &None).await.unwrap();
+        let query = BookQuery::default();
+        let first = bms.list(&query, None, &2, &0).await.unwrap();
+        assert!(first.total >= first.books.len() as i64);
+        // 游标翻页与 offset 翻页的结果一致
+        if let Some(cursor) = &first.next_cursor {
+            let next = bms.list(&query, Some(cursor), &2, &0).await.unwrap();
+            let skipped = bms.list(&query, None, &2, &2).await.unwrap();
+            let ids = |p: &query::BookPage| p.books.iter().map(|b| b.id).collect::<Vec<_>>();
+            assert_eq!(ids(&next), ids(&skipped));
+        }
+    }
+    #[tokio::test]
+    async fn search() {
+        let bms = new_bms().await.unwrap();
+        let (title_id, _) = new_book(&bms, "Rust 编程之道", &["张汉东"]).await;
+        for q in [
+            "rust 编程",
+            "编程 RUST",
+            "汉东",
+            "之道",
+            "bianchengzhidao",
+            "zhang han",
+            "rbczd",
+        ] {
+            let query = BookQuery {
+                q: Some(q.to_string()),
+                ..Default::default()
+            };
+            let page = bms.list_titles(&query, None, &10, &0).await.unwrap();
+            assert!(page.books.iter().any(|b| b.title_id == title_id), "{}", q);
+        }
+        // 全拼只从音节开头匹配
+        let query = BookQuery {
+            q: Some("ianchengzhidao".to_string()),
+            ..Default::default()
+        };
+        let page = bms.list_titles(&query, None, &10, &0).await.unwrap();
+        assert!(page.books.iter().all(|b| b.title_id != title_id));
+    }
+    #[tokio::test]
+    async fn facets() {
+        let bms = new_bms().await.unwrap();
+        let (_, book) = new_book(&bms, "内部技术报告", &["分面测试"]).await;
+        bms.lost(&book.id, &admin()).await.unwrap();
+        let query = BookQuery {
+            author: Some("分面测试".to_string()),
+            state: Some(BookState::Available),
+            ..Default::default()
+        };
+        let facets = bms.facets(&query, true).await.unwrap();
+        // 状态分面不受状态筛选影响，其它分面仍然按状态筛选
+        assert!(facets
+            .states
+            .iter()
+            .any(|f| f.value == BookState::Lost.code() && f.count == 1));
+        assert!(facets.authors.iter().all(|f| f.value != "分面测试"));
+    }
+    #[tokio::test]
+    async fn history() {
+        let bms = new_bms().await.unwrap();
+        let page = bms.list(&BookQuery::default(), None, &1, &0).await.unwrap();
+        for b in page.books {
+            let logs = bms.history(&b.id, &10, &0).await.unwrap();
+            assert!(logs
+                .iter()
+                .filter(|l| l.source_type == "book")
+                .all(|l| l.source_id == b.id));
+        }
+        // 书目的修改记录也出现在书籍的历史中，source_id 是书目 id
+        let (title_id, book) = new_book(&bms, "内部技术报告", &[]).await;
+        let fields = TitleFieldsModel {
+            title: "内部技术报告（修订版）".to_string(),
+            ..Default::default()
+        };
+        bms.update_title(&title_id, &fields, "songsong")
+            .await
+            .unwrap();
+        let logs = bms.history(&book.id, &10, &0).await.unwrap();
+        assert!(logs
+            .iter()
+            .any(|l| l.source_type == "title" && l.source_id == title_id));
+        assert!(logs
+            .iter()
+            .filter(|l| l.source_type == "book")
+            .all(|l| l.source_id == book.id));
+    }
+    #[tokio::test]
+    async fn my_books() {
+        let bms = new_bms().await.unwrap();
+        let borrowed = bms.borrowed_by("songsong").await.unwrap();
+        assert!(borrowed
+            .iter()
+            .all(|b| b.borrower.as_deref() == Some("songsong")));
+        let returned = bms.returned_by("songsong").await.unwrap();
+        assert!(returned.iter().all(|b| b.state == BookStateModel::Returned));
+        let (records, total) = bms.borrowing_history("songsong", &10, &0).await.unwrap();
+        assert!(total >= records.len() as i64);
+        // 正在借阅的书在历史中还没有结束时间
+        for b in &borrowed {
+            let (records, _) = bms
+                .borrowing_history("songsong", &total.max(1), &0)
+                .await
+                .unwrap();
+            let latest = records.iter().find(|r| r.book_id == b.id).unwrap();
+            assert!(latest.ended_at.is_none());
+        }
+    }
+    #[tokio::test]
+    async fn stats() {
+        let bms = new_bms().await.unwrap();
+        let stats = bms.stats(&5, &12).await.unwrap();
+        assert_eq!(stats.acquisitions.len(), 12);
+        assert_eq!(stats.borrows.len(), 12);
+        assert!(stats.top_titles.len() <= 5);
+        assert!(stats
+            .top_titles
+            .windows(2)
+            .all(|w| w[0].borrows >= w[1].borrows));
+    }
+    #[tokio::test]
+    async fn return_queue() {
+        let bms = new_bms().await.unwrap();
+        let queue = bms.return_queue().await.unwrap();
+        assert!(queue.iter().all(|b| b.state == BookStateModel::Returned));
+        assert!(queue.windows(2).all(|w| w[0].operate_at <= w[1].operate_at));
+    }
+    #[tokio::test]
+    async fn report_borrowed() {
+        let bms = new_bms().await.unwrap();
+        let (title_id, book) = new_book(&bms, "内部技术报告", &[]).await;
+        let who = admin();
+        bms.borrow(&book.id, &who).await.unwrap();
+        // 借出中的书不能从确认归还队列报告问题
+        let err = bms
+            .report_problem(&book.id, BookAction::Damage, "封面破损", &who)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            BookError::Denied {
+                reason: ActionDenied::InvalidState(BookState::Borrowed),
+                ..
+            }
+        ));
+    }
+    #[tokio::test]
+    async fn reserve_on_reset() {
+        let bms = new_bms().await.unwrap();
+        let (title_id, book) = new_book(&bms, "内部技术报告", &[]).await;
+        let who = admin();
+        bms.lost(&book.id, &who).await.unwrap();
+        bms.place_hold(&book.id, &who).await.unwrap();
+        // 找回的书直接为排在最前面的人保留
+        bms.reset(&book.id, &who).await.unwrap();
+        let found = bms.get_one_by_id(&book.id).await.unwrap();
+        assert_eq!(found.state, BookStateModel::Reserved);
+        assert_eq!(found.reserved_for.as_deref(), Some("songsong"));
+        // 取消预约后没有人排队，回到可借阅
+        let hold = &bms.holds(&title_id).await.unwrap()[0];
+        bms.cancel_hold(&hold.id, &who).await.unwrap();
+        let found = bms.get_one_by_id(&book.id).await.unwrap();
+        assert_eq!(found.state, BookStateModel::Available);
     }
     #[tokio::test]
     async fn decode() {
@@ -498,5 +2025,123 @@ mod test {
         \"reviews\":null,\"tags\":null}}" ;
         let root = serde_json::from_str::<Root>(r).unwrap();
         println!("{:?}", root.data);
+        let data = root.data.unwrap();
+        assert_eq!(data.douban, Some(35676616));
+        assert_eq!(data.translator, None);
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            parse_tags(&serde_json::json!(["运筹学", {"name": "数学"}, 1])),
+            vec!["运筹学".to_string(), "数学".to_string()]
+        );
+        assert_eq!(
+            parse_tags(&serde_json::json!("运筹学，教材, 数学")),
+            vec!["运筹学".to_string(), "教材".to_string(), "数学".to_string()]
+        );
+        assert_eq!(split_names(" 张三 / 李四 /"), vec!["张三", "李四"]);
+    }
+
+    #[test]
+    fn decode_open_library() {
+        let r = r#"{"ISBN:9780262033848": {"title": "Introduction to Algorithms",
+            "subtitle": "Third Edition",
+            "authors": [{"url": "", "name": "Thomas H. Cormen"}, {"url": "", "name": "Charles E. Leiserson"}],
+            "publishers": [{"name": "MIT Press"}],
+            "publish_date": "2009",
+            "number_of_pages": 1292,
+            "cover": {"small": "s.jpg", "medium": "m.jpg", "large": "l.jpg"}}}"#;
+        let mut resp: std::collections::HashMap<String, OpenLibraryBook> =
+            serde_json::from_str(r).unwrap();
+        let data = resp
+            .remove("ISBN:9780262033848")
+            .unwrap()
+            .into_isbn_data("9780262033848");
+        assert_eq!(data.name, "Introduction to Algorithms");
+        assert_eq!(data.authors.len(), 2);
+        assert_eq!(data.publishing, "MIT Press");
+        assert_eq!(data.pages, "1292");
+        assert_eq!(data.photo_url, "m.jpg");
+        assert_eq!(data.code, "9780262033848");
+    }
+    #[test]
+    fn decode_google_books() {
+        let r = r#"{"kind": "books#volumes", "totalItems": 1, "items": [{"volumeInfo": {
+            "title": "Introduction to Algorithms",
+            "authors": ["Thomas H. Cormen"],
+            "publisher": "MIT Press",
+            "publishedDate": "2009-07-31",
+            "description": "A comprehensive update.",
+            "pageCount": 1313,
+            "imageLinks": {"smallThumbnail": "s.jpg", "thumbnail": "t.jpg"}}}]}"#;
+        let resp: GoogleVolumes = serde_json::from_str(r).unwrap();
+        let data = resp
+            .items
+            .into_iter()
+            .next()
+            .unwrap()
+            .volume_info
+            .into_isbn_data("9780262033848");
+        assert_eq!(data.published, "2009-07-31");
+        assert_eq!(data.description, "A comprehensive update.");
+        assert_eq!(data.photo_url, "t.jpg");
+        let empty: GoogleVolumes = serde_json::from_str(r#"{"totalItems": 0}"#).unwrap();
+        assert!(empty.items.is_empty());
+    }
+    #[tokio::test]
+    async fn file_provider() {
+        let path = std::env::temp_dir().join("libraryms_isbn_test.json");
+        std::fs::write(
+            &path,
+            r#"{"9787302547648": {"name": "测试书籍", "authors": ["songsong"]}}"#,
+        )
+        .unwrap();
+        let provider = FileProvider {
+            path: path.to_string_lossy().to_string(),
+        };
+        let data = provider.lookup("9787302547648").await.unwrap().data;
+        assert_eq!(data.name, "测试书籍");
+        assert_eq!(data.code, "9787302547648");
+        assert!(provider.lookup("9787121390746").await.is_err());
+    }
+    #[tokio::test]
+    async fn isbn_cache() {
+        let conf = parse_conf("./config.toml").unwrap();
+        let pool = new_pool(&conf).await.unwrap();
+        let path = std::env::temp_dir().join("libraryms_isbn_cache_test.json");
+        std::fs::write(&path, r#"{"9787000000001": {"name": "缓存测试"}}"#).unwrap();
+        let providers = isbn_providers(&[IsbnProviderConf::File {
+            path: path.to_string_lossy().to_string(),
+        }]);
+        let bms = BookMS::new(&pool, providers, &conf.loan, 30);
+        assert_eq!(
+            bms.lookup_isbn("9787000000001").await.unwrap().name,
+            "缓存测试"
+        );
+        // 文件删除后仍然能从缓存中查到
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            bms.lookup_isbn("9787000000001").await.unwrap().name,
+            "缓存测试"
+        );
+    }
+    #[test]
+    fn merge() {
+        let mut a = ISBNData {
+            name: "运筹学".to_string(),
+            authors: vec!["".to_string()],
+            ..Default::default()
+        };
+        a.merge(ISBNData {
+            name: "Operations Research".to_string(),
+            authors: vec!["《运筹学》教材编写组".to_string()],
+            publishing: "清华大学出版社".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(a.name, "运筹学");
+        assert_eq!(a.authors, vec!["《运筹学》教材编写组".to_string()]);
+        assert_eq!(a.publishing, "清华大学出版社");
+        assert!(!a.is_complete());
     }
 }
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
 
 #[derive(Debug, Clone, Deserialize)]
@@ -11,6 +12,109 @@ pub struct LDAP {
     pub bind_pw: Option<String>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoanPolicy {
+    // 默认借阅天数
+    #[serde(default = "default_loan_days")]
+    pub days: i64,
+    // 每次借阅最多续借次数
+    #[serde(default = "default_max_renewals")]
+    pub max_renewals: i32,
+    // 归还的书籍为预约人保留的天数
+    #[serde(default = "default_pickup_days")]
+    pub pickup_days: i64,
+    // 每人同时借阅的最多本数，0 表示不限制
+    #[serde(default = "default_max_loans")]
+    pub max_loans: i64,
+    // 有逾期未还的书籍时不允许继续借阅
+    #[serde(default = "default_block_overdue")]
+    pub block_overdue: bool,
+    // 按角色（admin / user）覆盖借阅天数和借阅上限
+    #[serde(default)]
+    pub roles: HashMap<String, RoleLoanPolicy>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RoleLoanPolicy {
+    pub days: Option<i64>,
+    pub max_loans: Option<i64>,
+}
+
+impl LoanPolicy {
+    // 该角色的借阅天数
+    pub fn days_for(&self, role: &str) -> i64 {
+        self.roles
+            .get(role)
+            .and_then(|r| r.days)
+            .unwrap_or(self.days)
+    }
+
+    // 该角色同时借阅的最多本数
+    pub fn max_loans_for(&self, role: &str) -> i64 {
+        self.roles
+            .get(role)
+            .and_then(|r| r.max_loans)
+            .unwrap_or(self.max_loans)
+    }
+}
+
+impl Default for LoanPolicy {
+    fn default() -> Self {
+        Self {
+            days: default_loan_days(),
+            max_renewals: default_max_renewals(),
+            pickup_days: default_pickup_days(),
+            max_loans: default_max_loans(),
+            block_overdue: default_block_overdue(),
+            roles: HashMap::new(),
+        }
+    }
+}
+
+fn default_loan_days() -> i64 {
+    30
+}
+
+fn default_max_renewals() -> i32 {
+    2
+}
+
+fn default_pickup_days() -> i64 {
+    3
+}
+
+fn default_max_loans() -> i64 {
+    5
+}
+
+fn default_block_overdue() -> bool {
+    true
+}
+
+fn default_isbn_cache_days() -> i64 {
+    30
+}
+
+fn default_cover_dir() -> String {
+    "./covers".to_string()
+}
+
+fn default_auto_migrate() -> bool {
+    true
+}
+
+// ISBN 检索服务，查询书籍信息时按配置的顺序依次尝试
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IsbnProviderConf {
+    // 极客 API，未配置 api_key 时使用 isbn_api_key
+    Jike { api_key: Option<String> },
+    OpenLibrary,
+    Google { api_key: Option<String> },
+    // 本地 JSON 文件，ISBN 到书籍信息的映射，主要用于测试
+    File { path: String },
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub pg_dsn: String,
@@ -18,7 +122,34 @@ pub struct Config {
     #[serde(default)]
     pub compress: bool,
     pub ldap: LDAP,
+    #[serde(default)]
     pub isbn_api_key: String,
+    #[serde(default)]
+    pub isbn_providers: Vec<IsbnProviderConf>,
+    // ISBN 检索结果缓存的有效天数
+    #[serde(default = "default_isbn_cache_days")]
+    pub isbn_cache_days: i64,
+    #[serde(default)]
+    pub loan: LoanPolicy,
+    // 手工编目上传的封面图片保存目录，通过 /covers 访问
+    #[serde(default = "default_cover_dir")]
+    pub cover_dir: String,
+    // 启动服务时执行尚未执行的数据库迁移，关闭后需要用 migrate 子命令手工执行
+    #[serde(default = "default_auto_migrate")]
+    pub auto_migrate: bool,
+}
+
+impl Config {
+    // 没有配置 isbn_providers 时只使用极客 API
+    pub fn isbn_chain(&self) -> Vec<IsbnProviderConf> {
+        if self.isbn_providers.is_empty() {
+            vec![IsbnProviderConf::Jike {
+                api_key: Some(self.isbn_api_key.clone()),
+            }]
+        } else {
+            self.isbn_providers.clone()
+        }
+    }
 }
 
 pub fn parse_conf(p: &str) -> Result<Config> {
@@ -26,3 +157,60 @@ pub fn parse_conf(p: &str) -> Result<Config> {
     let conf: Config = toml::from_str(contents.as_str())?;
     Ok(conf)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn role_loan_policy() {
+        let conf: LoanPolicy = toml::from_str(
+            r#"
+days = 30
+max_loans = 3
+
+[roles.admin]
+days = 60
+max_loans = 0
+"#,
+        )
+        .unwrap();
+        assert_eq!(conf.days_for("admin"), 60);
+        assert_eq!(conf.max_loans_for("admin"), 0);
+        assert_eq!(conf.days_for("user"), 30);
+        assert_eq!(conf.max_loans_for("user"), 3);
+        assert!(conf.block_overdue);
+    }
+
+    #[test]
+    fn isbn_providers() {
+        #[derive(Deserialize)]
+        struct Providers {
+            providers: Vec<IsbnProviderConf>,
+        }
+        let conf: Providers = toml::from_str(
+            r#"
+[[providers]]
+kind = "jike"
+
+[[providers]]
+kind = "openlibrary"
+
+[[providers]]
+kind = "file"
+path = "./isbn.json"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            conf.providers,
+            vec![
+                IsbnProviderConf::Jike { api_key: None },
+                IsbnProviderConf::OpenLibrary,
+                IsbnProviderConf::File {
+                    path: "./isbn.json".to_string()
+                },
+            ]
+        );
+    }
+}
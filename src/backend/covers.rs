@@ -0,0 +1,55 @@
+use crate::api::books::MAX_COVER_BYTES;
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use std::path::Path;
+use time::OffsetDateTime;
+
+// 封面图片的访问路径前缀，serv 中把 cover_dir 挂在这个路径下
+pub const COVER_PATH: &str = "/covers";
+
+// 解析浏览器上传的 data URL，返回扩展名和图片内容
+fn decode(data_url: &str) -> Result<(&'static str, Vec<u8>)> {
+    let (meta, data) = data_url
+        .strip_prefix("data:")
+        .and_then(|s| s.split_once(','))
+        .ok_or(anyhow!("封面图片格式不正确"))?;
+    let ext = match meta {
+        "image/png;base64" => "png",
+        "image/jpeg;base64" => "jpg",
+        "image/webp;base64" => "webp",
+        "image/gif;base64" => "gif",
+        _ => bail!("只支持 PNG、JPEG、WebP、GIF 格式的封面图片"),
+    };
+    let bytes = base64::engine::general_purpose::STANDARD.decode(data)?;
+    if bytes.len() > MAX_COVER_BYTES {
+        bail!("封面图片不能超过 {}KB", MAX_COVER_BYTES / 1024);
+    }
+    Ok((ext, bytes))
+}
+
+// 保存封面图片，返回可以直接作为 thumbnail 使用的路径
+pub async fn save(dir: &str, data_url: &str) -> Result<String> {
+    let (ext, bytes) = decode(data_url)?;
+    tokio::fs::create_dir_all(dir).await?;
+    let name = format!(
+        "{}.{}",
+        OffsetDateTime::now_utc().unix_timestamp_nanos(),
+        ext
+    );
+    tokio::fs::write(Path::new(dir).join(&name), bytes).await?;
+    Ok(format!("{}/{}", COVER_PATH, name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_data_url() {
+        let (ext, bytes) = decode("data:image/png;base64,iVBORw0KGgo=").unwrap();
+        assert_eq!(ext, "png");
+        assert_eq!(bytes, b"\x89PNG\r\n\x1a\n");
+        assert!(decode("data:text/plain;base64,aGVsbG8=").is_err());
+        assert!(decode("iVBORw0KGgo=").is_err());
+    }
+}
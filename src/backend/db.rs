@@ -9,6 +9,13 @@ pub async fn init(pg_dsn: &str) -> Result<PgPool> {
     Ok(PgPool::connect(pg_dsn).await?)
 }
 
+// 执行 migrations 目录中尚未执行的数据库迁移，迁移脚本在编译时嵌入二进制
+#[cfg(feature = "ssr")]
+pub async fn migrate(pg: &PgPool) -> Result<()> {
+    sqlx::migrate!("./migrations").run(pg).await?;
+    Ok(())
+}
+
 #[cfg(feature = "ssr")]
 pub fn from_scope(cx: leptos::Scope) -> Result<Arc<PgPool>> {
     Ok(use_context::<Arc<PgPool>>(cx).ok_or(anyhow::anyhow!("No pg context found"))?)
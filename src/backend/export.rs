@@ -0,0 +1,466 @@
+use crate::api::entity::BookState;
+use crate::backend::books::BookStateModel;
+use anyhow::{bail, Result};
+use sqlx::PgPool;
+use std::str::FromStr;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+// 导出格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+    Marc21,
+    MarcXml,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "json" => Ok(ExportFormat::Jsonl),
+            "marc" | "marc21" | "mrc" => Ok(ExportFormat::Marc21),
+            "marcxml" | "xml" => Ok(ExportFormat::MarcXml),
+            _ => Err(format!(
+                "不支持的导出格式 {}，可选 csv、jsonl、marc、marcxml",
+                s
+            )),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Marc21 => "application/marc",
+            ExportFormat::MarcXml => "application/marcxml+xml",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Marc21 => "mrc",
+            ExportFormat::MarcXml => "xml",
+        }
+    }
+}
+
+// 导出的一本书：书目信息、当前状态和借阅人（或者为其保留的预约人）
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct ExportRecord {
+    pub id: i64,
+    pub barcode: Option<String>,
+    pub title_id: i64,
+    pub isbn: Option<String>,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub authors: Vec<String>,
+    pub publisher: Option<String>,
+    pub publish_date: Option<String>,
+    pub pages: Option<String>,
+    pub price: Option<String>,
+    pub state: BookStateModel,
+    pub holder: Option<String>,
+    pub holder_name: Option<String>,
+    pub due_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+const COLUMNS: [&str; 17] = [
+    "id",
+    "barcode",
+    "title_id",
+    "isbn",
+    "title",
+    "subtitle",
+    "authors",
+    "publisher",
+    "publish_date",
+    "pages",
+    "price",
+    "state",
+    "state_name",
+    "holder",
+    "holder_name",
+    "due_at",
+    "created_at",
+];
+
+impl ExportRecord {
+    fn state_name(&self) -> String {
+        BookState::from(&self.state).to_string()
+    }
+
+    fn values(&self) -> Vec<String> {
+        let s = |v: &Option<String>| v.clone().unwrap_or_default();
+        vec![
+            self.id.to_string(),
+            s(&self.barcode),
+            self.title_id.to_string(),
+            s(&self.isbn),
+            self.title.clone(),
+            s(&self.subtitle),
+            self.authors.join("; "),
+            s(&self.publisher),
+            s(&self.publish_date),
+            s(&self.pages),
+            s(&self.price),
+            self.state.code().to_string(),
+            self.state_name(),
+            s(&self.holder),
+            s(&self.holder_name),
+            format_time(self.due_at),
+            format_time(Some(self.created_at)),
+        ]
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let mut v = serde_json::Map::new();
+        for (k, s) in COLUMNS.iter().zip(self.values()) {
+            v.insert(k.to_string(), serde_json::Value::String(s));
+        }
+        v.insert("id".to_string(), self.id.into());
+        v.insert("title_id".to_string(), self.title_id.into());
+        v.insert("authors".to_string(), self.authors.clone().into());
+        serde_json::Value::Object(v)
+    }
+
+    fn to_marc(&self) -> MarcRecord {
+        let mut r = MarcRecord::default();
+        r.control("001", &self.id.to_string());
+        r.control(
+            "005",
+            &self
+                .created_at
+                .format(time::macros::format_description!(
+                    "[year][month][day][hour][minute][second].0"
+                ))
+                .unwrap_or_default(),
+        );
+        if let Some(isbn) = &self.isbn {
+            let mut f = vec![('a', isbn.clone())];
+            if let Some(price) = &self.price {
+                f.push(('c', price.clone()));
+            }
+            r.data("020", ' ', ' ', f);
+        }
+        let mut authors = self.authors.iter();
+        if let Some(a) = authors.next() {
+            r.data("100", '1', ' ', vec![('a', a.clone())]);
+        }
+        let mut title = vec![('a', self.title.clone())];
+        if let Some(sub) = &self.subtitle {
+            title.push(('b', sub.clone()));
+        }
+        r.data(
+            "245",
+            if self.authors.is_empty() { '0' } else { '1' },
+            '0',
+            title,
+        );
+        let publication: Vec<_> = [('b', &self.publisher), ('c', &self.publish_date)]
+            .into_iter()
+            .filter_map(|(c, v)| v.clone().map(|v| (c, v)))
+            .collect();
+        if !publication.is_empty() {
+            r.data("260", ' ', ' ', publication);
+        }
+        if let Some(pages) = &self.pages {
+            r.data("300", ' ', ' ', vec![('a', format!("{} 页", pages))]);
+        }
+        for a in authors {
+            r.data("700", '1', ' ', vec![('a', a.clone())]);
+        }
+        // 馆藏信息：条码、状态和借阅人
+        let mut holding = vec![(
+            'p',
+            self.barcode.clone().unwrap_or_else(|| self.id.to_string()),
+        )];
+        holding.push(('z', self.state_name()));
+        if let Some(holder) = &self.holder {
+            holding.push(('x', holder.clone()));
+        }
+        r.data("852", ' ', ' ', holding);
+        r
+    }
+}
+
+fn format_time(t: Option<OffsetDateTime>) -> String {
+    t.and_then(|t| t.format(&Rfc3339).ok()).unwrap_or_default()
+}
+
+// MARC21 记录，控制字段和数据字段按添加的顺序输出
+#[derive(Debug, Default)]
+struct MarcRecord {
+    fields: Vec<MarcField>,
+}
+
+#[derive(Debug)]
+enum MarcField {
+    Control(&'static str, String),
+    Data(&'static str, char, char, Vec<(char, String)>),
+}
+
+const SUBFIELD_DELIMITER: u8 = 0x1f;
+const FIELD_TERMINATOR: u8 = 0x1e;
+const RECORD_TERMINATOR: u8 = 0x1d;
+
+// 字段内容中不能出现分隔符
+fn clean(v: &str) -> String {
+    v.chars().filter(|c| !c.is_control()).collect()
+}
+
+impl MarcRecord {
+    fn control(&mut self, tag: &'static str, value: &str) {
+        self.fields.push(MarcField::Control(tag, clean(value)));
+    }
+
+    fn data(&mut self, tag: &'static str, ind1: char, ind2: char, subfields: Vec<(char, String)>) {
+        let subfields = subfields
+            .into_iter()
+            .map(|(c, v)| (c, clean(&v)))
+            .filter(|(_, v)| !v.is_empty())
+            .collect();
+        self.fields
+            .push(MarcField::Data(tag, ind1, ind2, subfields));
+    }
+
+    // 头标区中的记录长度和数据起始地址按字节计算
+    fn leader(record_len: usize, base_address: usize) -> String {
+        format!("{:05}nam a22{:05} i 4500", record_len, base_address)
+    }
+
+    fn to_binary(&self) -> Result<Vec<u8>> {
+        let mut directory = vec![];
+        let mut data = vec![];
+        for field in self.fields.iter() {
+            let mut bytes = vec![];
+            let tag = match field {
+                MarcField::Control(tag, value) => {
+                    bytes.extend(value.as_bytes());
+                    tag
+                }
+                MarcField::Data(tag, ind1, ind2, subfields) => {
+                    bytes.extend(format!("{}{}", ind1, ind2).as_bytes());
+                    for (code, value) in subfields {
+                        bytes.push(SUBFIELD_DELIMITER);
+                        bytes.extend(code.to_string().as_bytes());
+                        bytes.extend(value.as_bytes());
+                    }
+                    tag
+                }
+            };
+            bytes.push(FIELD_TERMINATOR);
+            if bytes.len() > 9999 {
+                bail!("字段 {} 超过 MARC21 的长度限制", tag);
+            }
+            directory.extend(format!("{}{:04}{:05}", tag, bytes.len(), data.len()).as_bytes());
+            data.extend(bytes);
+        }
+        directory.push(FIELD_TERMINATOR);
+        let base_address = 24 + directory.len();
+        let record_len = base_address + data.len() + 1;
+        if record_len > 99999 {
+            bail!("记录超过 MARC21 的长度限制");
+        }
+        let mut record = Self::leader(record_len, base_address).into_bytes();
+        record.extend(directory);
+        record.extend(data);
+        record.push(RECORD_TERMINATOR);
+        Ok(record)
+    }
+
+    fn to_xml(&self) -> Result<String> {
+        let binary = self.to_binary()?;
+        let mut xml = format!(
+            "<record><leader>{}</leader>",
+            String::from_utf8_lossy(&binary[..24])
+        );
+        for field in self.fields.iter() {
+            match field {
+                MarcField::Control(tag, value) => xml.push_str(&format!(
+                    "<controlfield tag=\"{}\">{}</controlfield>",
+                    tag,
+                    escape_xml(value)
+                )),
+                MarcField::Data(tag, ind1, ind2, subfields) => {
+                    xml.push_str(&format!(
+                        "<datafield tag=\"{}\" ind1=\"{}\" ind2=\"{}\">",
+                        tag, ind1, ind2
+                    ));
+                    for (code, value) in subfields {
+                        xml.push_str(&format!(
+                            "<subfield code=\"{}\">{}</subfield>",
+                            code,
+                            escape_xml(value)
+                        ));
+                    }
+                    xml.push_str("</datafield>");
+                }
+            }
+        }
+        xml.push_str("</record>");
+        Ok(xml)
+    }
+}
+
+fn escape_xml(v: &str) -> String {
+    v.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// 未删除的全部馆藏
+pub async fn records(pg: &PgPool) -> sqlx::Result<Vec<ExportRecord>> {
+    sqlx::query_as!(
+        ExportRecord,
+        r#"SELECT b.id,
+       b.barcode,
+       t.id                                 AS title_id,
+       t.isbn,
+       t.title,
+       t.subtitle,
+       coalesce(t.authors, '{}')            AS "authors!",
+       t.publisher,
+       t.publish_date,
+       t.pages,
+       t.price,
+       b.state                              AS "state!: BookStateModel",
+       coalesce(l.borrower, h.requester)    AS holder,
+       a.display_name                       AS "holder_name?",
+       l.due_at                             AS "due_at?",
+       b.created_at
+FROM books b
+         JOIN titles t ON t.id = b.title_id
+         LEFT JOIN loans l ON l.book_id = b.id AND l.returned_at IS NULL
+         LEFT JOIN holds h ON h.book_id = b.id AND h.state = 'ready'
+         LEFT JOIN accounts a ON a.id = coalesce(l.borrower, h.requester)
+WHERE b.deleted_at IS NULL
+ORDER BY b.id"#
+    )
+    .fetch_all(pg)
+    .await
+}
+
+pub async fn export(pg: &PgPool, format: ExportFormat) -> Result<Vec<u8>> {
+    let records = records(pg).await?;
+    write(&records, format)
+}
+
+pub fn write(records: &[ExportRecord], format: ExportFormat) -> Result<Vec<u8>> {
+    match format {
+        ExportFormat::Csv => {
+            // 带 BOM，Excel 打开时才能正确识别中文
+            let mut out = "\u{feff}".as_bytes().to_vec();
+            {
+                let mut w = csv::Writer::from_writer(&mut out);
+                w.write_record(COLUMNS)?;
+                for r in records {
+                    w.write_record(r.values())?;
+                }
+                w.flush()?;
+            }
+            Ok(out)
+        }
+        ExportFormat::Jsonl => {
+            let mut out = vec![];
+            for r in records {
+                serde_json::to_writer(&mut out, &r.to_json())?;
+                out.push(b'\n');
+            }
+            Ok(out)
+        }
+        ExportFormat::Marc21 => {
+            let mut out = vec![];
+            for r in records {
+                out.extend(r.to_marc().to_binary()?);
+            }
+            Ok(out)
+        }
+        ExportFormat::MarcXml => {
+            let mut out = String::from(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<collection xmlns=\"http://www.loc.gov/MARC21/slim\">\n",
+            );
+            for r in records {
+                out.push_str(&r.to_marc().to_xml()?);
+                out.push('\n');
+            }
+            out.push_str("</collection>\n");
+            Ok(out.into_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record() -> ExportRecord {
+        ExportRecord {
+            id: 7,
+            barcode: Some("A001".to_string()),
+            title_id: 3,
+            isbn: Some("9787302590811".to_string()),
+            title: "运筹学".to_string(),
+            subtitle: Some("第5版".to_string()),
+            authors: vec!["甲".to_string(), "乙".to_string()],
+            publisher: Some("清华大学出版社".to_string()),
+            publish_date: None,
+            pages: Some("540".to_string()),
+            price: None,
+            state: BookStateModel::Borrowed,
+            holder: Some("songsong".to_string()),
+            holder_name: None,
+            due_at: None,
+            created_at: time::macros::datetime!(2023-04-01 08:00 UTC),
+        }
+    }
+
+    #[test]
+    fn marc21() {
+        let bytes = write(&[record()], ExportFormat::Marc21).unwrap();
+        let leader = std::str::from_utf8(&bytes[..24]).unwrap();
+        assert_eq!(leader[..5].parse::<usize>().unwrap(), bytes.len());
+        assert_eq!(&leader[5..12], "nam a22");
+        let base: usize = leader[12..17].parse().unwrap();
+        assert_eq!(bytes[base - 1], FIELD_TERMINATOR);
+        assert_eq!(*bytes.last().unwrap(), RECORD_TERMINATOR);
+        // 目录中每一项 12 字节：001 是第一个字段，从 0 开始
+        assert_eq!(&bytes[24..27], b"001");
+        assert_eq!(&bytes[31..36], b"00000");
+        let data = String::from_utf8_lossy(&bytes[base..]);
+        assert!(data.contains("\u{1f}a运筹学\u{1f}b第5版"));
+        assert!(data.contains("\u{1f}pA001\u{1f}z已借出\u{1f}xsongsong"));
+    }
+
+    #[test]
+    fn marcxml_and_csv() {
+        let xml = String::from_utf8(write(&[record()], ExportFormat::MarcXml).unwrap()).unwrap();
+        assert!(xml.contains("<datafield tag=\"700\" ind1=\"1\" ind2=\" \"><subfield code=\"a\">乙</subfield></datafield>"));
+        let csv = String::from_utf8(write(&[record()], ExportFormat::Csv).unwrap()).unwrap();
+        let mut lines = csv.trim_start_matches('\u{feff}').lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("id,barcode,title_id,isbn"));
+        assert!(lines.next().unwrap().contains("甲; 乙"));
+        let json = String::from_utf8(write(&[record()], ExportFormat::Jsonl).unwrap()).unwrap();
+        let v: serde_json::Value = serde_json::from_str(json.trim()).unwrap();
+        assert_eq!(v["id"], 7);
+        assert_eq!(v["state"], "borrowed");
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!("MARCXML".parse::<ExportFormat>(), Ok(ExportFormat::MarcXml));
+        assert!("pdf".parse::<ExportFormat>().is_err());
+    }
+}
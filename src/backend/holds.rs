@@ -0,0 +1,238 @@
+use crate::backend::conf::LoanPolicy;
+use sqlx::{PgPool, Postgres, Transaction};
+use time::{Duration, OffsetDateTime};
+
+#[derive(PartialEq, Debug, Clone, sqlx::Type)]
+#[sqlx(type_name = "text")]
+#[sqlx(rename_all = "lowercase")]
+pub enum HoldStateModel {
+    // 排队中
+    Waiting,
+    // 已有书籍为预约人保留，等待取书
+    Ready,
+    Fulfilled,
+    Cancelled,
+    Expired,
+}
+
+// 对某个书目的一次预约，同一书目的预约按 id 先后排队
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct HoldModel {
+    pub id: i64,
+    pub title_id: i64,
+    pub requester: String,
+    pub requester_name: String,
+    pub state: HoldStateModel,
+    pub book_id: Option<i64>,
+    pub created_at: OffsetDateTime,
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+// 书目当前的预约队列
+pub async fn queue(pg: &PgPool, title_id: &i64) -> sqlx::Result<Vec<HoldModel>> {
+    sqlx::query_as!(
+        HoldModel,
+        r#"SELECT h.id,
+       h.title_id,
+       h.requester,
+       coalesce(a.display_name, h.requester) AS "requester_name!",
+       h.state AS "state: HoldStateModel",
+       h.book_id,
+       h.created_at,
+       h.expires_at
+FROM holds h
+         LEFT JOIN accounts a on a.id = h.requester
+WHERE h.title_id = $1
+  AND h.state in ('waiting', 'ready')
+ORDER BY h.id"#,
+        title_id
+    )
+    .fetch_all(pg)
+    .await
+}
+
+pub async fn place(
+    tc: &mut Transaction<'_, Postgres>,
+    title_id: &i64,
+    requester: &str,
+) -> sqlx::Result<i64> {
+    sqlx::query_scalar!(
+        r#"INSERT INTO holds (title_id, requester, state, created_at)
+VALUES ($1, $2, $3, $4)
+RETURNING id"#,
+        title_id,
+        requester,
+        HoldStateModel::Waiting as _,
+        OffsetDateTime::now_utc()
+    )
+    .fetch_one(&mut *tc)
+    .await
+}
+
+// 预约人取消仍在排队或等待取书的预约，返回被取消的预约
+pub async fn cancel(
+    tc: &mut Transaction<'_, Postgres>,
+    hold_id: &i64,
+    requester: &str,
+) -> sqlx::Result<Option<HoldModel>> {
+    sqlx::query_as!(
+        HoldModel,
+        r#"UPDATE holds h
+SET state = $3
+FROM holds o
+         LEFT JOIN accounts a on a.id = o.requester
+WHERE h.id = o.id
+  AND h.id = $1
+  AND h.requester = $2
+  AND h.state in ('waiting', 'ready')
+RETURNING h.id,
+       h.title_id,
+       h.requester,
+       coalesce(a.display_name, h.requester) AS "requester_name!",
+       h.state AS "state: HoldStateModel",
+       h.book_id,
+       h.created_at,
+       h.expires_at"#,
+        hold_id,
+        requester,
+        HoldStateModel::Cancelled as _
+    )
+    .fetch_optional(&mut *tc)
+    .await
+}
+
+// 书籍所属书目排在最前面的预约人还有同事在排队时，把书籍保留给他
+pub async fn reserve_next(
+    tc: &mut Transaction<'_, Postgres>,
+    book_id: &i64,
+    policy: &LoanPolicy,
+) -> sqlx::Result<Option<HoldModel>> {
+    sqlx::query_as!(
+        HoldModel,
+        r#"UPDATE holds h
+SET state      = $2,
+    book_id    = $1,
+    expires_at = $3
+FROM holds o
+         LEFT JOIN accounts a on a.id = o.requester
+WHERE h.id = o.id
+  AND h.id = (SELECT n.id
+              FROM holds n
+              WHERE n.title_id = (SELECT title_id FROM books WHERE id = $1)
+                AND n.state = 'waiting'
+              ORDER BY n.id
+              LIMIT 1 FOR UPDATE)
+RETURNING h.id,
+       h.title_id,
+       h.requester,
+       coalesce(a.display_name, h.requester) AS "requester_name!",
+       h.state AS "state: HoldStateModel",
+       h.book_id,
+       h.created_at,
+       h.expires_at"#,
+        book_id,
+        HoldStateModel::Ready as _,
+        OffsetDateTime::now_utc() + Duration::days(policy.pickup_days)
+    )
+    .fetch_optional(&mut *tc)
+    .await
+}
+
+// 借出书籍时，借阅人对这本书的保留以及对同一书目的排队都算作已完成
+pub async fn fulfill(
+    tc: &mut Transaction<'_, Postgres>,
+    book_id: &i64,
+    borrower: &str,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"UPDATE holds
+SET state   = $3,
+    book_id = $1
+WHERE requester = $2
+  AND title_id = (SELECT title_id FROM books WHERE id = $1)
+  AND (state = 'waiting' OR (state = 'ready' AND book_id = $1))"#,
+        book_id,
+        borrower,
+        HoldStateModel::Fulfilled as _
+    )
+    .execute(&mut *tc)
+    .await?;
+    Ok(())
+}
+
+// 为预约人保留的书籍遗失或删除时，预约回到原来的排队位置
+pub async fn release(tc: &mut Transaction<'_, Postgres>, book_id: &i64) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"UPDATE holds
+SET state      = $2,
+    book_id    = null,
+    expires_at = null
+WHERE book_id = $1
+  AND state = 'ready'"#,
+        book_id,
+        HoldStateModel::Waiting as _
+    )
+    .execute(&mut *tc)
+    .await?;
+    Ok(())
+}
+
+// 书籍所属书目是否还有除 requester 以外的同事在排队
+pub async fn has_waiting(
+    tc: &mut Transaction<'_, Postgres>,
+    book_id: &i64,
+    requester: &str,
+) -> sqlx::Result<bool> {
+    sqlx::query_scalar!(
+        r#"SELECT exists(SELECT 1
+              FROM holds
+              WHERE title_id = (SELECT title_id FROM books WHERE id = $1)
+                AND requester != $2
+                AND state = 'waiting') AS "exists!""#,
+        book_id,
+        requester
+    )
+    .fetch_one(&mut *tc)
+    .await
+}
+
+// 超过取书期限仍未借出的书籍
+pub async fn overdue_books(pg: &PgPool) -> sqlx::Result<Vec<i64>> {
+    sqlx::query_scalar!(
+        r#"SELECT book_id AS "book_id!" FROM holds WHERE state = 'ready' AND expires_at < $1"#,
+        OffsetDateTime::now_utc()
+    )
+    .fetch_all(pg)
+    .await
+}
+
+// 把书籍上已经超过取书期限的保留标记为过期，返回过期的预约
+pub async fn expire(
+    tc: &mut Transaction<'_, Postgres>,
+    book_id: &i64,
+) -> sqlx::Result<Option<HoldModel>> {
+    sqlx::query_as!(
+        HoldModel,
+        r#"UPDATE holds h
+SET state = $2
+FROM holds o
+         LEFT JOIN accounts a on a.id = o.requester
+WHERE h.id = o.id
+  AND h.book_id = $1
+  AND h.state = 'ready'
+  AND h.expires_at < $3
+RETURNING h.id,
+       h.title_id,
+       h.requester,
+       coalesce(a.display_name, h.requester) AS "requester_name!",
+       h.state AS "state: HoldStateModel",
+       h.book_id,
+       h.created_at,
+       h.expires_at"#,
+        book_id,
+        HoldStateModel::Expired as _,
+        OffsetDateTime::now_utc()
+    )
+    .fetch_optional(&mut *tc)
+    .await
+}
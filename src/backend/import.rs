@@ -0,0 +1,375 @@
+use crate::api::import::{ColumnMapping, ImportRowUI, ImportStatus};
+use crate::api::isbn;
+use crate::backend::books::{insert_copy, insert_title, title_by_isbn, BookMS, ISBNData};
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use time::OffsetDateTime;
+
+// 一次最多导入的行数，每一行都可能要查询检索服务
+pub const MAX_IMPORT_ROWS: usize = 2000;
+
+// 读取出来的表格，第一行作为表头
+#[derive(Debug, Clone, Default)]
+pub struct Sheet {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+// 按映射取出来的一行，line 为文件中的行号
+#[derive(Debug, Clone, Default)]
+pub struct ImportRow {
+    pub line: usize,
+    pub isbn: String,
+    pub barcode: Option<String>,
+    pub title: Option<String>,
+    pub location: Option<String>,
+    pub donor: Option<String>,
+}
+
+// 浏览器上传的文件是 data URL
+pub fn read_data_url(file_name: &str, data_url: &str) -> Result<Sheet> {
+    let data = data_url
+        .split_once(',')
+        .map(|(_, d)| d)
+        .ok_or(anyhow!("文件内容格式不正确"))?;
+    let bytes = base64::engine::general_purpose::STANDARD.decode(data)?;
+    read_sheet(file_name, &bytes)
+}
+
+// 按扩展名识别文件格式，支持 CSV 和 XLSX
+pub fn read_sheet(file_name: &str, bytes: &[u8]) -> Result<Sheet> {
+    let name = file_name.to_lowercase();
+    let mut rows = if name.ends_with(".csv") || name.ends_with(".txt") {
+        read_csv(bytes)?
+    } else if name.ends_with(".xlsx") || name.ends_with(".xls") {
+        read_xlsx(bytes)?
+    } else {
+        bail!("只支持 CSV 和 XLSX 格式的文件")
+    };
+    rows.retain(|r| r.iter().any(|c| !c.trim().is_empty()));
+    if rows.is_empty() {
+        bail!("文件是空的");
+    }
+    let headers = rows
+        .remove(0)
+        .into_iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+    if rows.len() > MAX_IMPORT_ROWS {
+        bail!("一次最多导入 {} 行，请拆分文件", MAX_IMPORT_ROWS);
+    }
+    Ok(Sheet { headers, rows })
+}
+
+// Excel 另存的 CSV 常常是 GBK 编码，不是合法的 UTF-8 时按 GBK 解码
+fn read_csv(bytes: &[u8]) -> Result<Vec<Vec<String>>> {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(t) => t.to_string(),
+        Err(_) => encoding_rs::GBK.decode(bytes).0.into_owned(),
+    };
+    let text = text.trim_start_matches('\u{feff}');
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let mut rows = vec![];
+    for record in reader.records() {
+        rows.push(record?.iter().map(|c| c.to_string()).collect());
+    }
+    Ok(rows)
+}
+
+// 只读取第一个工作表
+fn read_xlsx(bytes: &[u8]) -> Result<Vec<Vec<String>>> {
+    use calamine::Reader;
+    let mut workbook = calamine::open_workbook_auto_from_rs(Cursor::new(bytes.to_vec()))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or(anyhow!("文件中没有工作表"))??;
+    Ok(range
+        .rows()
+        .map(|r| r.iter().map(|c| c.to_string()).collect())
+        .collect())
+}
+
+// 按列映射取出每一行的字段，ISBN 和书名至少要映射一列
+pub fn map_rows(sheet: &Sheet, mapping: &ColumnMapping) -> Result<Vec<ImportRow>> {
+    let column = |name: &str| -> Result<Option<usize>> {
+        if name.is_empty() {
+            return Ok(None);
+        }
+        sheet
+            .headers
+            .iter()
+            .position(|h| h == name)
+            .map(Some)
+            .ok_or(anyhow!("文件中没有“{}”这一列", name))
+    };
+    let (isbn, barcode, title) = (
+        column(&mapping.isbn)?,
+        column(&mapping.barcode)?,
+        column(&mapping.title)?,
+    );
+    let (location, donor) = (column(&mapping.location)?, column(&mapping.donor)?);
+    if isbn.is_none() && title.is_none() {
+        bail!("请至少指定 ISBN 或书名所在的列");
+    }
+    let cell = |row: &Vec<String>, i: Option<usize>| {
+        i.and_then(|i| row.get(i))
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+    };
+    Ok(sheet
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| ImportRow {
+            line: i + 2,
+            isbn: cell(row, isbn).unwrap_or_default(),
+            barcode: cell(row, barcode),
+            title: cell(row, title),
+            location: cell(row, location),
+            donor: cell(row, donor),
+        })
+        .collect())
+}
+
+// 每一行的入库方式
+enum Plan {
+    // 已有书目
+    Existing(i64),
+    // 同一文件前面的行会新建这个 ISBN 的书目
+    SameAs(String),
+    // 新建书目
+    Create(Option<String>, ISBNData),
+    // 跳过
+    Skip,
+}
+
+impl BookMS {
+    // 逐行检查并查询 ISBN，commit 为 false 时只返回预览；
+    // 为 true 时在一个事务中入库所有可以入库的行，重复和失败的行跳过
+    pub async fn import(
+        &self,
+        rows: &[ImportRow],
+        operator: &str,
+        commit: bool,
+    ) -> Result<Vec<ImportRowUI>> {
+        let barcodes: Vec<String> = rows.iter().filter_map(|r| r.barcode.clone()).collect();
+        let existing_barcodes: HashSet<String> = sqlx::query_scalar!(
+            r#"SELECT barcode AS "barcode!" FROM books WHERE barcode = ANY ($1)"#,
+            &barcodes
+        )
+        .fetch_all(self.pool())
+        .await?
+        .into_iter()
+        .collect();
+        let mut seen_barcodes = HashSet::new();
+        let mut new_isbns = HashSet::new();
+        let mut results = vec![];
+        let mut plans = vec![];
+        for row in rows {
+            let mut result = ImportRowUI {
+                line: row.line,
+                isbn: row.isbn.clone(),
+                barcode: row.barcode.clone().unwrap_or_default(),
+                title: row.title.clone().unwrap_or_default(),
+                status: ImportStatus::Failed,
+                message: "".to_string(),
+            };
+            let plan = self
+                .plan_row(
+                    row,
+                    &mut result,
+                    &existing_barcodes,
+                    &mut seen_barcodes,
+                    &mut new_isbns,
+                )
+                .await?;
+            results.push(result);
+            plans.push(plan);
+        }
+        if !commit {
+            return Ok(results);
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let mut created: HashMap<String, i64> = HashMap::new();
+        let mut tc = self.pool().begin().await?;
+        for ((row, plan), result) in rows.iter().zip(plans).zip(results.iter_mut()) {
+            let (title_id, new_title) = match plan {
+                Plan::Skip => continue,
+                Plan::Existing(id) => (id, false),
+                Plan::SameAs(isbn) => match created.get(&isbn) {
+                    Some(id) => (*id, false),
+                    None => continue,
+                },
+                Plan::Create(isbn, data) => {
+                    let (id, new_title) =
+                        match insert_title(&mut tc, isbn.as_deref(), &data, now).await? {
+                            Some(id) => (id, true),
+                            // 预览之后该 ISBN 已经被其他人入库，作为副本挂到已有书目下
+                            None => {
+                                let isbn = isbn.as_deref().unwrap_or_default();
+                                let id = title_by_isbn(&mut tc, isbn)
+                                    .await?
+                                    .ok_or_else(|| anyhow!("ISBN {} 的书目不存在", isbn))?;
+                                (id, false)
+                            }
+                        };
+                    if let Some(isbn) = isbn {
+                        created.insert(isbn, id);
+                    }
+                    (id, new_title)
+                }
+            };
+            let payload = serde_json::json!({
+                "new_title": new_title,
+                "manual": result.status == ImportStatus::Manual,
+                "import": true,
+                "location": row.location,
+                "donor": row.donor,
+            });
+            let location = row.location.as_deref();
+            let bid = insert_copy(
+                &mut tc,
+                title_id,
+                &row.barcode,
+                location,
+                operator,
+                payload,
+                self.loan(),
+            )
+            .await?;
+            result.status = ImportStatus::Stored;
+            result.message = format!("副本 {}", bid);
+        }
+        tc.commit().await?;
+        Ok(results)
+    }
+
+    async fn plan_row(
+        &self,
+        row: &ImportRow,
+        result: &mut ImportRowUI,
+        existing_barcodes: &HashSet<String>,
+        seen_barcodes: &mut HashSet<String>,
+        new_isbns: &mut HashSet<String>,
+    ) -> Result<Plan> {
+        if let Some(barcode) = &row.barcode {
+            if existing_barcodes.contains(barcode) || !seen_barcodes.insert(barcode.clone()) {
+                result.status = ImportStatus::Duplicate;
+                result.message = format!("条码 {} 已经存在", barcode);
+                return Ok(Plan::Skip);
+            }
+        }
+        let manual = |title: &Option<String>, isbn: Option<String>| {
+            title.as_ref().map(|t| {
+                Plan::Create(
+                    isbn,
+                    ISBNData {
+                        name: t.clone(),
+                        ..Default::default()
+                    },
+                )
+            })
+        };
+        if row.isbn.is_empty() {
+            return Ok(match manual(&row.title, None) {
+                Some(plan) => {
+                    result.status = ImportStatus::Manual;
+                    plan
+                }
+                None => {
+                    result.message = "缺少 ISBN 和书名".to_string();
+                    Plan::Skip
+                }
+            });
+        }
+        let isbn = match isbn::normalize(&row.isbn) {
+            Ok(i) => i,
+            Err(e) => {
+                result.message = e.to_string();
+                return Ok(Plan::Skip);
+            }
+        };
+        result.isbn = isbn.clone();
+        let existing = sqlx::query!(
+            "SELECT id, title FROM titles WHERE isbn = ANY ($1) LIMIT 1",
+            &isbn::variants(&isbn)
+        )
+        .fetch_optional(self.pool())
+        .await?
+        .map(|t| (t.id, t.title));
+        if let Some((id, title)) = existing {
+            result.status = ImportStatus::Copy;
+            result.title = title;
+            result.message = "已有书目".to_string();
+            return Ok(Plan::Existing(id));
+        }
+        if new_isbns.contains(&isbn) {
+            result.status = ImportStatus::Copy;
+            result.message = "与前面的行是同一本书".to_string();
+            return Ok(Plan::SameAs(isbn));
+        }
+        match self.lookup_isbn(&isbn).await {
+            Ok(data) => {
+                new_isbns.insert(isbn.clone());
+                result.status = ImportStatus::New;
+                result.title = data.name.clone();
+                Ok(Plan::Create(Some(isbn), data))
+            }
+            Err(e) => match manual(&row.title, Some(isbn.clone())) {
+                Some(plan) => {
+                    new_isbns.insert(isbn);
+                    result.status = ImportStatus::Manual;
+                    result.message = "检索服务没有查到，按书名编目".to_string();
+                    Ok(plan)
+                }
+                None => {
+                    result.message = format!("{:#}", e);
+                    Ok(Plan::Skip)
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv_sheet() {
+        let csv = "ISBN,书名,条码\n9787302547648,,A001\n\n,内部技术报告,A002\n";
+        let sheet = read_sheet("books.csv", csv.as_bytes()).unwrap();
+        assert_eq!(sheet.headers, vec!["ISBN", "书名", "条码"]);
+        assert_eq!(sheet.rows.len(), 2);
+        let rows = map_rows(&sheet, &ColumnMapping::detect(&sheet.headers)).unwrap();
+        assert_eq!(rows[0].isbn, "9787302547648");
+        assert_eq!(rows[0].title, None);
+        assert_eq!(rows[1].barcode.as_deref(), Some("A002"));
+        assert_eq!(rows[1].title.as_deref(), Some("内部技术报告"));
+    }
+
+    #[test]
+    fn gbk_csv() {
+        let (bytes, _, _) = encoding_rs::GBK.encode("书名\n运筹学\n");
+        let sheet = read_sheet("books.csv", &bytes).unwrap();
+        assert_eq!(sheet.headers, vec!["书名"]);
+        assert_eq!(sheet.rows, vec![vec!["运筹学".to_string()]]);
+    }
+
+    #[test]
+    fn missing_column() {
+        let sheet = read_sheet("books.csv", "ISBN\n9787302547648\n".as_bytes()).unwrap();
+        let mapping = ColumnMapping {
+            isbn: "书号".to_string(),
+            ..Default::default()
+        };
+        assert!(map_rows(&sheet, &mapping).is_err());
+        assert!(map_rows(&sheet, &ColumnMapping::default()).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use crate::backend::books::ISBNData;
+use sqlx::types::Json;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+// 一条 ISBN 检索结果缓存
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct IsbnMetadataModel {
+    pub isbn: String,
+    pub data: Json<ISBNData>,
+    pub raw: serde_json::Value,
+    pub fetched_at: OffsetDateTime,
+}
+
+pub async fn get(pg: &PgPool, isbn: &str) -> sqlx::Result<Option<IsbnMetadataModel>> {
+    sqlx::query_as!(
+        IsbnMetadataModel,
+        r#"SELECT isbn, data AS "data: Json<ISBNData>", raw, fetched_at FROM isbn_metadata WHERE isbn = $1"#,
+        isbn
+    )
+    .fetch_optional(pg)
+    .await
+}
+
+// 写入或覆盖缓存
+pub async fn put(
+    pg: &PgPool,
+    isbn: &str,
+    data: &ISBNData,
+    raw: &serde_json::Value,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO isbn_metadata (isbn, data, raw, fetched_at)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (isbn) DO UPDATE SET data       = excluded.data,
+                                 raw        = excluded.raw,
+                                 fetched_at = excluded.fetched_at"#,
+        isbn,
+        Json(data) as _,
+        raw,
+        OffsetDateTime::now_utc()
+    )
+    .execute(pg)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use crate::backend::conf::LoanPolicy;
+use sqlx::{Postgres, Transaction};
+use time::{Duration, OffsetDateTime};
+
+// 一次借阅：从借出开始，到归还为止
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct LoanModel {
+    pub id: i64,
+    pub book_id: i64,
+    pub borrower: String,
+    pub borrowed_at: OffsetDateTime,
+    pub due_at: OffsetDateTime,
+    pub returned_at: Option<OffsetDateTime>,
+    pub renewals: i32,
+}
+
+// 违反借阅规则的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoanDenied {
+    // 同时借阅的本数已达上限
+    TooMany(i64),
+    // 有逾期未还的书籍
+    Overdue(i64),
+}
+
+impl std::fmt::Display for LoanDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoanDenied::TooMany(max) => write!(f, "最多同时借阅 {} 本，请先归还", max),
+            LoanDenied::Overdue(n) => write!(f, "有 {} 本书已逾期，请先归还", n),
+        }
+    }
+}
+
+// 按借阅规则检查借阅人能否再借一本。会锁住借阅人的账号，同一个人的并发借阅在这里排队，
+// 保证计数和随后的借出在同一个事务里
+pub async fn check(
+    tc: &mut Transaction<'_, Postgres>,
+    borrower: &str,
+    role: &str,
+    policy: &LoanPolicy,
+) -> sqlx::Result<Result<(), LoanDenied>> {
+    sqlx::query!("SELECT id FROM accounts WHERE id = $1 FOR UPDATE", borrower)
+        .fetch_optional(&mut *tc)
+        .await?;
+    let counts = sqlx::query!(
+        r#"SELECT count(*) AS "open!", count(*) FILTER (WHERE due_at < $2) AS "overdue!"
+FROM loans
+WHERE borrower = $1
+  AND returned_at is null"#,
+        borrower,
+        OffsetDateTime::now_utc()
+    )
+    .fetch_one(&mut *tc)
+    .await?;
+    let (open, overdue) = (counts.open, counts.overdue);
+    if policy.block_overdue && overdue > 0 {
+        return Ok(Err(LoanDenied::Overdue(overdue)));
+    }
+    let max = policy.max_loans_for(role);
+    if max > 0 && open >= max {
+        return Ok(Err(LoanDenied::TooMany(max)));
+    }
+    Ok(Ok(()))
+}
+
+// 借出书籍时创建借阅记录，借阅 days 天
+pub async fn open(
+    tc: &mut Transaction<'_, Postgres>,
+    book_id: &i64,
+    borrower: &str,
+    days: i64,
+) -> sqlx::Result<LoanModel> {
+    let now = OffsetDateTime::now_utc();
+    sqlx::query_as!(
+        LoanModel,
+        r#"INSERT INTO loans (book_id, borrower, borrowed_at, due_at)
+VALUES ($1, $2, $3, $4)
+RETURNING id, book_id, borrower, borrowed_at, due_at, returned_at, renewals"#,
+        book_id,
+        borrower,
+        now,
+        now + Duration::days(days)
+    )
+    .fetch_one(&mut *tc)
+    .await
+}
+
+// 结束书籍当前未归还的借阅记录，没有借阅记录时什么也不做
+pub async fn close(tc: &mut Transaction<'_, Postgres>, book_id: &i64) -> sqlx::Result<()> {
+    sqlx::query!(
+        "UPDATE loans SET returned_at = $1 WHERE book_id = $2 AND returned_at is null",
+        OffsetDateTime::now_utc(),
+        book_id
+    )
+    .execute(&mut *tc)
+    .await?;
+    Ok(())
+}
+
+// 续借：从当前应还日期（已逾期则从今天）起再延长 days 天，超过续借次数上限时返回 None
+pub async fn renew(
+    tc: &mut Transaction<'_, Postgres>,
+    book_id: &i64,
+    days: i64,
+    max_renewals: i32,
+) -> sqlx::Result<Option<LoanModel>> {
+    sqlx::query_as!(
+        LoanModel,
+        r#"UPDATE loans
+SET due_at   = greatest(due_at, $2) + make_interval(days => $3::int),
+    renewals = renewals + 1
+WHERE book_id = $1
+  AND returned_at is null
+  AND renewals < $4
+RETURNING id, book_id, borrower, borrowed_at, due_at, returned_at, renewals"#,
+        book_id,
+        OffsetDateTime::now_utc(),
+        days as i32,
+        max_renewals
+    )
+    .fetch_optional(&mut *tc)
+    .await
+}
@@ -1,5 +1,15 @@
 pub mod auth;
 pub mod books;
 pub mod conf;
+pub mod covers;
 pub mod db;
+pub mod export;
+pub mod holds;
+pub mod import;
+pub mod isbn_cache;
 pub mod ldap;
+pub mod loans;
+pub mod my;
+pub mod pinyin;
+pub mod query;
+pub mod stats;
@@ -0,0 +1,194 @@
+use crate::backend::books::{BookMS, BookModel, BookStateModel, ChangeActionModel};
+use crate::backend::holds::HoldStateModel;
+use anyhow::Result;
+use time::OffsetDateTime;
+
+// 用户自己的预约，带上书目信息和在队列中的位置
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct MyHoldModel {
+    pub id: i64,
+    pub title_id: i64,
+    pub title: String,
+    pub thumbnail: Option<String>,
+    pub state: HoldStateModel,
+    // 在队列中的位置，从 1 开始
+    pub position: i64,
+    // 已保留的书籍，还在排队时取该书目的任意一本，用于跳转到书籍详情
+    pub book_id: Option<i64>,
+    pub created_at: OffsetDateTime,
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+// 一次借阅记录，由 change_logs 中的借出记录和其后第一条归还（或遗失、重置、删除）记录组成
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct BorrowRecordModel {
+    pub id: i64,
+    pub book_id: i64,
+    pub title_id: i64,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub borrowed_at: OffsetDateTime,
+    pub ended_at: Option<OffsetDateTime>,
+    pub ended_by: Option<ChangeActionModel>,
+}
+
+impl BookMS {
+    // 用户当前借着的书，最早到期的排在前面
+    pub async fn borrowed_by(&self, uid: &str) -> Result<Vec<BookModel>> {
+        let books = sqlx::query_as!(
+            BookModel,
+            r#"SELECT id AS "id!",
+       title_id AS "title_id!",
+       barcode,
+       isbn,
+       title AS "title!",
+       authors AS "authors!",
+       publisher,
+       publish_date,
+       created_at AS "created_at!",
+       state AS "state!: BookStateModel",
+       operator AS "operator!",
+       operator_name AS "operator_name!",
+       operate_at AS "operate_at!",
+       thumbnail, deleted_at, log_id AS "log_id!",
+       subtitle, translators, pages, price, description,
+       author_intro, tags, douban_id, douban_score,
+       copies AS "copies!", available_copies AS "available_copies!",
+       borrower, borrower_name, due_at, renewals,
+       reserved_for, reserved_for_name, reserved_until,
+       location
+FROM book_details
+WHERE deleted_at is null
+  AND borrower = $1
+ORDER BY due_at, id"#,
+            uid
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(books)
+    }
+
+    // 用户已经归还、等待管理员确认的书，书籍最后一条记录就是这次归还
+    pub async fn returned_by(&self, uid: &str) -> Result<Vec<BookModel>> {
+        let books = sqlx::query_as!(
+            BookModel,
+            r#"SELECT id AS "id!",
+       title_id AS "title_id!",
+       barcode,
+       isbn,
+       title AS "title!",
+       authors AS "authors!",
+       publisher,
+       publish_date,
+       created_at AS "created_at!",
+       state AS "state!: BookStateModel",
+       operator AS "operator!",
+       operator_name AS "operator_name!",
+       operate_at AS "operate_at!",
+       thumbnail, deleted_at, log_id AS "log_id!",
+       subtitle, translators, pages, price, description,
+       author_intro, tags, douban_id, douban_score,
+       copies AS "copies!", available_copies AS "available_copies!",
+       borrower, borrower_name, due_at, renewals,
+       reserved_for, reserved_for_name, reserved_until,
+       location
+FROM book_details
+WHERE deleted_at is null
+  AND state = 'returned'
+  AND operator = $1
+ORDER BY operate_at"#,
+            uid
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(books)
+    }
+
+    // 用户还在排队或等待取书的预约
+    pub async fn holds_of(&self, uid: &str) -> Result<Vec<MyHoldModel>> {
+        let holds = sqlx::query_as!(
+            MyHoldModel,
+            r#"SELECT h.id,
+       h.title_id,
+       t.title,
+       t.thumbnail,
+       h.state AS "state: HoldStateModel",
+       (SELECT count(*)
+        FROM holds q
+        WHERE q.title_id = h.title_id
+          AND q.state in ('waiting', 'ready')
+          AND q.id <= h.id) AS "position!",
+       coalesce(h.book_id, (SELECT min(s.id)
+                            FROM books s
+                            WHERE s.title_id = h.title_id
+                              AND s.deleted_at is null)) AS book_id,
+       h.created_at,
+       h.expires_at
+FROM holds h
+         JOIN titles t on t.id = h.title_id
+WHERE h.requester = $1
+  AND h.state in ('waiting', 'ready')
+ORDER BY h.id"#,
+            uid
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(holds)
+    }
+
+    // 用户的全部借阅记录，最近借出的在前，同时返回总数
+    pub async fn borrowing_history(
+        &self,
+        uid: &str,
+        limit: &i64,
+        offset: &i64,
+    ) -> Result<(Vec<BorrowRecordModel>, i64)> {
+        let records = sqlx::query_as!(
+            BorrowRecordModel,
+            r#"SELECT cl.id,
+       b.id          AS book_id,
+       b.title_id,
+       t.title,
+       coalesce(t.authors, '{}') AS "authors!",
+       cl.operate_at AS borrowed_at,
+       e.operate_at  AS "ended_at?",
+       e.action      AS "ended_by?: ChangeActionModel"
+FROM change_logs cl
+         JOIN books b on b.id = cl.source_id
+         JOIN titles t on t.id = b.title_id
+         LEFT JOIN LATERAL (SELECT r.operate_at, r.action
+                            FROM change_logs r
+                            WHERE r.source_type = 'book'
+                              AND r.source_id = cl.source_id
+                              AND r.id > cl.id
+                              AND r.action in ('returned', 'lost', 'reset', 'deleted')
+                            ORDER BY r.id
+                            LIMIT 1) e on true
+WHERE cl.source_type = 'book'
+  AND cl.action = 'borrowed'
+  AND cl.operator = $1
+ORDER BY cl.id DESC
+LIMIT $2 OFFSET $3"#,
+            uid,
+            limit,
+            offset
+        )
+        .fetch_all(self.pool())
+        .await?;
+        let total = sqlx::query_scalar!(
+            r#"SELECT count(*) AS "count!"
+FROM change_logs cl
+WHERE cl.source_type = 'book'
+  AND cl.action = 'borrowed'
+  AND cl.operator = $1"#,
+            uid
+        )
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok((records, total))
+    }
+}
@@ -0,0 +1,168 @@
+use ::pinyin::ToPinyin;
+use anyhow::Result;
+use sqlx::PgPool;
+
+// 音节之间的分隔符，搜索全拼时只从音节开头匹配
+const SYLLABLE_SEPARATOR: char = '\'';
+// 搜索词去掉空格后至少要有这么多个字符才按拼音匹配，避免一两个字母匹配到大量书目
+const MIN_PATTERN_LEN: usize = 2;
+// 补齐拼音时每批处理的书目数，每批一个事务
+const BACKFILL_BATCH: i64 = 500;
+
+// 书名和作者的拼音，多音字取常用读音
+#[derive(Debug, Default, PartialEq)]
+pub struct PinyinIndex {
+    // 全拼，音节之间用 ' 隔开，如 yun'chou'xue
+    pub full: String,
+    // 首字母，如 ycx
+    pub initials: String,
+}
+
+// 汉字转成拼音，字母和数字转成小写保留，其它字符丢弃。
+// 每个汉字和每个英文单词在全拼中是一个音节，在首字母中只保留第一个字母
+fn spell(text: &str) -> (String, String) {
+    let mut full = String::new();
+    let mut initials = String::new();
+    let mut in_word = false;
+    for c in text.chars() {
+        if let Some(p) = c.to_pinyin() {
+            if !full.is_empty() {
+                full.push(SYLLABLE_SEPARATOR);
+            }
+            full.push_str(p.plain());
+            initials.push_str(p.first_letter());
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            let lower = c.to_lowercase().collect::<String>();
+            if !in_word {
+                if !full.is_empty() {
+                    full.push(SYLLABLE_SEPARATOR);
+                }
+                initials.push_str(&lower);
+            }
+            full.push_str(&lower);
+            in_word = true;
+        } else {
+            in_word = false;
+        }
+    }
+    (full, initials)
+}
+
+// 书名和每个作者分别拼写，用空格隔开，避免搜索词跨字段匹配
+pub fn index(title: &str, authors: &[String]) -> PinyinIndex {
+    let mut full = vec![];
+    let mut initials = vec![];
+    for text in std::iter::once(title).chain(authors.iter().map(|a| a.as_str())) {
+        let (f, i) = spell(text);
+        if !f.is_empty() {
+            full.push(f);
+            initials.push(i);
+        }
+    }
+    PinyinIndex {
+        full: full.join(" "),
+        initials: initials.join(" "),
+    }
+}
+
+// 拼音搜索的匹配模式
+#[derive(Debug, PartialEq)]
+pub struct PinyinPattern {
+    // 匹配全拼的正则，必须从书名或作者的某个音节开头开始，音节之间的分隔符可有可无
+    pub full: String,
+    // 匹配首字母的 LIKE 模式，首字母每个字符就是一个音节，不需要另外对齐
+    pub initials: String,
+}
+
+// 搜索词只有字母和数字、去掉空格后长度足够时才按拼音匹配
+pub fn pattern(q: &str) -> Option<PinyinPattern> {
+    let q: String = q
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+    if q.chars().count() < MIN_PATTERN_LEN
+        || !q.chars().all(|c| c.is_ascii_alphanumeric())
+        || !q.chars().any(|c| c.is_ascii_alphabetic())
+    {
+        return None;
+    }
+    let optional = format!("{}?", SYLLABLE_SEPARATOR);
+    let letters = q.chars().map(String::from).collect::<Vec<_>>();
+    Some(PinyinPattern {
+        full: format!("(^|[ {}]){}", SYLLABLE_SEPARATOR, letters.join(&optional)),
+        initials: format!("%{}%", q),
+    })
+}
+
+// 为还没有拼音的书目补齐拼音，all 为 true 时全部重新生成，返回更新的书目数。
+// 按 id 分批处理，服务启动后也会在后台执行一次
+pub async fn backfill(pg: &PgPool, all: bool) -> Result<u64> {
+    let mut after = 0;
+    let mut total = 0;
+    loop {
+        let titles = sqlx::query!(
+            r#"SELECT id, title, coalesce(authors, '{}') AS "authors!"
+FROM titles
+WHERE id > $2
+  AND ($1 OR pinyin is null)
+ORDER BY id
+LIMIT $3"#,
+            all,
+            after,
+            BACKFILL_BATCH
+        )
+        .fetch_all(pg)
+        .await?;
+        let last = match titles.last() {
+            Some(t) => t.id,
+            None => return Ok(total),
+        };
+        let mut tc = pg.begin().await?;
+        for t in &titles {
+            let py = index(&t.title, &t.authors);
+            sqlx::query!(
+                "UPDATE titles SET pinyin = $1, pinyin_initials = $2 WHERE id = $3",
+                py.full,
+                py.initials,
+                t.id
+            )
+            .execute(&mut tc)
+            .await?;
+        }
+        tc.commit().await?;
+        after = last;
+        total += titles.len() as u64;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spell_titles() {
+        let py = index("运筹学（第5版）", &["《运筹学》教材编写组".to_string()]);
+        assert_eq!(
+            py.full,
+            "yun'chou'xue'di'5'ban yun'chou'xue'jiao'cai'bian'xie'zu"
+        );
+        assert_eq!(py.initials, "ycxd5b ycxjcbxz");
+        let py = index("Rust 编程之道", &["张汉东".to_string()]);
+        assert_eq!(py.full, "rust'bian'cheng'zhi'dao zhang'han'dong");
+        assert_eq!(py.initials, "rbczd zhd");
+    }
+
+    #[test]
+    fn search_pattern() {
+        let p = pattern("chou xue").unwrap();
+        assert_eq!(p.full, "(^|[ '])c'?h'?o'?u'?x'?u'?e");
+        assert_eq!(p.initials, "%chouxue%");
+        assert_eq!(pattern("YCX").unwrap().initials, "%ycx%");
+        assert_eq!(pattern("y"), None);
+        assert_eq!(pattern("9787302590811"), None);
+        assert_eq!(pattern("运筹学"), None);
+        assert_eq!(pattern("100%"), None);
+    }
+}
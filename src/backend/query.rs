@@ -0,0 +1,423 @@
+use crate::api::isbn;
+use crate::api::search::{self, BookQuery, BookSort, FacetCount, FacetField, Facets};
+use crate::backend::books::{BookMS, BookModel};
+use crate::backend::pinyin;
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+// 筛选只用到书目、副本和当前借阅，统计分面时不需要 book_details 中的其它关联
+const FILTER_TABLES: &str = r#"books b
+         JOIN titles t on b.title_id = t.id
+         LEFT JOIN loans l on l.book_id = b.id and l.returned_at is null
+         LEFT JOIN accounts la on la.id = l.borrower"#;
+
+// 出版日期的格式各不相同，取开头的四位数字作为年份
+const PUBLISH_YEAR: &str = "substring(t.publish_date from '^[0-9]{4}')";
+
+// 搜索词转换出来的各种匹配方式
+struct SearchTerms {
+    like: String,
+    isbns: Vec<String>,
+    tsquery: Option<String>,
+    pinyin: Option<pinyin::PinyinPattern>,
+}
+
+impl SearchTerms {
+    fn new(q: &Option<String>) -> Option<Self> {
+        let q = q.as_deref().filter(|q| !q.trim().is_empty())?;
+        Some(Self {
+            like: search::like_pattern(q),
+            isbns: isbn::variants(q),
+            tsquery: search::tsquery(q),
+            pinyin: pinyin::pattern(q),
+        })
+    }
+}
+
+// 追加查询条件，copies 为 true 时按副本查询，条码也参与搜索
+fn push_filters(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    query: &BookQuery,
+    terms: &Option<SearchTerms>,
+    copies: bool,
+) {
+    qb.push(" WHERE b.deleted_at is null");
+    if let Some(s) = terms {
+        qb.push(" AND (t.search_text LIKE ")
+            .push_bind(s.like.clone());
+        qb.push(" OR lower(t.isbn) LIKE ").push_bind(s.like.clone());
+        qb.push(" OR t.isbn = ANY (")
+            .push_bind(s.isbns.clone())
+            .push(")");
+        if let Some(ts) = &s.tsquery {
+            qb.push(" OR t.search_vector @@ to_tsquery('simple', ")
+                .push_bind(ts.clone())
+                .push(")");
+        }
+        if let Some(py) = &s.pinyin {
+            qb.push(" OR t.pinyin ~ ").push_bind(py.full.clone());
+            qb.push(" OR t.pinyin_initials LIKE ")
+                .push_bind(py.initials.clone());
+        }
+        if copies {
+            qb.push(" OR lower(b.barcode) LIKE ")
+                .push_bind(s.like.clone());
+        }
+        qb.push(")");
+    }
+    if let Some(state) = &query.state {
+        qb.push(" AND b.state = ").push_bind(state.code());
+    }
+    if let Some(author) = &query.author {
+        qb.push(" AND ")
+            .push_bind(author.clone())
+            .push(" = ANY (t.authors)");
+    }
+    if let Some(publisher) = &query.publisher {
+        qb.push(" AND t.publisher = ").push_bind(publisher.clone());
+    }
+    if let Some(from) = query.year_from {
+        qb.push(format!(" AND {}::int >= ", PUBLISH_YEAR))
+            .push_bind(from);
+    }
+    if let Some(to) = query.year_to {
+        qb.push(format!(" AND {}::int <= ", PUBLISH_YEAR))
+            .push_bind(to);
+    }
+    if let Some(tag) = &query.tag {
+        qb.push(" AND ")
+            .push_bind(tag.clone())
+            .push(" = ANY (t.tags)");
+    }
+    if let Some(location) = &query.location {
+        qb.push(" AND b.location = ").push_bind(location.clone());
+    }
+    if let Some(borrower) = &query.borrower {
+        qb.push(" AND (l.borrower = ")
+            .push_bind(borrower.clone())
+            .push(" OR la.display_name = ")
+            .push_bind(borrower.clone())
+            .push(")");
+    }
+}
+
+// 搜索结果的相关度：全文检索得分，书名包含搜索词时加分，ISBN 完全一致时排在最前
+fn push_rank(qb: &mut QueryBuilder<'_, Postgres>, terms: &Option<SearchTerms>) {
+    let s = match terms {
+        Some(s) => s,
+        None => {
+            qb.push("0::real");
+            return;
+        }
+    };
+    qb.push("(coalesce((lower(t.title) LIKE ")
+        .push_bind(s.like.clone())
+        .push(")::int, 0) + coalesce((t.isbn = ANY (")
+        .push_bind(s.isbns.clone())
+        .push("))::int * 10, 0)");
+    if let Some(ts) = &s.tsquery {
+        qb.push(" + ts_rank(t.search_vector, to_tsquery('simple', ")
+            .push_bind(ts.clone())
+            .push("))");
+    }
+    qb.push(")::real");
+}
+
+// 列表查询额外返回的排序字段，用于生成下一页的游标
+#[derive(Debug, sqlx::FromRow)]
+struct ListedBook {
+    #[sqlx(flatten)]
+    book: BookModel,
+    rank: f32,
+    sort_title: String,
+    borrowed: i64,
+}
+
+// 分页游标，记录上一页最后一行的排序字段，sort 不一致时游标无效
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Cursor {
+    sort: String,
+    rank: f32,
+    title: String,
+    borrowed: i64,
+    id: i64,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    fn decode(s: &str, sort: BookSort) -> Result<Self> {
+        let cursor = URL_SAFE_NO_PAD
+            .decode(s)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Cursor>(&json).ok())
+            .filter(|c| c.sort == sort.code())
+            .ok_or(anyhow!("分页游标无效，请回到第一页"))?;
+        Ok(cursor)
+    }
+}
+
+// 一页列表，total 为符合条件的总数，最后一页时 next_cursor 为空
+#[derive(Debug)]
+pub struct BookPage {
+    pub books: Vec<BookModel>,
+    pub total: i64,
+    pub next_cursor: Option<String>,
+}
+
+impl BookMS {
+    // 获取图书列表，每本实体书一行。
+    // 有游标时从游标之后开始取，否则按 offset 跳过，用于直接跳到某一页
+    pub async fn list(
+        &self,
+        query: &BookQuery,
+        cursor: Option<&str>,
+        limit: &i64,
+        offset: &i64,
+    ) -> Result<BookPage> {
+        self.query_books(query, true, cursor, limit, offset).await
+    }
+
+    // 获取书目列表，每个书目一行，优先选一本可借阅的副本作为代表
+    pub async fn list_titles(
+        &self,
+        query: &BookQuery,
+        cursor: Option<&str>,
+        limit: &i64,
+        offset: &i64,
+    ) -> Result<BookPage> {
+        self.query_books(query, false, cursor, limit, offset).await
+    }
+
+    async fn query_books(
+        &self,
+        query: &BookQuery,
+        copies: bool,
+        cursor: Option<&str>,
+        limit: &i64,
+        offset: &i64,
+    ) -> Result<BookPage> {
+        let sort = query.sort();
+        let cursor = cursor
+            .filter(|c| !c.is_empty())
+            .map(|c| Cursor::decode(c, sort))
+            .transpose()?;
+        let terms = SearchTerms::new(&query.q);
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT * FROM (SELECT ");
+        if !copies {
+            qb.push("DISTINCT ON (b.title_id) ");
+        }
+        qb.push("d.*, ");
+        push_rank(&mut qb, &terms);
+        qb.push(" as rank, coalesce(t.pinyin, lower(t.title)) as sort_title, ");
+        if copies {
+            qb.push("(SELECT count(*) FROM loans lo WHERE lo.book_id = b.id)");
+        } else {
+            qb.push(
+                "(SELECT count(*) FROM loans lo JOIN books lb on lb.id = lo.book_id WHERE lb.title_id = b.title_id)",
+            );
+        }
+        // 筛选条件用到的表在前，列表需要的列从 book_details 中取
+        qb.push(" as borrowed FROM ")
+            .push(FILTER_TABLES)
+            .push(" JOIN book_details d on d.id = b.id");
+        push_filters(&mut qb, query, &terms, copies);
+        if !copies {
+            qb.push(" ORDER BY b.title_id, b.state = 'available' desc, b.id");
+        }
+        qb.push(") bk");
+        // 副本按 id 排序，书目按 title_id 排序，id 越大入库越晚，同时作为排序相同时的依据
+        let key = if copies { "bk.id" } else { "bk.title_id" };
+        if let Some(c) = &cursor {
+            qb.push(" WHERE ");
+            match sort {
+                BookSort::Relevance => {
+                    qb.push(format!("(bk.rank, {}) < (", key))
+                        .push_bind(c.rank)
+                        .push(", ")
+                        .push_bind(c.id)
+                        .push(")");
+                }
+                BookSort::Newest => {
+                    qb.push(format!("{} < ", key)).push_bind(c.id);
+                }
+                BookSort::Title => {
+                    qb.push("(bk.sort_title > ")
+                        .push_bind(c.title.clone())
+                        .push(" OR (bk.sort_title = ")
+                        .push_bind(c.title.clone())
+                        .push(format!(" AND {} < ", key))
+                        .push_bind(c.id)
+                        .push("))");
+                }
+                BookSort::MostBorrowed => {
+                    qb.push(format!("(bk.borrowed, {}) < (", key))
+                        .push_bind(c.borrowed)
+                        .push(", ")
+                        .push_bind(c.id)
+                        .push(")");
+                }
+            }
+        }
+        qb.push(" ORDER BY ");
+        match sort {
+            BookSort::Relevance => qb.push("bk.rank desc, "),
+            BookSort::Newest => &mut qb,
+            BookSort::Title => qb.push("bk.sort_title, "),
+            BookSort::MostBorrowed => qb.push("bk.borrowed desc, "),
+        };
+        qb.push(format!("{} desc", key));
+        qb.push(" LIMIT ").push_bind(*limit);
+        if cursor.is_none() {
+            qb.push(" OFFSET ").push_bind(*offset);
+        }
+        let rows = qb
+            .build_query_as::<ListedBook>()
+            .fetch_all(self.pool())
+            .await?;
+        let total = self.count(query, &terms, copies).await?;
+        let next_cursor = match rows.last() {
+            Some(last) if rows.len() as i64 == *limit => Some(
+                Cursor {
+                    sort: sort.code().to_string(),
+                    rank: last.rank,
+                    title: last.sort_title.clone(),
+                    borrowed: last.borrowed,
+                    id: if copies {
+                        last.book.id
+                    } else {
+                        last.book.title_id
+                    },
+                }
+                .encode(),
+            ),
+            _ => None,
+        };
+        Ok(BookPage {
+            books: rows.into_iter().map(|r| r.book).collect(),
+            total,
+            next_cursor,
+        })
+    }
+
+    // 符合条件的副本数，copies 为 false 时为书目数
+    async fn count(
+        &self,
+        query: &BookQuery,
+        terms: &Option<SearchTerms>,
+        copies: bool,
+    ) -> Result<i64> {
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT ");
+        qb.push(if copies {
+            "count(*)"
+        } else {
+            "count(DISTINCT b.title_id)"
+        });
+        qb.push(" FROM ").push(FILTER_TABLES);
+        push_filters(&mut qb, query, terms, copies);
+        let (total,): (i64,) = qb.build_query_as().fetch_one(self.pool()).await?;
+        Ok(total)
+    }
+
+    // 统计当前查询条件下各字段最常见的取值，copies 为 false 时按书目计数。
+    // 每个字段去掉自身的筛选条件后统计，选中一个取值后仍能看到同一字段的其它取值；各字段合并成一次查询
+    pub async fn facets(&self, query: &BookQuery, copies: bool) -> Result<Facets> {
+        let terms = SearchTerms::new(&query.q);
+        let year = format!("(SELECT {})", PUBLISH_YEAR);
+        let sources = [
+            (FacetField::State, "(SELECT b.state)"),
+            (FacetField::Author, "unnest(t.authors)"),
+            (FacetField::Publisher, "(SELECT t.publisher)"),
+            (FacetField::Year, year.as_str()),
+            (FacetField::Tag, "unnest(t.tags)"),
+            (FacetField::Location, "(SELECT b.location)"),
+        ];
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT * FROM (");
+        for (i, (field, source)) in sources.iter().enumerate() {
+            if i > 0 {
+                qb.push(" UNION ALL ");
+            }
+            qb.push(format!("(SELECT {} as facet, f.value, ", i));
+            qb.push(if copies {
+                "count(*)"
+            } else {
+                "count(DISTINCT b.title_id)"
+            });
+            qb.push(" as count FROM ")
+                .push(FILTER_TABLES)
+                .push(" CROSS JOIN LATERAL ")
+                .push(source)
+                .push(" f(value)");
+            push_filters(&mut qb, &query.without(*field), &terms, copies);
+            qb.push(" AND f.value is not null AND f.value <> ''");
+            qb.push(" GROUP BY f.value ORDER BY count desc, f.value LIMIT 10)");
+        }
+        qb.push(") fc ORDER BY fc.facet, fc.count desc, fc.value");
+        let rows: Vec<(i32, String, i64)> = qb.build_query_as().fetch_all(self.pool()).await?;
+        let mut counts = vec![vec![]; sources.len()];
+        for (facet, value, count) in rows {
+            if let Some(c) = counts.get_mut(facet as usize) {
+                c.push(FacetCount { value, count });
+            }
+        }
+        let mut counts = counts.into_iter();
+        let mut next = || counts.next().unwrap_or_default();
+        Ok(Facets {
+            states: next(),
+            authors: next(),
+            publishers: next(),
+            years: next(),
+            tags: next(),
+            locations: next(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::entity::BookState;
+
+    #[test]
+    fn build_filters() {
+        let query = BookQuery {
+            q: Some("rust".to_string()),
+            state: Some(BookState::Available),
+            author: Some("张汉东".to_string()),
+            year_from: Some(2010),
+            ..Default::default()
+        };
+        let terms = SearchTerms::new(&query.q);
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT b.id FROM books b");
+        push_filters(&mut qb, &query, &terms, true);
+        let sql = qb.sql();
+        assert!(sql.contains("t.search_vector @@ to_tsquery('simple', $4)"));
+        assert!(sql.contains("t.pinyin ~ $5"));
+        assert!(sql.contains("lower(b.barcode) LIKE $7"));
+        assert!(sql.contains("b.state = $8"));
+        assert!(sql.contains("$9 = ANY (t.authors)"));
+        assert!(sql.contains("::int >= $10"));
+        assert!(!sql.contains("t.tags"));
+    }
+
+    #[test]
+    fn cursor_roundtrip() {
+        let cursor = Cursor {
+            sort: BookSort::Relevance.code().to_string(),
+            rank: 0.0607927,
+            title: "yunchouxue".to_string(),
+            borrowed: 3,
+            id: 42,
+        };
+        let s = cursor.encode();
+        assert_eq!(Cursor::decode(&s, BookSort::Relevance).unwrap(), cursor);
+        assert!(Cursor::decode(&s, BookSort::Title).is_err());
+        assert!(Cursor::decode("not a cursor", BookSort::Relevance).is_err());
+    }
+}
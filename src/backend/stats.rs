@@ -0,0 +1,161 @@
+use crate::backend::books::{BookMS, BookStateModel};
+use anyhow::Result;
+use time::OffsetDateTime;
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct StateCountModel {
+    pub state: BookStateModel,
+    pub count: i64,
+}
+
+// 借阅次数最多的书目
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct TopTitleModel {
+    pub title_id: i64,
+    pub title: String,
+    // 书目下任意一本书，用于跳转到书籍详情
+    pub book_id: i64,
+    pub borrows: i64,
+}
+
+// 借阅次数最多的用户
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct TopBorrowerModel {
+    pub uid: String,
+    pub name: String,
+    pub borrows: i64,
+}
+
+// 按月汇总的数量，month 格式为 2023-01，没有记录的月份数量为 0
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct MonthCountModel {
+    pub month: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LibraryStatsModel {
+    // 未删除的书籍按状态计数
+    pub states: Vec<StateCountModel>,
+    // 已归还、等待管理员确认的书籍
+    pub awaiting_confirmation: i64,
+    pub overdue: i64,
+    pub top_titles: Vec<TopTitleModel>,
+    pub top_borrowers: Vec<TopBorrowerModel>,
+    // 最近几个月每月入库的书籍
+    pub acquisitions: Vec<MonthCountModel>,
+    // 最近几个月每月借出的次数
+    pub borrows: Vec<MonthCountModel>,
+}
+
+impl BookMS {
+    // 管理员统计页面的数据，top 为排行榜的条数，months 为按月统计的月数（含本月）
+    pub async fn stats(&self, top: &i64, months: &i32) -> Result<LibraryStatsModel> {
+        let states = sqlx::query_as!(
+            StateCountModel,
+            r#"SELECT coalesce(state, 'unknown') AS "state!: BookStateModel", count(*) AS "count!"
+FROM books
+WHERE deleted_at is null
+GROUP BY coalesce(state, 'unknown')
+ORDER BY count(*) DESC"#
+        )
+        .fetch_all(self.pool())
+        .await?;
+        let awaiting_confirmation = states
+            .iter()
+            .find(|s| s.state == BookStateModel::Returned)
+            .map(|s| s.count)
+            .unwrap_or(0);
+
+        let overdue = sqlx::query_scalar!(
+            r#"SELECT count(*) AS "count!"
+FROM loans l
+         JOIN books b on b.id = l.book_id
+WHERE l.returned_at is null
+  AND b.deleted_at is null
+  AND l.due_at < $1"#,
+            OffsetDateTime::now_utc()
+        )
+        .fetch_one(self.pool())
+        .await?;
+
+        let top_titles = sqlx::query_as!(
+            TopTitleModel,
+            r#"SELECT t.id      AS title_id,
+       t.title,
+       min(b.id) AS "book_id!",
+       count(*)  AS "borrows!"
+FROM change_logs cl
+         JOIN books b on b.id = cl.source_id
+         JOIN titles t on t.id = b.title_id
+WHERE cl.source_type = 'book'
+  AND cl.action = 'borrowed'
+GROUP BY t.id, t.title
+ORDER BY count(*) DESC, t.id
+LIMIT $1"#,
+            top
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        let top_borrowers = sqlx::query_as!(
+            TopBorrowerModel,
+            r#"SELECT cl.operator                           AS uid,
+       coalesce(a.display_name, cl.operator) AS "name!",
+       count(*)                              AS "borrows!"
+FROM change_logs cl
+         LEFT JOIN accounts a on a.id = cl.operator
+WHERE cl.source_type = 'book'
+  AND cl.action = 'borrowed'
+GROUP BY cl.operator, a.display_name
+ORDER BY count(*) DESC, cl.operator
+LIMIT $1"#,
+            top
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        // 入库时间以书籍创建时间为准，已删除的书也算在入库当月
+        let acquisitions = sqlx::query_as!(
+            MonthCountModel,
+            r#"SELECT to_char(m.month, 'YYYY-MM') AS "month!",
+       count(b.id)                   AS "count!"
+FROM generate_series(date_trunc('month', now()) - make_interval(months => $1 - 1),
+                     date_trunc('month', now()), interval '1 month') m(month)
+         LEFT JOIN books b on b.created_at >= m.month
+    AND b.created_at < m.month + interval '1 month'
+GROUP BY m.month
+ORDER BY m.month"#,
+            months
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        let borrows = sqlx::query_as!(
+            MonthCountModel,
+            r#"SELECT to_char(m.month, 'YYYY-MM') AS "month!",
+       count(cl.id)                  AS "count!"
+FROM generate_series(date_trunc('month', now()) - make_interval(months => $1 - 1),
+                     date_trunc('month', now()), interval '1 month') m(month)
+         LEFT JOIN change_logs cl on cl.source_type = 'book'
+    AND cl.action = 'borrowed'
+    AND cl.operate_at >= m.month
+    AND cl.operate_at < m.month + interval '1 month'
+GROUP BY m.month
+ORDER BY m.month"#,
+            months
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(LibraryStatsModel {
+            states,
+            awaiting_confirmation,
+            overdue,
+            top_titles,
+            top_borrowers,
+            acquisitions,
+            borrows,
+        })
+    }
+}
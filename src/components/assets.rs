@@ -1,5 +1,7 @@
 use crate::components::book::*;
+use crate::components::catalogue::*;
 use leptos::*;
+use leptos_router::*;
 
 #[allow(non_snake_case)]
 #[component]
@@ -7,9 +9,23 @@ pub fn AssetsPage(cx: Scope) -> impl IntoView {
     view! {
         cx,
         <div class="mx-auto max-w-screen-xl px-4 my-4 gap-8">
+            <div class="my-4 flex justify-end gap-4 text-sm">
+                <A href="/assets-mgr/returns" class="text-blue-600">"确认归还"</A>
+                <A href="/assets-mgr/stats" class="text-blue-600">"馆藏统计"</A>
+                <A href="/assets-mgr/import" class="text-blue-600">"批量入库"</A>
+                <span class="text-gray-500">"导出目录："</span>
+                // 导出由 axum 路由直接返回文件，不走前端路由
+                <a href="/admin/export/csv" rel="external" download class="text-blue-600">"CSV"</a>
+                <a href="/admin/export/jsonl" rel="external" download class="text-blue-600">"JSON Lines"</a>
+                <a href="/admin/export/marc" rel="external" download class="text-blue-600">"MARC21"</a>
+                <a href="/admin/export/marcxml" rel="external" download class="text-blue-600">"MARCXML"</a>
+            </div>
             <div class="my-4" >
                 <BookStorage/>
             </div>
+            <div class="my-4" >
+                <ManualCatalogue/>
+            </div>
             <div class="my-4">
                 <BookList />
             </div>
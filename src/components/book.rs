@@ -1,5 +1,11 @@
 use crate::api::books::{BookAction, BookUI};
 use crate::api::entity::BookState;
+use crate::api::search::BookQuery;
+use crate::components::book_gallery::{Highlighted, HighlightedProps};
+use crate::components::catalogue::*;
+use crate::components::facets::*;
+use crate::components::history::*;
+use crate::components::hold::*;
 use crate::components::pagination::*;
 use leptos::*;
 use leptos_router::*;
@@ -17,6 +23,13 @@ pub fn BookDetailPage(cx: Scope) -> impl IntoView {
 
     let borrow_act = create_server_action::<crate::api::books::BorrowBook>(cx);
     let revert_to_act = create_server_action::<crate::api::books::ReturnBook>(cx);
+    let renew_act = create_server_action::<crate::api::books::RenewLoan>(cx);
+    let lost_act = create_server_action::<crate::api::books::MarkBookLost>(cx);
+    let reset_act = create_server_action::<crate::api::books::ResetBook>(cx);
+    let delete_act = create_server_action::<crate::api::books::DeleteBook>(cx);
+    let refresh_act = create_server_action::<crate::api::books::RefreshMetadata>(cx);
+    let update_act = create_server_action::<crate::api::books::UpdateBook>(cx);
+    let revert_edit_act = create_server_action::<crate::api::books::RevertEdit>(cx);
 
     let b = create_resource(
         cx,
@@ -25,25 +38,134 @@ pub fn BookDetailPage(cx: Scope) -> impl IntoView {
                 book_id_fn(),
                 borrow_act.version().get(),
                 revert_to_act.version().get(),
+                renew_act.version().get(),
+                lost_act.version().get(),
+                reset_act.version().get(),
+                refresh_act.version().get(),
+                update_act.version().get(),
+                revert_edit_act.version().get(),
             )
         },
-        move |(id, _, _)| crate::api::books::book_detail(cx, id),
+        move |(id, _, _, _, _, _, _, _, _)| crate::api::books::book_detail(cx, id),
     );
 
+    // 书籍删除后详情页已经没有内容了，回到首页
+    let navigate = use_navigate(cx);
+    create_effect(cx, move |_| {
+        if let Some(Ok(_)) = delete_act.value().get() {
+            let _ = navigate("/", Default::default());
+        }
+    });
+
     let g = move || match b.read(cx) {
         None => None,
         Some(Err(_)) => None,
-        Some(Ok(book)) => Some(view! {
-            cx,
-            <BookDetail book=book borrow=borrow_act revert=revert_to_act/>
-        }),
+        Some(Ok(book)) => {
+            let book_id = book.id;
+            let editor = book
+                .manageable
+                .then(|| view! { cx, <TitleEditor book=book.clone() update=update_act/> });
+            Some(view! {
+                cx,
+                <BookDetail book=book borrow=borrow_act revert=revert_to_act renew=renew_act
+                    lost=lost_act reset=reset_act delete=delete_act refresh=refresh_act/>
+                {editor}
+                <HoldQueue book_id=book_id/>
+                <Timeline book_id=book_id revert=revert_edit_act/>
+            })
+        }
+    };
+
+    let act_err = move || {
+        [
+            borrow_act.value().get(),
+            revert_to_act.value().get(),
+            renew_act.value().get(),
+            lost_act.value().get(),
+            reset_act.value().get(),
+            delete_act.value().get(),
+            refresh_act.value().get(),
+            update_act.value().get(),
+            revert_edit_act.value().get(),
+        ]
+        .into_iter()
+        .find_map(|r| r.and_then(|r| r.err()))
+        .map(|e| {
+            view! { cx,
+                <div class="mx-auto max-w-screen-xl px-4">
+                    <p class="rounded bg-red-50 px-4 py-2 text-sm text-red-600">{action_error(e)}</p>
+                </div>
+            }
+        })
     };
 
+    let copies = create_resource(
+        cx,
+        move || b.read(cx).and_then(|b| b.ok()).map(|b| b.title_id),
+        move |title_id| async move {
+            match title_id {
+                Some(title_id) => crate::api::books::book_copies(cx, title_id).await,
+                None => Ok(vec![]),
+            }
+        },
+    );
+
     view! {
         cx,
+        {act_err}
         <Suspense fallback=move || view! { cx, <p>"Loading..."</p> }>
             {g}
         </Suspense>
+        <Suspense fallback=move || view! { cx, <p>"Loading..."</p> }>
+            {move || copies.read(cx).and_then(|c| c.ok()).map(|c| view! { cx, <CopyList copies=c/> })}
+        </Suspense>
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+pub fn CopyList(cx: Scope, copies: Vec<BookUI>) -> impl IntoView {
+    view! {
+        cx,
+        <div class="mx-auto max-w-screen-xl px-4 pb-8">
+            <h2 class="mb-4 text-lg font-bold">"馆藏副本"</h2>
+            <table class="min-w-full divide-y-2 divide-gray-200 text-sm">
+                <thead>
+                    <tr>
+                        <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"条码"</th>
+                        <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"状态"</th>
+                        <th class="px-4 py-2"></th>
+                    </tr>
+                </thead>
+                <tbody class="divide-y divide-gray-200">
+                    <For each=move || copies.clone() key=|b| b.id
+                    view=move |cx, b: BookUI| {
+                        view! { cx,
+                            <tr>
+                                <td class="whitespace-nowrap px-4 py-2 text-gray-700">
+                                    {if b.barcode.is_empty() { b.id.to_string() } else { b.barcode.clone() }}
+                                </td>
+                                <td class="whitespace-nowrap px-4 py-2 text-gray-700">
+                                    {b.state.to_string()}
+                                    <div><DueDate due_at=b.due_at overdue=b.overdue/></div>
+                                </td>
+                                <td class="whitespace-nowrap px-4 py-2">
+                                    <A href=format!("/book/{}", b.id) class="text-blue-600">"查看"</A>
+                                </td>
+                            </tr>
+                        }
+                    }/>
+                </tbody>
+            </table>
+        </div>
+    }
+}
+
+// 服务端返回的失败原因，去掉 ServerFnError 自带的前缀
+pub fn action_error(e: ServerFnError) -> String {
+    match e {
+        ServerFnError::Request(s) | ServerFnError::ServerError(s) => s,
+        e => e.to_string(),
     }
 }
 
@@ -54,11 +176,20 @@ pub fn BookDetail(
     #[prop()] book: BookUI,
     borrow: Action<crate::api::books::BorrowBook, Result<(), ServerFnError>>,
     revert: Action<crate::api::books::ReturnBook, Result<(), ServerFnError>>,
+    renew: Action<crate::api::books::RenewLoan, Result<(), ServerFnError>>,
+    lost: Action<crate::api::books::MarkBookLost, Result<(), ServerFnError>>,
+    reset: Action<crate::api::books::ResetBook, Result<(), ServerFnError>>,
+    delete: Action<crate::api::books::DeleteBook, Result<(), ServerFnError>>,
+    refresh: Action<crate::api::books::RefreshMetadata, Result<(), ServerFnError>>,
 ) -> impl IntoView {
     let act_btn :Vec<_>= book.actions.iter().filter(|a| {
         match a {
             BookAction::Borrow => true,
             BookAction::Return => true,
+            BookAction::Renew => true,
+            BookAction::Lost => true,
+            BookAction::Reset => true,
+            BookAction::Delete => true,
             _ => false
         }
     }).map(move |a| {
@@ -81,6 +212,42 @@ pub fn BookDetail(
                     </button>
                 </ActionForm>
             }.into_view(cx),
+            BookAction::Renew => view! {
+                    cx,
+                <ActionForm action=renew>
+                    <input type="hidden" name="id" value=book.id/>
+                    <button type="submit" class="block rounded border border-green-600 px-5 py-2 text-sm font-medium text-green-600 hover:bg-green-50">
+                    "续借"
+                    </button>
+                </ActionForm>
+            }.into_view(cx),
+            BookAction::Lost => view! {
+                    cx,
+                <ActionForm action=lost>
+                    <input type="hidden" name="id" value=book.id/>
+                    <button type="submit" class="block rounded bg-black px-5 py-2 text-sm font-medium text-white">
+                    "丢失"
+                    </button>
+                </ActionForm>
+            }.into_view(cx),
+            BookAction::Reset => view! {
+                    cx,
+                <ActionForm action=reset>
+                    <input type="hidden" name="id" value=book.id/>
+                    <button type="submit" class="block rounded bg-yellow-600 px-5 py-2 text-sm font-medium text-white hover:bg-yellow-700">
+                    "重置"
+                    </button>
+                </ActionForm>
+            }.into_view(cx),
+            BookAction::Delete => view! {
+                    cx,
+                <ActionForm action=delete>
+                    <input type="hidden" name="id" value=book.id/>
+                    <button type="submit" class="block rounded bg-red-600 px-5 py-2 text-sm font-medium text-white hover:bg-red-700">
+                    "删除"
+                    </button>
+                </ActionForm>
+            }.into_view(cx),
             _ => view! { cx, <span></span> }.into_view(cx)
         }
     }).collect();
@@ -100,16 +267,26 @@ pub fn BookDetail(
             "归还中"
             </strong>}
         }
+        BookState::Reserved => {
+            view! {cx, <strong class="rounded-full border border-blue-600 bg-gray-100 px-3 py-0.5 text-xs font-medium tracking-wide text-blue-600">
+            "预约保留中"
+            </strong>}
+        }
         BookState::Lost => {
             view! {cx, <strong class="rounded-full border border-red-600 bg-gray-100 px-3 py-0.5 text-xs font-medium tracking-wide text-red-600">
             "已丢失"
             </strong>}
         }
-        BookState::Deleted => {
+        BookState::Damaged => {
             view! {cx, <strong class="rounded-full border border-orange-600 bg-gray-100 px-3 py-0.5 text-xs font-medium tracking-wide text-orange-600">
             "已损坏"
             </strong>}
         }
+        BookState::Deleted => {
+            view! {cx, <strong class="rounded-full border border-gray-400 bg-gray-100 px-3 py-0.5 text-xs font-medium tracking-wide text-gray-500">
+            "已删除"
+            </strong>}
+        }
         BookState::Unknown => {
             view! {cx, <strong class="rounded-full border border-purple-600 bg-gray-100 px-3 py-0.5 text-xs font-medium tracking-wide text-purple-600">
             "状态异常"
@@ -131,14 +308,73 @@ pub fn BookDetail(
                     <div class="mt-8 flex justify-between">
                         <div class="max-w-[50ch] space-y-2">
                             <h1 class="text-xl font-bold sm:text-2xl">{book.title}</h1>
+                            {(!book.subtitle.is_empty()).then(|| view! { cx,
+                                <p class="text-gray-600">{book.subtitle.clone()}</p>
+                            })}
                             <p class="text-sm">{book.authors.join(", ")}</p>
+                            {(!book.translators.is_empty()).then(|| view! { cx,
+                                <p class="text-sm">{format!("{} 译", book.translators.join(", "))}</p>
+                            })}
                             <p class="text-sm">{book.isbn}</p>
                             <p class="text-sm">{book.publisher}</p>
-                            {act_btn}
+                            {(!book.publish_date.is_empty()).then(|| view! { cx,
+                                <p class="text-sm">{format!("{} 出版", book.publish_date)}</p>
+                            })}
+                            {(!book.pages.is_empty()).then(|| view! { cx,
+                                <p class="text-sm">{format!("{} 页", book.pages)}</p>
+                            })}
+                            {(!book.price.is_empty()).then(|| view! { cx,
+                                <p class="text-sm">{format!("定价 {}", book.price)}</p>
+                            })}
+                            {book.douban_score.map(|s| view! { cx,
+                                <p class="text-sm">{format!("豆瓣评分 {:.1}", s)}</p>
+                            })}
+                            {(!book.tags.is_empty()).then(|| view! { cx,
+                                <p class="flex flex-wrap gap-1">
+                                {book.tags.iter().map(|t| view! { cx,
+                                    <span class="rounded bg-gray-100 px-2 py-0.5 text-xs text-gray-600">{t.clone()}</span>
+                                }).collect::<Vec<_>>()}
+                                </p>
+                            })}
+                            <p class="text-sm">{format!("馆藏 {} 本，{} 本可借", book.copies, book.available_copies)}</p>
+                            <p class="text-sm text-gray-500">{format!("条码 {}", if book.barcode.is_empty() { book.id.to_string() } else { book.barcode.clone() })}</p>
+                            {(!book.borrower.is_empty()).then(|| view! { cx,
+                                <p class="text-sm">{format!("借阅人 {}", book.borrower_name)}</p>
+                            })}
+                            <p><DueDate due_at=book.due_at overdue=book.overdue/></p>
+                            {(!book.reserved_for.is_empty()).then(|| view! { cx,
+                                <p class="text-sm">{format!("为 {} 保留至 {}", book.reserved_for_name, book.reserved_until.map(format_date).unwrap_or("".to_string()))}</p>
+                            })}
+                            {(book.renewals > 0).then(|| view! { cx,
+                                <p class="text-xs text-gray-500">{format!("已续借 {} 次", book.renewals)}</p>
+                            })}
+                            <div class="flex gap-2">
+                                {act_btn}
+                                {book.manageable.then(|| view! { cx,
+                                    <ActionForm action=refresh>
+                                        <input type="hidden" name="title_id" value=book.title_id/>
+                                        <button type="submit" class="block rounded border border-gray-300 px-5 py-2 text-sm font-medium text-gray-600 hover:bg-gray-50">
+                                        "刷新书目信息"
+                                        </button>
+                                    </ActionForm>
+                                })}
+                            </div>
                         </div>
                     </div>
                 </div>
             </div>
+            {(!book.description.is_empty()).then(|| view! { cx,
+                <div class="mt-8 max-w-prose space-y-2">
+                    <h2 class="text-lg font-bold">"内容简介"</h2>
+                    <p class="whitespace-pre-line text-sm leading-relaxed text-gray-700">{book.description.clone()}</p>
+                </div>
+            })}
+            {(!book.author_intro.is_empty()).then(|| view! { cx,
+                <div class="mt-8 max-w-prose space-y-2">
+                    <h2 class="text-lg font-bold">"作者简介"</h2>
+                    <p class="whitespace-pre-line text-sm leading-relaxed text-gray-700">{book.author_intro.clone()}</p>
+                </div>
+            })}
         </div>
     </section>
     }
@@ -148,19 +384,48 @@ pub fn BookDetail(
 #[component]
 pub fn BookStorage(cx: Scope) -> impl IntoView {
     let fast_storage_book_act = create_server_action::<crate::api::books::FastStorageBook>(cx);
+    let (isbn, set_isbn) = create_signal(cx, "".to_string());
+    // 输入时就校验 ISBN，不用等提交后再看服务端报错
+    let checked = move || crate::api::isbn::normalize(&isbn.get());
+    let hint = move || {
+        if isbn.get().trim().is_empty() {
+            return None;
+        }
+        Some(match checked() {
+            Ok(isbn13) => view! { cx, <p class="mt-1 text-xs text-gray-500">{format!("ISBN-13：{}", isbn13)}</p> },
+            Err(e) => view! { cx, <p class="mt-1 text-xs text-red-600">{e.to_string()}</p> },
+        })
+    };
+    let result = move || {
+        fast_storage_book_act.value().get().map(|r| match r {
+            Ok(_) => view! { cx, <p class="text-sm text-green-600">"入库成功"</p> },
+            Err(e) => view! { cx, <p class="text-sm text-red-600">{action_error(e)}</p> },
+        })
+    };
     view! {
         cx,
-        <ActionForm class="grid grid-cols-2 gap-4  row g-3" action=fast_storage_book_act>
+        <div class="space-y-2">
+        <ActionForm class="grid grid-cols-3 gap-4  row g-3" action=fast_storage_book_act>
             <div>
                 <label for="isbn" class="sr-only">"ISBN"</label>
                     <input type="text" name="isbn"
                         class="w-full rounded-lg border-gray-200 p-3 text-sm"
-                        placeholder="输入书籍ISBN" autocomplete="off"/>
+                        placeholder="输入书籍ISBN" autocomplete="off"
+                        on:input=move |ev| set_isbn(event_target_value(&ev))/>
+                    {hint}
+            </div>
+            <div>
+                <label for="barcode" class="sr-only">"条码"</label>
+                    <input type="text" name="barcode"
+                        class="w-full rounded-lg border-gray-200 p-3 text-sm"
+                        placeholder="输入馆藏条码（可选）" autocomplete="off"/>
             </div>
 
-            <button type="submit" class="inline-block shrink-0 rounded-md border border-blue-600 bg-blue-600 px-12 py-3 text-sm font-medium text-white transition hover:bg-transparent hover:text-blue-600 focus:outline-none focus:ring active:text-blue-500">"入库"</button>
+            <button type="submit" disabled=move || checked().is_err() class="inline-block shrink-0 rounded-md border border-blue-600 bg-blue-600 px-12 py-3 text-sm font-medium text-white transition hover:bg-transparent hover:text-blue-600 focus:outline-none focus:ring active:text-blue-500 disabled:opacity-50">"入库"</button>
 
         </ActionForm>
+        {result}
+        </div>
     }
 }
 
@@ -214,25 +479,99 @@ pub fn BookStorage(cx: Scope) -> impl IntoView {
 //       }
 // }
 
+const PAGE_SIZE: i64 = 10;
+
+// 输入框的值，为空时不作为筛选条件
+fn input_filter(ev: &web_sys::Event) -> Option<String> {
+    Some(event_target_value(ev).trim().to_string()).filter(|v| !v.is_empty())
+}
+
 #[allow(non_snake_case)]
 #[component]
 pub fn BookList(cx: Scope) -> impl IntoView {
     let confirm_act = create_server_action::<crate::api::books::ConfirmReturnBook>(cx);
+    let lost_act = create_server_action::<crate::api::books::MarkBookLost>(cx);
+    let reset_act = create_server_action::<crate::api::books::ResetBook>(cx);
+    let delete_act = create_server_action::<crate::api::books::DeleteBook>(cx);
 
-    let (pn, set_pn) = create_signal(cx, 1);
+    let page = use_page(cx);
+    let cursor = use_cursor(cx);
+    let (filters, set_filters) = create_signal(cx, BookQuery::default());
+    // 筛选条件变化后回到第一页，第一次运行时保留地址栏中的页码
+    create_effect(cx, move |prev: Option<()>| {
+        filters.with(|_| ());
+        if prev.is_some() {
+            reset_page(cx);
+        }
+    });
     let posts = create_resource(
         cx,
-        move || (pn.get(), confirm_act.version().get()),
-        move |(pn, _)| {
-            let offset = (pn - 1) * 10;
-            crate::api::books::book_list(cx, Some(offset), None, None)
+        move || {
+            (
+                page.get(),
+                cursor.get(),
+                filters.get(),
+                confirm_act.version().get(),
+                lost_act.version().get(),
+                reset_act.version().get(),
+                delete_act.version().get(),
+            )
+        },
+        move |(page, cursor, f, _, _, _, _)| {
+            crate::api::books::book_list(
+                cx,
+                cursor,
+                Some((page - 1) * PAGE_SIZE),
+                Some(PAGE_SIZE),
+                f.q,
+                f.state,
+                f.author,
+                f.publisher,
+                f.year_from,
+                f.year_to,
+                f.tag,
+                f.location,
+                f.borrower,
+                f.sort,
+            )
         },
     );
+    let total = Signal::derive(cx, move || {
+        posts.read(cx).and_then(|r| r.ok()).map(|list| list.total)
+    });
+    let next = Signal::derive(cx, move || {
+        posts
+            .read(cx)
+            .and_then(|r| r.ok())
+            .and_then(|list| list.next_cursor)
+    });
+    let facets = move || {
+        posts.read(cx).and_then(|r| r.ok()).map(|list| {
+            view! { cx,
+                <details class="px-4">
+                    <summary class="cursor-pointer text-sm text-gray-700">"按分类筛选"</summary>
+                    <div class="mt-2">
+                        <FacetPanel facets=list.facets filters=filters set_filters=set_filters/>
+                    </div>
+                </details>
+            }
+        })
+    };
     view! {
         cx,
         <div>
+            <div class="my-4 px-4 flex flex-wrap items-center gap-4">
+                <input type="search" placeholder="书名、ISBN 或条码" class="h-10 rounded-lg border-gray-200 text-sm"
+                    on:change=move |ev| set_filters.update(|f| f.q = input_filter(&ev))/>
+                <input type="search" placeholder="借阅人" class="h-10 rounded-lg border-gray-200 text-sm"
+                    prop:value=move || filters.get().borrower.unwrap_or_default()
+                    on:change=move |ev| set_filters.update(|f| f.borrower = input_filter(&ev))/>
+                <SortSelect filters=filters set_filters=set_filters/>
+                <FilterChips filters=filters set_filters=set_filters/>
+            </div>
+            {facets}
             <div class="my-4 px-4">
-                <Pagination pn=pn set_pn=set_pn/>
+                <Pagination total=total per_page=PAGE_SIZE next=next/>
             </div>
             <div class="overflow-x-auto">
               <table class="min-w-full divide-y-2 divide-gray-200 text-sm">
@@ -268,22 +607,31 @@ pub fn BookList(cx: Scope) -> impl IntoView {
         {move || match posts.read(cx) {
             None => None,
             Some(Err(_)) => None,
-            Some(Ok(books)) => {
+            Some(Ok(list)) => {
+                let books = list.books;
                 Some(view! {
                     cx,
                     <For
                     each=move || books.clone()
                     key=|b| b.id
                     view=move |cx, b: BookUI| {
+                        let title = b.highlight.as_ref().map(|h| h.title.clone());
                         view! { cx,
                             <tr>
-                            <th class="whitespace-nowrap px-4 py-2 font-medium text-gray-900">{b.id}</th>
+                            <th class="whitespace-nowrap px-4 py-2 font-medium text-gray-900">
+                                {b.id}
+                                <div class="text-xs font-normal text-gray-500">{b.barcode.clone()}</div>
+                            </th>
                             <td class="whitespace-nowrap px-4 py-2 text-gray-700 " title=&b.title>
-                                <div class="truncate max-w-xs">{b.title}</div>
+                                <div class="truncate max-w-xs">{match title {
+                                    Some(fragments) => view! { cx, <Highlighted fragments=fragments/> }.into_view(cx),
+                                    None => b.title.clone().into_view(cx),
+                                }}</div>
                                 <div class="truncate max-w-xs pl-4">{b.authors.join(", ")}</div>
                             </td>
                             <td class="whitespace-nowrap px-4 py-2 text-gray-700">
                             {b.state.to_string()}
+                            <div><DueDate due_at=b.due_at overdue=b.overdue/></div>
                             {
                                 move || if b.state == BookState::Returned {
                                 Some(view! {
@@ -315,30 +663,33 @@ pub fn BookList(cx: Scope) -> impl IntoView {
                             }).map(|a| match a {
                                 BookAction::Lost => view! {
                                     cx,
-                                    <A
-                                    href=format!("/book/{}/lost", b.id)
+                                    <ActionForm action=lost_act class="inline-block">
+                                    <input type="hidden" value=b.id name="id" />
+                                    <button type="submit"
                                     class="mr-4 inline-block rounded bg-black px-4 py-2 text-xs font-medium text-white"
                                     >
                                     "丢失"
-                                    </A>
+                                    </button></ActionForm>
                                 }.into_view(cx),
                                 BookAction::Reset => view! {
                                     cx,
-                                    <A
-                                    href=format!("/book/{}/reset", b.id)
+                                    <ActionForm action=reset_act class="inline-block">
+                                    <input type="hidden" value=b.id name="id" />
+                                    <button type="submit"
                                     class="mr-4 inline-block rounded bg-yellow-600 px-4 py-2 text-xs font-medium text-white hover:bg-yellow-700"
                                     >
                                     "重置"
-                                    </A>
+                                    </button></ActionForm>
                                 }.into_view(cx),
                                 BookAction::Delete => view! {
                                     cx,
-                                    <A
-                                    href=format!("/book/{}/delete", b.id)
+                                    <ActionForm action=delete_act class="inline-block">
+                                    <input type="hidden" value=b.id name="id" />
+                                    <button type="submit"
                                     class="mr-4 inline-block rounded bg-red-600 px-4 py-2 text-xs font-medium text-white hover:bg-red-700"
                                     >
                                     "删除"
-                                    </A>
+                                    </button></ActionForm>
                                 }.into_view(cx),
                                 _ => view! { cx, <div/>}.into_view(cx)
                             }).collect::<Vec<_>>()
@@ -359,6 +710,26 @@ pub fn BookList(cx: Scope) -> impl IntoView {
     }
 }
 
+#[allow(non_snake_case)]
+#[component]
+pub fn DueDate(cx: Scope, due_at: Option<OffsetDateTime>, overdue: bool) -> impl IntoView {
+    due_at.map(|d| {
+        view! { cx,
+            <span class="text-xs text-gray-500">{format!("应还日期 {}", format_date(d))}</span>
+            {overdue.then(|| view! { cx,
+                <strong class="ml-2 rounded-full border border-red-600 bg-gray-100 px-3 py-0.5 text-xs font-medium tracking-wide text-red-600">
+                "已逾期"
+                </strong>
+            })}
+        }
+    })
+}
+
+pub fn format_date(date: OffsetDateTime) -> String {
+    date.format(time::macros::format_description!("[year]-[month]-[day]"))
+        .unwrap_or("".to_string())
+}
+
 // <Pagination
 // current=move || page
 // total=move || total
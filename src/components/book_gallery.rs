@@ -1,4 +1,5 @@
 use crate::api::books::BookUI;
+use crate::api::search::Fragment;
 use leptos::*;
 use leptos_router::*;
 
@@ -13,16 +14,47 @@ pub fn BookGallery(cx: Scope, books: Vec<BookUI>) -> impl IntoView {
         <div class="grid grid-cols-1 gap-x-6 gap-y-10 sm:grid-cols-2 lg:grid-cols-4 xl:grid-cols-6 xl:gap-x-8">
             <For each=move || books.clone() key=|b| b.id
             view=move |cx, b: BookUI| {
+                let (title, authors, snippet) = match b.highlight {
+                    Some(h) => (h.title, h.authors, h.snippet),
+                    None => (plain(&b.title), plain(&b.authors.join(", ")), vec![]),
+                };
                 view! { cx,
                 <A href=format!("/book/{}", b.id) class="group">
                     <div class="aspect-h-1 aspect-w-1 w-full overflow-hidden rounded-lg bg-gray-200 xl:aspect-h-8 xl:aspect-w-7">
                     <img loading="lazy" referrerpolicy="no-referrer" src={b.thumbnail} class="h-full w-full object-contain object-center group-hover:opacity-75" />
                     </div>
-                    <h3 class="mt-4 text-sm font-medium text-gray-900 overflow-hidden">{b.title}</h3>
-                    <p class="mt-1 text-sm text-gray-500">{b.authors.join(", ")}</p>
+                    <h3 class="mt-4 text-sm font-medium text-gray-900 overflow-hidden"><Highlighted fragments=title/></h3>
+                    <p class="mt-1 text-sm text-gray-500"><Highlighted fragments=authors/></p>
+                    {(!snippet.is_empty()).then(|| view! { cx,
+                        <p class="mt-1 text-xs text-gray-500 max-h-12 overflow-hidden"><Highlighted fragments=snippet/></p>
+                    })}
+                    <p class="mt-1 text-xs text-gray-400">{format!("{} / {} 本可借", b.available_copies, b.copies)}</p>
                 </A>}
             }/>
         </div>
         </div>
     }
 }
+
+fn plain(text: &str) -> Vec<Fragment> {
+    vec![Fragment {
+        text: text.to_string(),
+        matched: false,
+    }]
+}
+
+// 搜索结果中与搜索词匹配的部分用 <mark> 标出
+#[allow(non_snake_case)]
+#[component]
+pub fn Highlighted(cx: Scope, fragments: Vec<Fragment>) -> impl IntoView {
+    fragments
+        .into_iter()
+        .map(|f| {
+            if f.matched {
+                view! { cx, <mark class="bg-yellow-100">{f.text}</mark> }.into_view(cx)
+            } else {
+                f.text.into_view(cx)
+            }
+        })
+        .collect::<Vec<_>>()
+}
@@ -0,0 +1,208 @@
+use crate::api::books::{BookUI, LookupIsbn, MAX_COVER_BYTES};
+use crate::components::book::action_error;
+use leptos::*;
+use leptos_router::*;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+
+// 手工编目：检索服务查不到的图书由管理员填写书目信息。
+// 填了 ISBN 时可以先查一次检索服务，查到的部分信息用来预填表单
+#[allow(non_snake_case)]
+#[component]
+pub fn ManualCatalogue(cx: Scope) -> impl IntoView {
+    let create_act = create_server_action::<crate::api::books::CreateBook>(cx);
+    let lookup_act = create_server_action::<LookupIsbn>(cx);
+
+    let (isbn, set_isbn) = create_signal(cx, "".to_string());
+    let (title, set_title) = create_signal(cx, "".to_string());
+    let (authors, set_authors) = create_signal(cx, "".to_string());
+    let (publisher, set_publisher) = create_signal(cx, "".to_string());
+    let (publish_date, set_publish_date) = create_signal(cx, "".to_string());
+    let (thumbnail, set_thumbnail) = create_signal(cx, "".to_string());
+    let (cover, set_cover) = create_signal(cx, "".to_string());
+    let (cover_error, set_cover_error) = create_signal(cx, None::<String>);
+
+    // 只填空着的字段，不覆盖管理员已经填写的内容
+    create_effect(cx, move |_| {
+        if let Some(Ok(Some(d))) = lookup_act.value().get() {
+            let fill = |v: ReadSignal<String>, set: WriteSignal<String>, d: String| {
+                if v.get_untracked().trim().is_empty() {
+                    set(d)
+                }
+            };
+            fill(title, set_title, d.title);
+            fill(authors, set_authors, d.authors.join(", "));
+            fill(publisher, set_publisher, d.publisher);
+            fill(publish_date, set_publish_date, d.publish_date);
+            fill(thumbnail, set_thumbnail, d.thumbnail);
+        }
+    });
+
+    let isbn_valid = move || {
+        let i = isbn.get();
+        i.trim().is_empty() || crate::api::isbn::normalize(&i).is_ok()
+    };
+    let lookup = move |_| {
+        lookup_act.dispatch(LookupIsbn { isbn: isbn.get() });
+    };
+    let lookup_result = move || {
+        lookup_act.value().get().map(|r| match r {
+            Ok(Some(_)) => view! { cx, <p class="text-xs text-gray-500">"已根据检索结果预填，请核对后提交"</p> },
+            Ok(None) => view! { cx, <p class="text-xs text-gray-500">"检索服务没有这本书的信息，请手工填写"</p> },
+            Err(e) => view! { cx, <p class="text-xs text-red-600">{action_error(e)}</p> },
+        })
+    };
+
+    // 读取选择的封面图片，转成 data URL 随表单提交
+    let on_cover = move |ev: web_sys::Event| {
+        set_cover(String::new());
+        set_cover_error(None);
+        let input: web_sys::HtmlInputElement = event_target(&ev);
+        let file = match input.files().and_then(|f| f.get(0)) {
+            Some(f) => f,
+            None => return,
+        };
+        if file.size() > MAX_COVER_BYTES as f64 {
+            set_cover_error(Some(format!(
+                "封面图片不能超过 {}KB",
+                MAX_COVER_BYTES / 1024
+            )));
+            return;
+        }
+        let reader = match web_sys::FileReader::new() {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+        let r = reader.clone();
+        let onload = Closure::<dyn FnMut()>::new(move || {
+            if let Some(url) = r.result().ok().and_then(|v| v.as_string()) {
+                set_cover(url);
+            }
+        });
+        reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+        onload.forget();
+        let _ = reader.read_as_data_url(&file);
+    };
+    let preview = move || {
+        let src = if cover.get().is_empty() {
+            thumbnail.get()
+        } else {
+            cover.get()
+        };
+        (!src.is_empty()).then(|| {
+            view! { cx, <img referrerpolicy="no-referrer" src=src class="h-24 w-auto rounded border object-contain"/> }
+        })
+    };
+
+    let result = move || {
+        create_act.value().get().map(|r| match r {
+            Ok(_) => view! { cx, <p class="text-sm text-green-600">"编目成功"</p> },
+            Err(e) => view! { cx, <p class="text-sm text-red-600">{action_error(e)}</p> },
+        })
+    };
+    let input_class = "w-full rounded-lg border-gray-200 p-3 text-sm";
+    view! {
+        cx,
+        <details class="rounded-lg border border-gray-200 p-4">
+        <summary class="cursor-pointer text-sm font-medium text-gray-900">"手工编目"</summary>
+        <ActionForm class="mt-4 grid grid-cols-3 gap-4" action=create_act>
+            <div class="col-span-2">
+                <label for="title" class="sr-only">"书名"</label>
+                <input type="text" name="title" class=input_class placeholder="书名" required
+                    prop:value=title on:input=move |ev| set_title(event_target_value(&ev))/>
+            </div>
+            <div>
+                <label for="authors" class="sr-only">"作者"</label>
+                <input type="text" name="authors" class=input_class placeholder="作者，多个作者用逗号分隔"
+                    prop:value=authors on:input=move |ev| set_authors(event_target_value(&ev))/>
+            </div>
+            <div>
+                <label for="publisher" class="sr-only">"出版社"</label>
+                <input type="text" name="publisher" class=input_class placeholder="出版社"
+                    prop:value=publisher on:input=move |ev| set_publisher(event_target_value(&ev))/>
+            </div>
+            <div>
+                <label for="publish_date" class="sr-only">"出版日期"</label>
+                <input type="text" name="publish_date" class=input_class placeholder="出版日期，例如 2020-5"
+                    prop:value=publish_date on:input=move |ev| set_publish_date(event_target_value(&ev))/>
+            </div>
+            <div>
+                <label for="barcode" class="sr-only">"条码"</label>
+                <input type="text" name="barcode" class=input_class placeholder="馆藏条码（可选）" autocomplete="off"/>
+            </div>
+            <div class="col-span-2 flex gap-2">
+                <label for="isbn" class="sr-only">"ISBN"</label>
+                <input type="text" name="isbn" class=input_class placeholder="ISBN（可选）" autocomplete="off"
+                    prop:value=isbn on:input=move |ev| set_isbn(event_target_value(&ev))/>
+                <button type="button" on:click=lookup
+                    disabled=move || isbn.get().trim().is_empty() || !isbn_valid() || lookup_act.pending().get()
+                    class="shrink-0 rounded-md border border-gray-300 px-4 text-sm text-gray-700 hover:bg-gray-50 disabled:opacity-50">
+                    "检索预填"
+                </button>
+            </div>
+            <div>
+                <label for="cover_file" class="sr-only">"封面"</label>
+                <input type="file" accept="image/png,image/jpeg,image/webp,image/gif" class="w-full text-sm" on:change=on_cover/>
+                <input type="hidden" name="cover" prop:value=cover/>
+                <input type="hidden" name="thumbnail" prop:value=thumbnail/>
+            </div>
+            <div class="col-span-2 space-y-1">
+                {lookup_result}
+                {move || (!isbn_valid()).then(|| view! { cx, <p class="text-xs text-red-600">"ISBN 格式不正确"</p> })}
+                {move || cover_error.get().map(|e| view! { cx, <p class="text-xs text-red-600">{e}</p> })}
+                {preview}
+            </div>
+            <button type="submit" disabled=move || !isbn_valid() || cover_error.get().is_some()
+                class="inline-block shrink-0 rounded-md border border-blue-600 bg-blue-600 px-12 py-3 text-sm font-medium text-white transition hover:bg-transparent hover:text-blue-600 focus:outline-none focus:ring active:text-blue-500 disabled:opacity-50">
+                "编目入库"
+            </button>
+        </ActionForm>
+        {result}
+        </details>
+    }
+}
+
+// 管理员修改书目信息，保存后原来的值记录在操作记录中，可以从操作记录撤销
+#[allow(non_snake_case)]
+#[component]
+pub fn TitleEditor(
+    cx: Scope,
+    book: BookUI,
+    update: Action<crate::api::books::UpdateBook, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let input_class = "w-full rounded-lg border-gray-200 p-3 text-sm";
+    view! {
+        cx,
+        <div class="mx-auto max-w-screen-xl px-4 pb-8">
+        <details class="rounded-lg border border-gray-200 p-4">
+        <summary class="cursor-pointer text-sm font-medium text-gray-900">"修改书目信息"</summary>
+        <ActionForm class="mt-4 grid grid-cols-2 gap-4" action=update>
+            <input type="hidden" name="title_id" value=book.title_id/>
+            <label class="text-sm text-gray-700">"书名"
+                <input type="text" name="title" class=input_class required value=book.title/>
+            </label>
+            <label class="text-sm text-gray-700">"副标题"
+                <input type="text" name="subtitle" class=input_class value=book.subtitle/>
+            </label>
+            <label class="text-sm text-gray-700">"作者（多个作者用逗号分隔）"
+                <input type="text" name="authors" class=input_class value=book.authors.join(", ")/>
+            </label>
+            <label class="text-sm text-gray-700">"出版社"
+                <input type="text" name="publisher" class=input_class value=book.publisher/>
+            </label>
+            <label class="text-sm text-gray-700">"出版日期"
+                <input type="text" name="publish_date" class=input_class value=book.publish_date/>
+            </label>
+            <label class="text-sm text-gray-700">"封面地址"
+                <input type="text" name="thumbnail" class=input_class value=book.thumbnail/>
+            </label>
+            <div>
+                <button type="submit" class="inline-block rounded-md border border-blue-600 bg-blue-600 px-12 py-3 text-sm font-medium text-white transition hover:bg-transparent hover:text-blue-600 focus:outline-none focus:ring active:text-blue-500">
+                    "保存"
+                </button>
+            </div>
+        </ActionForm>
+        </details>
+        </div>
+    }
+}
@@ -0,0 +1,101 @@
+use crate::api::entity::BookState;
+use crate::api::search::{BookQuery, BookSort, FacetCount, FacetField, Facets};
+use leptos::*;
+
+// 已选的筛选条件，点击后去掉这个条件
+#[allow(non_snake_case)]
+#[component]
+pub fn FilterChips(
+    cx: Scope,
+    filters: ReadSignal<BookQuery>,
+    set_filters: WriteSignal<BookQuery>,
+) -> impl IntoView {
+    let chips = move || {
+        filters
+            .get()
+            .chips()
+            .into_iter()
+            .map(|(field, value)| {
+                view! { cx,
+                    <button type="button"
+                        class="inline-flex items-center gap-1 rounded-full bg-blue-50 px-3 py-1 text-xs text-blue-700 hover:bg-blue-100"
+                        on:click=move |_| set_filters.update(|f| *f = f.without(field))>
+                        {format!("{}：{}", field.to_string(), value)}
+                        <span aria-hidden="true">"×"</span>
+                    </button>
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    view! { cx, <div class="flex flex-wrap items-center gap-2">{chips}</div> }
+}
+
+#[allow(non_snake_case)]
+#[component]
+pub fn SortSelect(
+    cx: Scope,
+    filters: ReadSignal<BookQuery>,
+    set_filters: WriteSignal<BookQuery>,
+) -> impl IntoView {
+    let options = BookSort::all()
+        .into_iter()
+        .map(|s| {
+            view! { cx,
+                <option value=s.code() selected=move || filters.get().sort == Some(s)>{s.to_string()}</option>
+            }
+        })
+        .collect::<Vec<_>>();
+    view! { cx,
+        <select class="h-10 rounded-lg border-gray-200 text-sm"
+            on:change=move |ev| {
+                let sort = BookSort::from_code(&event_target_value(&ev));
+                set_filters.update(|f| f.sort = sort);
+            }>
+            <option value="" selected=move || filters.get().sort.is_none()>"默认排序"</option>
+            {options}
+        </select>
+    }
+}
+
+// 各字段在当前结果中最常见的取值，点击后增加这个筛选条件
+#[allow(non_snake_case)]
+#[component]
+pub fn FacetPanel(
+    cx: Scope,
+    facets: Facets,
+    filters: ReadSignal<BookQuery>,
+    set_filters: WriteSignal<BookQuery>,
+) -> impl IntoView {
+    let groups = facets
+        .groups()
+        .into_iter()
+        .filter(|(_, counts)| !counts.is_empty())
+        .map(|(field, counts)| {
+            let values = counts
+                .into_iter()
+                .map(|FacetCount { value, count }| {
+                    let label = match field {
+                        FacetField::State => BookState::from_code(&value).to_string(),
+                        _ => value.clone(),
+                    };
+                    view! { cx,
+                        <li>
+                            <button type="button" class="text-left text-sm text-gray-700 hover:text-blue-600"
+                                on:click=move |_| set_filters.set(filters.get().with(field, &value))>
+                                {label}
+                                <span class="ml-1 text-xs text-gray-400">{count}</span>
+                            </button>
+                        </li>
+                    }
+                })
+                .collect::<Vec<_>>();
+            view! { cx,
+                <div>
+                    <h3 class="text-sm font-medium text-gray-900">{field.to_string()}</h3>
+                    <ul class="mt-2 space-y-1">{values}</ul>
+                </div>
+            }
+        })
+        .collect::<Vec<_>>();
+    view! { cx, <div class="space-y-4">{groups}</div> }
+}
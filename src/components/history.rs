@@ -0,0 +1,177 @@
+use crate::api::books::ChangeLogUI;
+use crate::api::entity::ChangeAction;
+use crate::components::book::format_date;
+use crate::components::pagination::*;
+use leptos::*;
+use leptos_router::*;
+
+const PAGE_SIZE: i64 = 10;
+
+// 一本书从入库开始的全部操作记录
+#[allow(non_snake_case)]
+#[component]
+pub fn Timeline(
+    cx: Scope,
+    book_id: i64,
+    revert: Action<crate::api::books::RevertEdit, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let page = use_page(cx);
+
+    // 多取一条，用来判断还有没有下一页
+    let logs = create_resource(
+        cx,
+        move || page.get(),
+        move |page| {
+            crate::api::books::book_history(
+                cx,
+                book_id,
+                Some((page - 1) * PAGE_SIZE),
+                Some(PAGE_SIZE + 1),
+            )
+        },
+    );
+    let next = Signal::derive(cx, move || {
+        logs.read(cx)
+            .and_then(|r| r.ok())
+            .filter(|logs| logs.len() as i64 > PAGE_SIZE)
+            .map(|_| String::new())
+    });
+
+    let g = move || match logs.read(cx) {
+        None => None,
+        Some(Err(_)) => None,
+        Some(Ok(mut logs)) => {
+            logs.truncate(PAGE_SIZE as usize);
+            Some(view! {
+                cx,
+                <div>
+                {if logs.is_empty() {
+                    view! { cx, <p class="text-sm text-gray-500">"没有更多记录了"</p> }.into_view(cx)
+                } else {
+                    view! { cx,
+                        <ol class="relative border-l border-gray-200">
+                        <For each=move || logs.clone() key=|l| l.id
+                        view=move |cx, l: ChangeLogUI| {
+                            view! { cx,
+                                <li class="mb-4 ml-4">
+                                    <div class="absolute -left-1.5 mt-1.5 h-3 w-3 rounded-full border border-white bg-gray-300"></div>
+                                    <time class="text-xs text-gray-400">{format_date(l.operate_at)}</time>
+                                    <p class="text-sm">
+                                        <span class="font-medium">{l.operator_name.clone()}</span>
+                                        " "
+                                        {describe(&l)}
+                                    </p>
+                                    {l.revertible.then(|| view! { cx,
+                                        <ActionForm action=revert>
+                                            <input type="hidden" name="log_id" value=l.id/>
+                                            <button type="submit" class="text-xs text-blue-600 hover:underline">"撤销这次修改"</button>
+                                        </ActionForm>
+                                    })}
+                                </li>
+                            }
+                        }/>
+                        </ol>
+                    }.into_view(cx)
+                }}
+                </div>
+            })
+        }
+    };
+
+    view! {
+        cx,
+        <div class="mx-auto max-w-screen-xl px-4 pb-8">
+            <h2 class="mb-4 text-lg font-bold">"操作记录"</h2>
+            <Suspense fallback=move || view! { cx, <p>"Loading..."</p> }>
+                {g}
+            </Suspense>
+            <Pagination total=None per_page=PAGE_SIZE next=next/>
+        </div>
+    }
+}
+
+// 操作记录的文字说明，旧数据无法识别类型时展示迁移前的原始文字
+fn describe(l: &ChangeLogUI) -> String {
+    let payload = |key: &str| {
+        l.payload
+            .as_ref()
+            .and_then(|p| p.get(key))
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string())
+    };
+    let flag = |key: &str| {
+        l.payload
+            .as_ref()
+            .and_then(|p| p.get(key))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    };
+    let text = match (&l.action, payload("legacy")) {
+        (ChangeAction::Unknown, Some(legacy)) => return legacy,
+        (ChangeAction::Stored, _) if flag("manual") => "手工编目入库".to_string(),
+        (ChangeAction::Stored, _) if flag("new_title") => "新书第一次入库".to_string(),
+        (ChangeAction::Stored, _) => "新增馆藏副本".to_string(),
+        (ChangeAction::Edited, _) if l.is_title_edit() => describe_edit(l),
+        (action, _) => action.to_string(),
+    };
+    // 系统自动处理的记录说明是谁的预约
+    let text = match payload("requester") {
+        Some(r) => format!("{}（{} 的预约）", text, r),
+        None => text,
+    };
+    let text = match payload("note") {
+        Some(note) => format!("{}：{}", text, note),
+        None => text,
+    };
+    match payload("reserved_for") {
+        Some(r) => format!("{}，为 {} 保留", text, r),
+        None => text,
+    }
+}
+
+// 书目修改记录列出每个字段修改前后的值
+fn describe_edit(l: &ChangeLogUI) -> String {
+    let field = |key: &str| match key {
+        "title" => "书名",
+        "subtitle" => "副标题",
+        "authors" => "作者",
+        "publisher" => "出版社",
+        "publish_date" => "出版日期",
+        "thumbnail" => "封面",
+        _ => "其他",
+    };
+    let show = |v: Option<&serde_json::Value>| match v {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(serde_json::Value::Array(a)) => a
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => "".to_string(),
+    };
+    let payload = l.payload.clone().unwrap_or_default();
+    let changes: Vec<String> = payload["after"]
+        .as_object()
+        .map(|after| {
+            after
+                .iter()
+                .map(|(k, v)| {
+                    format!(
+                        "{}「{}」→「{}」",
+                        field(k),
+                        show(payload["before"].get(k)),
+                        show(Some(v))
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let verb = if payload.get("reverted").is_some() {
+        "撤销了书目修改"
+    } else if payload.get("refreshed").is_some() {
+        "重新查询了 ISBN 信息"
+    } else {
+        "修改了书目信息"
+    };
+    format!("{}：{}", verb, changes.join("；"))
+}
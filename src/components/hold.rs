@@ -0,0 +1,88 @@
+use crate::api::books::HoldUI;
+use crate::components::book::{action_error, format_date};
+use leptos::*;
+use leptos_router::*;
+
+// 书籍所属书目的预约队列，以及预约、取消预约按钮
+#[allow(non_snake_case)]
+#[component]
+pub fn HoldQueue(cx: Scope, book_id: i64) -> impl IntoView {
+    let place_act = create_server_action::<crate::api::books::PlaceHold>(cx);
+    let cancel_act = create_server_action::<crate::api::books::CancelHold>(cx);
+
+    let queue = create_resource(
+        cx,
+        move || (place_act.version().get(), cancel_act.version().get()),
+        move |_| crate::api::books::title_holds(cx, book_id),
+    );
+
+    let act_err = move || {
+        [place_act.value().get(), cancel_act.value().get()]
+            .into_iter()
+            .find_map(|r| r.and_then(|r| r.err()))
+            .map(|e| {
+                view! { cx,
+                    <p class="rounded bg-red-50 px-4 py-2 text-sm text-red-600">{action_error(e)}</p>
+                }
+            })
+    };
+
+    let g = move || match queue.read(cx) {
+        None => None,
+        Some(Err(_)) => None,
+        Some(Ok(q)) => Some(view! {
+            cx,
+            <div>
+            {q.can_place.then(|| view! { cx,
+                <ActionForm action=place_act class="mb-4">
+                    <input type="hidden" name="id" value=book_id/>
+                    <button type="submit" class="block rounded bg-blue-600 px-5 py-2 text-sm font-medium text-white hover:bg-blue-500">
+                    "预约排队"
+                    </button>
+                </ActionForm>
+            })}
+            {if q.holds.is_empty() {
+                view! { cx, <p class="text-sm text-gray-500">"暂时没有同事在排队"</p> }.into_view(cx)
+            } else {
+                view! { cx,
+                    <ol class="space-y-2 text-sm">
+                    <For each=move || q.holds.clone() key=|h| h.id
+                    view=move |cx, h: HoldUI| {
+                        view! { cx,
+                            <li class="flex items-center gap-4">
+                                <span class="font-medium">{format!("第 {} 位", h.position)}</span>
+                                <span>{h.requester_name.clone()}</span>
+                                {h.ready.then(|| view! { cx,
+                                    <span class="text-green-600">
+                                    {format!("已保留，请在 {} 前取书", h.expires_at.map(format_date).unwrap_or("".to_string()))}
+                                    </span>
+                                })}
+                                {h.mine.then(|| view! { cx,
+                                    <ActionForm action=cancel_act class="inline-block">
+                                        <input type="hidden" name="id" value=h.id/>
+                                        <button type="submit" class="rounded border border-gray-300 px-3 py-1 text-xs text-gray-600 hover:bg-gray-50">
+                                        "取消预约"
+                                        </button>
+                                    </ActionForm>
+                                })}
+                            </li>
+                        }
+                    }/>
+                    </ol>
+                }.into_view(cx)
+            }}
+            </div>
+        }),
+    };
+
+    view! {
+        cx,
+        <div class="mx-auto max-w-screen-xl px-4 pb-8">
+            <h2 class="mb-4 text-lg font-bold">"预约队列"</h2>
+            {act_err}
+            <Suspense fallback=move || view! { cx, <p>"Loading..."</p> }>
+                {g}
+            </Suspense>
+        </div>
+    }
+}
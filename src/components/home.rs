@@ -1,7 +1,14 @@
+use crate::api::search::BookQuery;
 use crate::components::assets::*;
 use crate::components::auth::*;
 use crate::components::book::*;
 use crate::components::book_gallery::*;
+use crate::components::facets::*;
+use crate::components::import::*;
+use crate::components::my::*;
+use crate::components::pagination::*;
+use crate::components::returns::*;
+use crate::components::stats::*;
 use leptos::*;
 use leptos_meta::*;
 use leptos_router::SsrMode::InOrder;
@@ -34,8 +41,11 @@ pub fn BlogApp(cx: Scope) -> impl IntoView {
         <Route path="" view=|cx| view! {cx,<DefaultPage/>}/>
         <Route path="book/:id" view=|cx| view! {cx,<BookDetailPage/>}/>
         <Route path="assets-mgr" view=|cx| view! {cx,<AssetsPage/>}/>
+        <Route path="assets-mgr/import" view=|cx| view! {cx,<ImportPage/>}/>
+        <Route path="assets-mgr/stats" view=|cx| view! {cx,<StatsPage/>}/>
+        <Route path="assets-mgr/returns" view=|cx| view! {cx,<ReturnQueuePage/>}/>
         <Route path="login" view= move |cx| view! {cx,<LoginPage action=login_action/>}/>
-        <Route path="my" view= move |cx| view! {cx,<div>"My"</div>}/>
+        <Route path="my" view=|cx| view! {cx,<MyPage/>}/>
         </Routes>
         </main>
       </Router>
@@ -152,19 +162,55 @@ pub fn Header(
     }
 }
 
+const PAGE_SIZE: i64 = 24;
+
 #[allow(non_snake_case)]
 #[component]
 pub fn DefaultPage(cx: Scope) -> impl IntoView {
     let query = use_query_map(cx);
     let search_filter = move || query.with(|q| q.get("q").cloned());
+    // 搜索词来自地址栏，其它筛选条件和排序保存在 filters 中
+    let (filters, set_filters) = create_signal(cx, BookQuery::default());
+    let page = use_page(cx);
+    let cursor = use_cursor(cx);
+    // 筛选条件变化后回到第一页，第一次运行时保留地址栏中的页码
+    create_effect(cx, move |prev: Option<()>| {
+        filters.with(|_| ());
+        if prev.is_some() {
+            reset_page(cx);
+        }
+    });
 
     let books = create_resource(
         cx,
-        // move || (query().get("q").cloned()),
-        search_filter,
-        move |q| async move {
-            trace!("default query: {:?}", q);
-            crate::api::books::book_list(cx, None, None, q).await
+        move || {
+            (
+                page.get(),
+                cursor.get(),
+                BookQuery {
+                    q: search_filter().filter(|q| !q.trim().is_empty()),
+                    ..filters.get()
+                },
+            )
+        },
+        move |(page, cursor, f)| async move {
+            trace!("default query: {:?}", f);
+            crate::api::books::title_list(
+                cx,
+                cursor,
+                Some((page - 1) * PAGE_SIZE),
+                Some(PAGE_SIZE),
+                f.q,
+                f.state,
+                f.author,
+                f.publisher,
+                f.year_from,
+                f.year_to,
+                f.tag,
+                f.location,
+                f.sort,
+            )
+            .await
         },
     );
 
@@ -207,6 +253,8 @@ pub fn DefaultPage(cx: Scope) -> impl IntoView {
             </button>
           </div>
         </Form>
+        <SortSelect filters=filters set_filters=set_filters/>
+        <FilterChips filters=filters set_filters=set_filters/>
       </div>
 
                 </div>
@@ -215,9 +263,19 @@ pub fn DefaultPage(cx: Scope) -> impl IntoView {
             books.read(cx)
                 .map(|a| {
                 match a {
-                    Ok(books) => view! {
+                    Ok(list) => view! {
                         cx,
-                        <BookGallery books=books />
+                        <div class="mx-auto max-w-screen-xl px-4 flex gap-4">
+                            <aside class="hidden lg:block w-48 shrink-0 my-4">
+                                <FacetPanel facets=list.facets filters=filters set_filters=set_filters/>
+                            </aside>
+                            <div class="flex-1">
+                                <BookGallery books=list.books />
+                                <div class="my-4 px-4 lg:px-8">
+                                    <Pagination total=Some(list.total) per_page=PAGE_SIZE next=list.next_cursor/>
+                                </div>
+                            </div>
+                        </div>
                     }.into_view(cx),
                     Err(e) => view! {
                         cx,
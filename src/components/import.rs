@@ -0,0 +1,240 @@
+use crate::api::import::{
+    ColumnMapping, CommitImport, ImportResultUI, ImportRowUI, ImportStatus, PreviewImport,
+};
+use crate::components::book::action_error;
+use leptos::*;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+
+// 批量入库：选择文件后先预览每一行的处理情况，确认映射和结果无误后再提交
+#[allow(non_snake_case)]
+#[component]
+pub fn ImportPage(cx: Scope) -> impl IntoView {
+    let preview_act = create_server_action::<PreviewImport>(cx);
+    let commit_act = create_server_action::<CommitImport>(cx);
+
+    let (file_name, set_file_name) = create_signal(cx, "".to_string());
+    let (content, set_content) = create_signal(cx, "".to_string());
+    let (mapping, set_mapping) = create_signal(cx, ColumnMapping::default());
+    let (result, set_result) = create_signal(cx, None::<Result<ImportResultUI, ServerFnError>>);
+
+    let dispatch_preview = move || {
+        let m = mapping.get_untracked();
+        preview_act.dispatch(PreviewImport {
+            file_name: file_name.get_untracked(),
+            content: content.get_untracked(),
+            isbn_column: Some(m.isbn),
+            barcode_column: Some(m.barcode),
+            title_column: Some(m.title),
+            location_column: Some(m.location),
+            donor_column: Some(m.donor),
+        });
+    };
+    let dispatch_commit = move |_| {
+        let m = mapping.get_untracked();
+        commit_act.dispatch(CommitImport {
+            file_name: file_name.get_untracked(),
+            content: content.get_untracked(),
+            isbn_column: Some(m.isbn),
+            barcode_column: Some(m.barcode),
+            title_column: Some(m.title),
+            location_column: Some(m.location),
+            donor_column: Some(m.donor),
+        });
+    };
+
+    // 预览时服务端可能猜出了列映射，同步到下拉框
+    create_effect(cx, move |_| {
+        if let Some(r) = preview_act.value().get() {
+            if let Ok(p) = &r {
+                set_mapping(p.mapping.clone());
+            }
+            set_result(Some(r));
+        }
+    });
+    create_effect(cx, move |_| {
+        if let Some(r) = commit_act.value().get() {
+            set_result(Some(r));
+        }
+    });
+
+    // 读取文件内容，读完后按猜测的列映射预览一次
+    let on_file = move |ev: web_sys::Event| {
+        let input: web_sys::HtmlInputElement = event_target(&ev);
+        let file = match input.files().and_then(|f| f.get(0)) {
+            Some(f) => f,
+            None => return,
+        };
+        set_file_name(file.name());
+        set_mapping(ColumnMapping::default());
+        set_result(None);
+        let reader = match web_sys::FileReader::new() {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+        let r = reader.clone();
+        let onload = Closure::<dyn FnMut()>::new(move || {
+            if let Some(url) = r.result().ok().and_then(|v| v.as_string()) {
+                set_content(url);
+                dispatch_preview();
+            }
+        });
+        reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+        onload.forget();
+        let _ = reader.read_as_data_url(&file);
+    };
+
+    let pending = move || preview_act.pending().get() || commit_act.pending().get();
+    let committed = move || {
+        matches!(result.get(), Some(Ok(r)) if r.rows.iter().any(|r| r.status == ImportStatus::Stored))
+    };
+    let importable = move || match result.get() {
+        Some(Ok(r)) => r.rows.iter().filter(|r| r.status.importable()).count(),
+        _ => 0,
+    };
+
+    let g = move || {
+        result.get().map(|r| match r {
+            Err(e) => view! { cx, <p class="text-sm text-red-600">{action_error(e)}</p> }.into_view(cx),
+            Ok(r) => view! { cx,
+                <div class="space-y-4">
+                    <MappingForm headers=r.headers.clone() mapping=mapping set_mapping=set_mapping/>
+                    <ImportSummary rows=r.rows.clone()/>
+                    <ImportRows rows=r.rows/>
+                </div>
+            }.into_view(cx),
+        })
+    };
+
+    view! {
+        cx,
+        <div class="mx-auto max-w-screen-xl px-4 my-4 space-y-4">
+            <h2 class="text-lg font-bold">"批量入库"</h2>
+            <p class="text-sm text-gray-500">"支持 CSV 和 XLSX 文件，第一行为表头，至少要有 ISBN 或书名一列。"</p>
+            <input type="file" accept=".csv,.xlsx,.xls" class="text-sm" on:change=on_file/>
+            {g}
+            <div class="flex gap-4">
+                <button type="button" on:click=move |_| dispatch_preview()
+                    disabled=move || content.get().is_empty() || pending()
+                    class="rounded-md border border-blue-600 px-8 py-2 text-sm font-medium text-blue-600 hover:bg-blue-50 disabled:opacity-50">
+                    "重新预览"
+                </button>
+                <button type="button" on:click=dispatch_commit
+                    disabled=move || importable() == 0 || committed() || pending()
+                    class="rounded-md border border-blue-600 bg-blue-600 px-8 py-2 text-sm font-medium text-white hover:bg-transparent hover:text-blue-600 disabled:opacity-50">
+                    {move || format!("确认导入 {} 本", importable())}
+                </button>
+            </div>
+            {move || pending().then(|| view! { cx, <p class="text-sm text-gray-500">"正在处理，需要逐行查询 ISBN，请稍候..."</p> })}
+        </div>
+    }
+}
+
+// 每个字段选择文件中的一列，修改后需要重新预览
+#[allow(non_snake_case)]
+#[component]
+fn MappingForm(
+    cx: Scope,
+    headers: Vec<String>,
+    mapping: ReadSignal<ColumnMapping>,
+    set_mapping: WriteSignal<ColumnMapping>,
+) -> impl IntoView {
+    type Field = fn(&mut ColumnMapping) -> &mut String;
+    let fields: Vec<(&'static str, Field)> = vec![
+        ("ISBN", |m| &mut m.isbn),
+        ("条码", |m| &mut m.barcode),
+        ("书名", |m| &mut m.title),
+        ("位置", |m| &mut m.location),
+        ("捐赠人", |m| &mut m.donor),
+    ];
+    let selects = fields
+        .into_iter()
+        .map(|(label, field)| {
+            let options = headers
+                .iter()
+                .map(|h| {
+                    let h = h.clone();
+                    let v = h.clone();
+                    view! { cx,
+                        <option value=h.clone() selected=move || *field(&mut mapping.get()) == v>{h}</option>
+                    }
+                })
+                .collect::<Vec<_>>();
+            view! { cx,
+                <label class="text-sm text-gray-700">{label}
+                    <select class="mt-1 w-full rounded-lg border-gray-200 text-sm"
+                        on:change=move |ev| {
+                            let v = event_target_value(&ev);
+                            set_mapping.update(|m| *field(m) = v);
+                        }>
+                        <option value="" selected=move || field(&mut mapping.get()).is_empty()>"（无）"</option>
+                        {options}
+                    </select>
+                </label>
+            }
+        })
+        .collect::<Vec<_>>();
+    view! { cx, <div class="grid grid-cols-5 gap-4">{selects}</div> }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn ImportSummary(cx: Scope, rows: Vec<ImportRowUI>) -> impl IntoView {
+    let count = |s: ImportStatus| rows.iter().filter(|r| r.status == s).count();
+    let summary = [
+        ImportStatus::Stored,
+        ImportStatus::New,
+        ImportStatus::Copy,
+        ImportStatus::Manual,
+        ImportStatus::Duplicate,
+        ImportStatus::Failed,
+    ]
+    .into_iter()
+    .filter_map(|s| {
+        let n = count(s.clone());
+        (n > 0).then(|| format!("{} {}", s, n))
+    })
+    .collect::<Vec<_>>()
+    .join("，");
+    view! { cx, <p class="text-sm text-gray-700">{format!("共 {} 行：{}", rows.len(), summary)}</p> }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn ImportRows(cx: Scope, rows: Vec<ImportRowUI>) -> impl IntoView {
+    view! {
+        cx,
+        <table class="min-w-full divide-y-2 divide-gray-200 text-sm">
+            <thead>
+                <tr>
+                    <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"行"</th>
+                    <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"ISBN"</th>
+                    <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"条码"</th>
+                    <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"书名"</th>
+                    <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"结果"</th>
+                    <th class="px-4 py-2 text-left font-medium text-gray-900">"说明"</th>
+                </tr>
+            </thead>
+            <tbody class="divide-y divide-gray-200">
+                <For each=move || rows.clone() key=|r| r.line
+                view=move |cx, r: ImportRowUI| {
+                    let color = match r.status {
+                        ImportStatus::Duplicate | ImportStatus::Failed => "text-red-600",
+                        ImportStatus::Stored => "text-green-600",
+                        _ => "text-gray-700",
+                    };
+                    view! { cx,
+                        <tr>
+                            <td class="whitespace-nowrap px-4 py-2 text-gray-700">{r.line}</td>
+                            <td class="whitespace-nowrap px-4 py-2 text-gray-700">{r.isbn}</td>
+                            <td class="whitespace-nowrap px-4 py-2 text-gray-700">{r.barcode}</td>
+                            <td class="px-4 py-2 text-gray-700">{r.title}</td>
+                            <td class=format!("whitespace-nowrap px-4 py-2 {}", color)>{r.status.to_string()}</td>
+                            <td class="px-4 py-2 text-gray-500">{r.message}</td>
+                        </tr>
+                    }
+                }/>
+            </tbody>
+        </table>
+    }
+}
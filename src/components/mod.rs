@@ -2,5 +2,13 @@ pub mod assets;
 pub mod auth;
 pub mod book;
 pub mod book_gallery;
+pub mod catalogue;
+pub mod facets;
+pub mod history;
+pub mod hold;
 pub mod home;
+pub mod import;
+pub mod my;
 pub mod pagination;
+pub mod returns;
+pub mod stats;
@@ -0,0 +1,278 @@
+use crate::api::books::{BookAction, BookUI};
+use crate::api::entity::ChangeAction;
+use crate::api::my::{BorrowRecordUI, MyHoldUI};
+use crate::components::book::{action_error, format_date, DueDate, DueDateProps};
+use crate::components::pagination::*;
+use leptos::*;
+use leptos_router::*;
+
+const PAGE_SIZE: i64 = 10;
+
+// 个人主页：当前借阅、等待确认归还、预约和借阅历史
+#[allow(non_snake_case)]
+#[component]
+pub fn MyPage(cx: Scope) -> impl IntoView {
+    let return_act = create_server_action::<crate::api::books::ReturnBook>(cx);
+    let renew_act = create_server_action::<crate::api::books::RenewLoan>(cx);
+    let cancel_act = create_server_action::<crate::api::books::CancelHold>(cx);
+
+    let books = create_resource(
+        cx,
+        move || {
+            (
+                return_act.version().get(),
+                renew_act.version().get(),
+                cancel_act.version().get(),
+            )
+        },
+        move |_| crate::api::my::my_books(cx),
+    );
+
+    let act_err = move || {
+        [
+            return_act.value().get(),
+            renew_act.value().get(),
+            cancel_act.value().get(),
+        ]
+        .into_iter()
+        .find_map(|r| r.and_then(|r| r.err()))
+        .map(|e| {
+            view! { cx,
+                <p class="rounded bg-red-50 px-4 py-2 text-sm text-red-600">{action_error(e)}</p>
+            }
+        })
+    };
+
+    let g = move || match books.read(cx) {
+        None => None,
+        Some(Err(_)) => Some(
+            view! { cx,
+                <p class="text-sm text-gray-500">
+                    "请先"<A href="/login" class="text-blue-600">"登录"</A>
+                </p>
+            }
+            .into_view(cx),
+        ),
+        Some(Ok(my)) => {
+            let content = view! { cx,
+                <section>
+                    <h2 class="mb-4 text-lg font-bold">"正在借阅"</h2>
+                    {if my.borrowed.is_empty() {
+                        view! { cx, <p class="text-sm text-gray-500">"目前没有借阅中的书籍"</p> }.into_view(cx)
+                    } else {
+                        view! { cx,
+                            <ul class="divide-y divide-gray-100">
+                            <For each=move || my.borrowed.clone() key=|b| b.id
+                            view=move |cx, b: BookUI| view! { cx,
+                                <LoanItem book=b return_act=return_act renew_act=renew_act/>
+                            }/>
+                            </ul>
+                        }.into_view(cx)
+                    }}
+                </section>
+                {(!my.returned.is_empty()).then(|| view! { cx,
+                    <section class="mt-8">
+                        <h2 class="mb-4 text-lg font-bold">"等待管理员确认归还"</h2>
+                        <ul class="divide-y divide-gray-100">
+                        <For each=move || my.returned.clone() key=|b| b.id
+                        view=move |cx, b: BookUI| view! { cx,
+                            <li class="flex items-center justify-between py-3 text-sm">
+                                <A href=format!("/book/{}", b.id) class="font-medium text-gray-900">{b.title.clone()}</A>
+                                <span class="text-xs text-gray-500">{format!("{} 归还", format_date(b.operate_at))}</span>
+                            </li>
+                        }/>
+                        </ul>
+                    </section>
+                })}
+                <section class="mt-8">
+                    <h2 class="mb-4 text-lg font-bold">"我的预约"</h2>
+                    {if my.holds.is_empty() {
+                        view! { cx, <p class="text-sm text-gray-500">"目前没有预约"</p> }.into_view(cx)
+                    } else {
+                        view! { cx,
+                            <ul class="divide-y divide-gray-100">
+                            <For each=move || my.holds.clone() key=|h| h.id
+                            view=move |cx, h: MyHoldUI| view! { cx,
+                                <HoldItem hold=h cancel_act=cancel_act/>
+                            }/>
+                            </ul>
+                        }.into_view(cx)
+                    }}
+                </section>
+            };
+            Some(content.into_view(cx))
+        }
+    };
+
+    view! {
+        cx,
+        <div class="mx-auto max-w-screen-xl px-4 py-8">
+            {act_err}
+            <Suspense fallback=move || view! { cx, <p>"Loading..."</p> }>
+                {g}
+            </Suspense>
+            <BorrowHistory/>
+        </div>
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn LoanItem(
+    cx: Scope,
+    book: BookUI,
+    return_act: Action<crate::api::books::ReturnBook, Result<(), ServerFnError>>,
+    renew_act: Action<crate::api::books::RenewLoan, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let can_return = book.actions.contains(&BookAction::Return);
+    let can_renew = book.actions.contains(&BookAction::Renew);
+    view! { cx,
+        <li class="flex items-center justify-between gap-4 py-3">
+            <div>
+                <A href=format!("/book/{}", book.id) class="text-sm font-medium text-gray-900">{book.title.clone()}</A>
+                <p class="text-xs text-gray-500">{book.authors.join(", ")}</p>
+                <DueDate due_at=book.due_at overdue=book.overdue/>
+                {(book.renewals > 0).then(|| view! { cx,
+                    <span class="ml-2 text-xs text-gray-400">{format!("已续借 {} 次", book.renewals)}</span>
+                })}
+            </div>
+            <div class="flex gap-2">
+                {can_renew.then(|| view! { cx,
+                    <ActionForm action=renew_act>
+                        <input type="hidden" name="id" value=book.id/>
+                        <button type="submit" class="rounded border border-green-600 px-3 py-1 text-xs text-green-600 hover:bg-green-50">
+                        "续借"
+                        </button>
+                    </ActionForm>
+                })}
+                {can_return.then(|| view! { cx,
+                    <ActionForm action=return_act>
+                        <input type="hidden" name="id" value=book.id/>
+                        <button type="submit" class="rounded bg-green-600 px-3 py-1 text-xs text-white hover:bg-green-500">
+                        "归还"
+                        </button>
+                    </ActionForm>
+                })}
+            </div>
+        </li>
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn HoldItem(
+    cx: Scope,
+    hold: MyHoldUI,
+    cancel_act: Action<crate::api::books::CancelHold, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let title = match hold.book_id {
+        Some(id) => view! { cx,
+            <A href=format!("/book/{}", id) class="text-sm font-medium text-gray-900">{hold.title.clone()}</A>
+        }
+        .into_view(cx),
+        None => view! { cx, <span class="text-sm font-medium text-gray-900">{hold.title.clone()}</span> }
+            .into_view(cx),
+    };
+    let status = if hold.ready {
+        format!(
+            "已保留，请在 {} 前取书",
+            hold.expires_at.map(format_date).unwrap_or("".to_string())
+        )
+    } else {
+        format!("排在第 {} 位", hold.position)
+    };
+    let status_class = if hold.ready {
+        "text-xs text-green-600"
+    } else {
+        "text-xs text-gray-500"
+    };
+    view! { cx,
+        <li class="flex items-center justify-between gap-4 py-3">
+            <div>
+                {title}
+                <p class=status_class>{status}</p>
+            </div>
+            <ActionForm action=cancel_act>
+                <input type="hidden" name="id" value=hold.id/>
+                <button type="submit" class="rounded border border-gray-300 px-3 py-1 text-xs text-gray-600 hover:bg-gray-50">
+                "取消预约"
+                </button>
+            </ActionForm>
+        </li>
+    }
+}
+
+// 全部借阅历史，页码保存在地址栏中
+#[allow(non_snake_case)]
+#[component]
+fn BorrowHistory(cx: Scope) -> impl IntoView {
+    let page = use_page(cx);
+    let history = create_resource(
+        cx,
+        move || page.get(),
+        move |page| crate::api::my::my_history(cx, Some((page - 1) * PAGE_SIZE), Some(PAGE_SIZE)),
+    );
+    let total = Signal::derive(cx, move || {
+        history.read(cx).and_then(|r| r.ok()).map(|h| h.total)
+    });
+
+    let g = move || match history.read(cx) {
+        None => None,
+        Some(Err(_)) => None,
+        Some(Ok(h)) if h.records.is_empty() => {
+            Some(view! { cx, <p class="text-sm text-gray-500">"还没有借阅记录"</p> }.into_view(cx))
+        }
+        Some(Ok(h)) => {
+            let content = view! { cx,
+                <table class="min-w-full divide-y-2 divide-gray-200 text-sm">
+                    <thead>
+                        <tr>
+                            <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"书名"</th>
+                            <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"借出日期"</th>
+                            <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"归还日期"</th>
+                        </tr>
+                    </thead>
+                    <tbody class="divide-y divide-gray-200">
+                    <For each=move || h.records.clone() key=|r| r.id
+                    view=move |cx, r: BorrowRecordUI| {
+                        let end = ended(&r);
+                        view! { cx,
+                            <tr>
+                                <td class="whitespace-nowrap px-4 py-2">
+                                    <A href=format!("/book/{}", r.book_id) class="text-gray-900">{r.title.clone()}</A>
+                                </td>
+                                <td class="whitespace-nowrap px-4 py-2 text-gray-700">{format_date(r.borrowed_at)}</td>
+                                <td class="whitespace-nowrap px-4 py-2 text-gray-700">{end}</td>
+                            </tr>
+                        }
+                    }/>
+                    </tbody>
+                </table>
+            };
+            Some(content.into_view(cx))
+        }
+    };
+
+    view! {
+        cx,
+        <section class="mt-8">
+            <h2 class="mb-4 text-lg font-bold">"借阅历史"</h2>
+            <Suspense fallback=move || view! { cx, <p>"Loading..."</p> }>
+                {g}
+            </Suspense>
+            <div class="mt-4">
+                <Pagination total=total per_page=PAGE_SIZE next=None/>
+            </div>
+        </section>
+    }
+}
+
+// 借阅的结束方式，正常归还时只显示日期
+fn ended(r: &BorrowRecordUI) -> String {
+    match (&r.ended_at, &r.ended_by) {
+        (None, _) => "借阅中".to_string(),
+        (Some(d), Some(ChangeAction::Returned)) | (Some(d), None) => format_date(*d),
+        (Some(d), Some(ChangeAction::Lost)) => format!("{} 遗失", format_date(*d)),
+        (Some(d), Some(action)) => format!("{} {}", format_date(*d), action.to_string()),
+    }
+}
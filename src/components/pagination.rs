@@ -1,75 +1,134 @@
 use leptos::*;
+use leptos_router::*;
 
+// 地址栏中的页码，从 1 开始
+pub fn use_page(cx: Scope) -> Memo<i64> {
+    let query = use_query_map(cx);
+    create_memo(cx, move |_| {
+        query.with(|q| {
+            q.get("page")
+                .and_then(|p| p.parse::<i64>().ok())
+                .filter(|p| *p > 0)
+                .unwrap_or(1)
+        })
+    })
+}
+
+// 地址栏中的分页游标，从上一页的“下一页”进入时才有
+pub fn use_cursor(cx: Scope) -> Memo<Option<String>> {
+    let query = use_query_map(cx);
+    create_memo(cx, move |_| {
+        query.with(|q| q.get("cursor").cloned().filter(|c| !c.is_empty()))
+    })
+}
+
+// 当前地址换成指定页码和游标后的链接，其它查询参数保持不变
+fn page_href(cx: Scope, page: i64, cursor: Option<String>) -> String {
+    let location = use_location(cx);
+    let mut query = location.query.get();
+    query.remove("page");
+    query.remove("cursor");
+    if page > 1 {
+        query.insert("page".to_string(), page.to_string());
+    }
+    if let Some(c) = cursor.filter(|c| !c.is_empty()) {
+        query.insert("cursor".to_string(), c);
+    }
+    format!("{}{}", location.pathname.get(), query.to_query_string())
+}
+
+// 筛选条件变化后回到第一页
+pub fn reset_page(cx: Scope) {
+    let navigate = use_navigate(cx);
+    let href = page_href(cx, 1, None);
+    let _ = navigate(
+        &href,
+        NavigateOptions {
+            replace: true,
+            ..Default::default()
+        },
+    );
+}
+
+// 页码导航，页码和游标保存在地址栏中，可以分享链接，也可以用浏览器后退。
+// total 为空表示不知道总数，此时只根据 next 判断有没有下一页；
+// next 为下一页的游标，已经是最后一页时为 None，为空字符串时按页码跳转
 #[allow(non_snake_case)]
 #[component]
 pub fn Pagination(
     cx: Scope,
-    #[prop(into)] pn: Signal<i64>,
-    #[prop(into)] set_pn: WriteSignal<i64>,
+    #[prop(into)] total: MaybeSignal<Option<i64>>,
+    per_page: i64,
+    #[prop(into)] next: MaybeSignal<Option<String>>,
 ) -> impl IntoView {
-    view! {
-            cx,
-    <div class="inline-flex justify-center gap-1">
-      <button
-        type="button"
-        class="inline-flex h-8 w-8 items-center justify-center rounded border border-gray-100"
-        on:click=move |_| {
-            set_pn.update(|pn| {
-                if pn.clone() > 1 {
-                    *pn -= 1
-                }
-            })
-        }
-      >
-        <span class="sr-only">"Prev Page"</span>
-        <svg
-          xmlns="http://www.w3.org/2000/svg"
-          class="h-3 w-3"
-          viewBox="0 0 20 20"
-          fill="currentColor"
-        >
-          <path
-            fill-rule="evenodd"
-            d="M12.707 5.293a1 1 0 010 1.414L9.414 10l3.293 3.293a1 1 0 01-1.414 1.414l-4-4a1 1 0 010-1.414l4-4a1 1 0 011.414 0z"
-            clip-rule="evenodd"
-          />
-        </svg>
-      </button>
+    let page = use_page(cx);
+    let pages = move || total.get().map(|t| ((t + per_page - 1) / per_page).max(1));
 
-      <div>
-        <label for="PaginationPage" class="sr-only">"Page"</label>
+    let item_class = "inline-flex h-8 min-w-[2rem] items-center justify-center rounded border border-gray-100 px-2 text-xs";
+    let disabled_class = "inline-flex h-8 min-w-[2rem] items-center justify-center rounded border border-gray-100 px-2 text-xs text-gray-300";
 
-        <input
-          type="number"
-          class="h-8 w-12 rounded border border-gray-100 p-0 text-center text-xs font-medium [-moz-appearance:_textfield] [&::-webkit-outer-spin-button]:m-0 [&::-webkit-outer-spin-button]:appearance-none [&::-webkit-inner-spin-button]:m-0 [&::-webkit-inner-spin-button]:appearance-none"
-          min="1"
-          value=pn
-          id="PaginationPage"
-        />
-      </div>
-
-      <a
-        href="#"
-        class="inline-flex h-8 w-8 items-center justify-center rounded border border-gray-100"
-        on:click=move |_| {
-            set_pn.update(|pn| *pn += 1)
+    // 显示首页、末页和当前页前后两页，中间用省略号
+    let numbers = move || {
+        let current = page.get();
+        let last = match pages() {
+            Some(last) => last,
+            None => return vec![],
+        };
+        let shown = (1..=last).filter(|p| *p == 1 || *p == last || (*p - current).abs() <= 2);
+        let mut views = vec![];
+        let mut prev = 0;
+        for p in shown {
+            if p - prev > 1 {
+                views.push(
+                    view! { cx, <span class="px-1 text-xs text-gray-400">"…"</span> }.into_view(cx),
+                );
+            }
+            prev = p;
+            let class = if p == current {
+                "inline-flex h-8 min-w-[2rem] items-center justify-center rounded border border-blue-600 bg-blue-600 px-2 text-xs text-white"
+            } else {
+                item_class
+            };
+            views.push(
+                view! { cx, <A href=page_href(cx, p, None) class=class>{p}</A> }.into_view(cx),
+            );
         }
-      >
-        <span class="sr-only">"Next Page"</span>
-        <svg
-          xmlns="http://www.w3.org/2000/svg"
-          class="h-3 w-3"
-          viewBox="0 0 20 20"
-          fill="currentColor"
-        >
-          <path
-            fill-rule="evenodd"
-            d="M7.293 14.707a1 1 0 010-1.414L10.586 10 7.293 6.707a1 1 0 011.414-1.414l4 4a1 1 0 010 1.414l-4 4a1 1 0 01-1.414 0z"
-            clip-rule="evenodd"
-          />
-        </svg>
-      </a>
-    </div>
+        views
+    };
 
+    let has_next = {
+        let next = next.clone();
+        move || match pages() {
+            Some(last) => page.get() < last,
+            None => next.get().is_some(),
         }
+    };
+
+    view! {
+        cx,
+        <nav class="inline-flex items-center justify-center gap-1" aria-label="Pagination">
+            {move || if page.get() > 1 {
+                view! { cx,
+                    <A href=page_href(cx, page.get() - 1, None) class=item_class>
+                        <span class="sr-only">"Prev Page"</span>"‹"
+                    </A>
+                }.into_view(cx)
+            } else {
+                view! { cx, <span class=disabled_class>"‹"</span> }.into_view(cx)
+            }}
+            {numbers}
+            {move || if has_next() {
+                view! { cx,
+                    <A href=page_href(cx, page.get() + 1, next.get()) class=item_class>
+                        <span class="sr-only">"Next Page"</span>"›"
+                    </A>
+                }.into_view(cx)
+            } else {
+                view! { cx, <span class=disabled_class>"›"</span> }.into_view(cx)
+            }}
+            {move || total.get().map(|t| view! { cx,
+                <span class="ml-2 text-xs text-gray-500">{format!("共 {} 条", t)}</span>
+            })}
+        </nav>
+    }
 }
@@ -0,0 +1,181 @@
+use crate::api::books::{BookUI, ConfirmReturns};
+use crate::components::book::{action_error, format_date, from_now};
+use leptos::*;
+use leptos_router::*;
+use std::collections::BTreeSet;
+
+// 等待确认归还的书籍，最早归还的排在前面。可以勾选多本一起确认，也可以报告遗失或损坏
+#[allow(non_snake_case)]
+#[component]
+pub fn ReturnQueuePage(cx: Scope) -> impl IntoView {
+    let confirm_act = create_server_action::<ConfirmReturns>(cx);
+    let report_act = create_server_action::<crate::api::books::ReportBookProblem>(cx);
+
+    let queue = create_resource(
+        cx,
+        move || (confirm_act.version().get(), report_act.version().get()),
+        move |_| crate::api::books::return_queue(cx),
+    );
+    let (selected, set_selected) = create_signal(cx, BTreeSet::<i64>::new());
+    // 队列刷新后去掉已经不在队列中的书
+    create_effect(cx, move |_| {
+        if let Some(Ok(books)) = queue.read(cx) {
+            set_selected.update(|s| s.retain(|id| books.iter().any(|b| b.id == *id)));
+        }
+    });
+    let ids = move || {
+        queue
+            .read(cx)
+            .and_then(|r| r.ok())
+            .map(|books| books.iter().map(|b| b.id).collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+    let all_selected = move || {
+        let ids = ids();
+        !ids.is_empty() && selected.with(|s| ids.iter().all(|id| s.contains(id)))
+    };
+    let confirm_selected = move |_| {
+        let ids = selected
+            .get()
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        confirm_act.dispatch(ConfirmReturns { ids });
+    };
+
+    let act_err = move || {
+        [confirm_act.value().get(), report_act.value().get()]
+            .into_iter()
+            .find_map(|r| r.and_then(|r| r.err()))
+            .map(|e| {
+                view! { cx,
+                    <p class="rounded bg-red-50 px-4 py-2 text-sm text-red-600">{action_error(e)}</p>
+                }
+            })
+    };
+
+    let g = move || match queue.read(cx) {
+        None => None,
+        Some(Err(e)) => {
+            Some(view! { cx, <p class="text-sm text-red-600">{action_error(e)}</p> }.into_view(cx))
+        }
+        Some(Ok(books)) if books.is_empty() => Some(
+            view! { cx, <p class="text-sm text-gray-500">"没有等待确认归还的书籍"</p> }
+                .into_view(cx),
+        ),
+        Some(Ok(books)) => {
+            let content = view! { cx,
+                <table class="min-w-full divide-y-2 divide-gray-200 text-sm">
+                    <thead>
+                        <tr>
+                            <th class="px-4 py-2 text-left">
+                                <input type="checkbox" class="rounded border-gray-300"
+                                    prop:checked=all_selected
+                                    on:change=move |ev| {
+                                        let ids = if event_target_checked(&ev) { ids() } else { vec![] };
+                                        set_selected.set(ids.into_iter().collect());
+                                    }/>
+                            </th>
+                            <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"书名"</th>
+                            <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"归还人"</th>
+                            <th class="whitespace-nowrap px-4 py-2 text-left font-medium text-gray-900">"归还时间"</th>
+                            <th class="px-4 py-2"></th>
+                        </tr>
+                    </thead>
+                    <tbody class="divide-y divide-gray-200">
+                    <For each=move || books.clone() key=|b| b.id
+                    view=move |cx, b: BookUI| view! { cx,
+                        <ReturnRow book=b selected=selected set_selected=set_selected
+                            confirm_act=confirm_act report_act=report_act/>
+                    }/>
+                    </tbody>
+                </table>
+            };
+            Some(content.into_view(cx))
+        }
+    };
+
+    view! {
+        cx,
+        <div class="mx-auto max-w-screen-xl px-4 py-8">
+            <div class="mb-6 flex items-center justify-between">
+                <h1 class="text-xl font-bold">"确认归还"</h1>
+                <button type="button"
+                    class="rounded bg-green-600 px-5 py-2 text-sm font-medium text-white hover:bg-green-500 disabled:opacity-50"
+                    prop:disabled=move || selected.with(|s| s.is_empty()) || confirm_act.pending().get()
+                    on:click=confirm_selected>
+                    {move || format!("确认选中的 {} 本", selected.with(|s| s.len()))}
+                </button>
+            </div>
+            {act_err}
+            <Suspense fallback=move || view! { cx, <p>"Loading..."</p> }>
+                {g}
+            </Suspense>
+        </div>
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn ReturnRow(
+    cx: Scope,
+    book: BookUI,
+    selected: ReadSignal<BTreeSet<i64>>,
+    set_selected: WriteSignal<BTreeSet<i64>>,
+    confirm_act: Action<ConfirmReturns, Result<(), ServerFnError>>,
+    report_act: Action<crate::api::books::ReportBookProblem, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let id = book.id;
+    view! { cx,
+        <tr>
+            <td class="px-4 py-2">
+                <input type="checkbox" class="rounded border-gray-300"
+                    prop:checked=move || selected.with(|s| s.contains(&id))
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        set_selected.update(|s| {
+                            if checked {
+                                s.insert(id);
+                            } else {
+                                s.remove(&id);
+                            }
+                        });
+                    }/>
+            </td>
+            <td class="whitespace-nowrap px-4 py-2">
+                <A href=format!("/book/{}", id) class="font-medium text-gray-900">{book.title.clone()}</A>
+                <div class="text-xs text-gray-500">{book.barcode.clone()}</div>
+            </td>
+            <td class="whitespace-nowrap px-4 py-2 text-gray-700">{book.operator_name.clone()}</td>
+            <td class="whitespace-nowrap px-4 py-2 text-gray-700">
+                {format_date(book.operate_at)}
+                <span class="ml-2 text-xs text-gray-400">{from_now(book.operate_at)}</span>
+            </td>
+            <td class="whitespace-nowrap px-4 py-2">
+                <div class="flex items-start gap-4">
+                    <ActionForm action=confirm_act class="inline-block">
+                        <input type="hidden" name="ids" value=id/>
+                        <button type="submit" class="rounded bg-green-600 px-4 py-2 text-xs font-medium text-white hover:bg-green-700">
+                        "确认归还"
+                        </button>
+                    </ActionForm>
+                    <details>
+                        <summary class="cursor-pointer py-2 text-xs text-gray-600">"报告问题"</summary>
+                        <ActionForm action=report_act class="mt-2 flex flex-col gap-2">
+                            <input type="hidden" name="id" value=id/>
+                            <select name="problem" class="rounded border-gray-200 text-xs">
+                                <option value="damaged">"损坏"</option>
+                                <option value="lost">"遗失"</option>
+                            </select>
+                            <input type="text" name="note" placeholder="问题说明" class="rounded border-gray-200 text-xs"/>
+                            <button type="submit" class="rounded bg-orange-600 px-4 py-2 text-xs font-medium text-white hover:bg-orange-700">
+                            "提交"
+                            </button>
+                        </ActionForm>
+                    </details>
+                </div>
+            </td>
+        </tr>
+    }
+}
@@ -0,0 +1,221 @@
+use crate::api::stats::{LibraryStatsUI, MonthCountUI};
+use crate::components::book::action_error;
+use leptos::*;
+
+// 图表的宽度，SVG 按 viewBox 缩放到容器宽度
+const CHART_WIDTH: f64 = 480.0;
+const ROW_HEIGHT: f64 = 28.0;
+const LABEL_WIDTH: f64 = 160.0;
+const COLUMN_HEIGHT: f64 = 160.0;
+
+#[derive(Clone, Debug)]
+pub struct ChartItem {
+    pub label: String,
+    pub value: i64,
+}
+
+// 管理员统计页面
+#[allow(non_snake_case)]
+#[component]
+pub fn StatsPage(cx: Scope) -> impl IntoView {
+    let stats = create_resource(
+        cx,
+        || (),
+        move |_| crate::api::stats::library_stats(cx, None),
+    );
+
+    let g = move || match stats.read(cx) {
+        None => None,
+        Some(Err(e)) => {
+            Some(view! { cx, <p class="text-sm text-red-600">{action_error(e)}</p> }.into_view(cx))
+        }
+        Some(Ok(s)) => Some(view! { cx, <StatsView stats=s/> }.into_view(cx)),
+    };
+
+    view! {
+        cx,
+        <div class="mx-auto max-w-screen-xl px-4 py-8">
+            <h1 class="mb-6 text-xl font-bold">"馆藏统计"</h1>
+            <Suspense fallback=move || view! { cx, <p>"Loading..."</p> }>
+                {g}
+            </Suspense>
+        </div>
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn StatsView(cx: Scope, stats: LibraryStatsUI) -> impl IntoView {
+    let states = stats
+        .states
+        .iter()
+        .map(|s| ChartItem {
+            label: s.state.to_string(),
+            value: s.count,
+        })
+        .collect::<Vec<_>>();
+    let top_titles = stats
+        .top_titles
+        .iter()
+        .map(|t| ChartItem {
+            label: t.title.clone(),
+            value: t.borrows,
+        })
+        .collect::<Vec<_>>();
+    let top_borrowers = stats
+        .top_borrowers
+        .iter()
+        .map(|b| ChartItem {
+            label: b.name.clone(),
+            value: b.borrows,
+        })
+        .collect::<Vec<_>>();
+    let awaiting = stats.awaiting_confirmation > 0;
+    let overdue = stats.overdue > 0;
+
+    view! { cx,
+        <div class="grid grid-cols-1 gap-4 sm:grid-cols-3">
+            <StatCard label="馆藏总数" value=stats.total() highlight=false/>
+            <StatCard label="等待确认归还" value=stats.awaiting_confirmation highlight=awaiting/>
+            <StatCard label="逾期未还" value=stats.overdue highlight=overdue/>
+        </div>
+        <div class="mt-8 grid grid-cols-1 gap-8 lg:grid-cols-2">
+            <ChartSection title="书籍状态">
+                <BarChart items=states/>
+            </ChartSection>
+            <ChartSection title="借阅最多的书">
+                <BarChart items=top_titles/>
+            </ChartSection>
+            <ChartSection title="借阅最多的同事">
+                <BarChart items=top_borrowers/>
+            </ChartSection>
+            <ChartSection title="每月入库">
+                <ColumnChart months=stats.acquisitions.clone()/>
+            </ChartSection>
+            <ChartSection title="每月借出">
+                <ColumnChart months=stats.borrows.clone()/>
+            </ChartSection>
+        </div>
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn StatCard(cx: Scope, label: &'static str, value: i64, highlight: bool) -> impl IntoView {
+    let value_class = if highlight {
+        "mt-1 text-2xl font-bold text-red-600"
+    } else {
+        "mt-1 text-2xl font-bold text-gray-900"
+    };
+    view! { cx,
+        <div class="rounded-lg border border-gray-100 p-4">
+            <p class="text-sm text-gray-500">{label}</p>
+            <p class=value_class>{value}</p>
+        </div>
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn ChartSection(cx: Scope, title: &'static str, children: Children) -> impl IntoView {
+    view! { cx,
+        <section class="rounded-lg border border-gray-100 p-4">
+            <h2 class="mb-4 text-sm font-medium text-gray-900">{title}</h2>
+            {children(cx)}
+        </section>
+    }
+}
+
+// 横向条形图，左边是名称，条形长度按最大值缩放，右边是数量
+#[allow(non_snake_case)]
+#[component]
+pub fn BarChart(cx: Scope, items: Vec<ChartItem>) -> impl IntoView {
+    if items.is_empty() {
+        return view! { cx, <p class="text-sm text-gray-500">"暂无数据"</p> }.into_view(cx);
+    }
+    let max = items.iter().map(|i| i.value).max().unwrap_or(0).max(1);
+    let height = ROW_HEIGHT * items.len() as f64;
+    // 右边留出显示数量的空间
+    let bar_width = CHART_WIDTH - LABEL_WIDTH - 40.0;
+    let rows = items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let top = ROW_HEIGHT * i as f64;
+            let text_y = top + 18.0;
+            let bar_y = top + 6.0;
+            let width = bar_width * item.value as f64 / max as f64;
+            let value_x = LABEL_WIDTH + width + 4.0;
+            view! { cx,
+                <g>
+                    <title>{format!("{}：{}", item.label, item.value)}</title>
+                    <text x=0 y=text_y font-size="12" fill="#374151">{ellipsis(&item.label, 12)}</text>
+                    <rect x=LABEL_WIDTH y=bar_y width=width height=16 rx=2 fill="#2563eb"></rect>
+                    <text x=value_x y=text_y font-size="12" fill="#6b7280">{item.value}</text>
+                </g>
+            }
+        })
+        .collect::<Vec<_>>();
+    view! { cx,
+        <svg viewBox=format!("0 0 {} {}", CHART_WIDTH, height) class="w-full" role="img">
+            {rows}
+        </svg>
+    }
+    .into_view(cx)
+}
+
+// 按月的柱状图，每根柱子下面是月份，上面是数量
+#[allow(non_snake_case)]
+#[component]
+pub fn ColumnChart(cx: Scope, months: Vec<MonthCountUI>) -> impl IntoView {
+    if months.is_empty() {
+        return view! { cx, <p class="text-sm text-gray-500">"暂无数据"</p> }.into_view(cx);
+    }
+    let max = months.iter().map(|m| m.count).max().unwrap_or(0).max(1);
+    let column = CHART_WIDTH / months.len() as f64;
+    // 上面留出数量，下面留出月份
+    let top = 16.0;
+    let bottom = top + COLUMN_HEIGHT;
+    let columns = months
+        .into_iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let height = COLUMN_HEIGHT * m.count as f64 / max as f64;
+            let x = column * i as f64 + column * 0.2;
+            let width = column * 0.6;
+            let center = column * i as f64 + column / 2.0;
+            let y = bottom - height;
+            let value_y = y - 4.0;
+            let label_y = bottom + 14.0;
+            // 只显示月份，一月显示年份
+            let label = match m.month.split_once('-') {
+                Some((year, "01")) => year.to_string(),
+                Some((_, month)) => month.to_string(),
+                None => m.month.clone(),
+            };
+            view! { cx,
+                <g>
+                    <title>{format!("{}：{}", m.month, m.count)}</title>
+                    <rect x=x y=y width=width height=height rx=2 fill="#16a34a"></rect>
+                    <text x=center y=value_y font-size="10" text-anchor="middle" fill="#6b7280">{m.count}</text>
+                    <text x=center y=label_y font-size="10" text-anchor="middle" fill="#374151">{label}</text>
+                </g>
+            }
+        })
+        .collect::<Vec<_>>();
+    view! { cx,
+        <svg viewBox=format!("0 0 {} {}", CHART_WIDTH, bottom + 20.0) class="w-full" role="img">
+            <line x1=0 y1=bottom x2=CHART_WIDTH y2=bottom stroke="#e5e7eb"></line>
+            {columns}
+        </svg>
+    }
+    .into_view(cx)
+}
+
+// 名称太长时截断，完整名称在悬停提示中
+fn ellipsis(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    format!("{}…", text.chars().take(max - 1).collect::<String>())
+}
@@ -3,24 +3,29 @@ use axum::response::IntoResponse;
 use axum::{
     body::Body as AxumBody,
     extract::Extension,
-    http::{header::HeaderMap, Request},
+    http::{header, header::HeaderMap, Request, StatusCode},
     routing::get,
     Router,
 };
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use leptos::*;
 use leptos_axum::{generate_route_list, handle_server_fns_with_context, LeptosRoutes};
+use libraryms::api::import::{ColumnMapping, ImportStatus};
+use libraryms::backend::auth::{account_info_from_headers, get_account_by_id, Role};
 use libraryms::backend::books::BookMS;
 use libraryms::backend::conf::parse_conf;
+use libraryms::backend::export::{self, ExportFormat};
 use libraryms::backend::ldap::LdapIdent;
 use libraryms::components::home::*;
 use libraryms::fallback::file_and_error_handler;
 use sqlx::PgPool;
+use std::io::Write;
 use std::sync::Arc;
 use tower::ServiceBuilder;
 use tower_http::compression::CompressionLayer;
+use tower_http::services::ServeDir;
 use tower_http::trace::TraceLayer;
-use tracing::{debug, info, Level};
+use tracing::{debug, error, info, Level};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -30,6 +35,49 @@ struct Args {
     config: String,
     #[arg(short, long, default_value = "debug")]
     log: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 执行数据库迁移后退出，不启动服务
+    Migrate,
+    /// 从 CSV 或 XLSX 文件批量入库，默认只预览，加 --commit 才会入库
+    Import {
+        /// 要导入的文件，第一行为表头
+        file: String,
+        /// 入库操作人
+        #[arg(long)]
+        operator: String,
+        /// ISBN 所在列的表头，不指定列时按表头名称猜测
+        #[arg(long)]
+        isbn_column: Option<String>,
+        #[arg(long)]
+        barcode_column: Option<String>,
+        #[arg(long)]
+        title_column: Option<String>,
+        #[arg(long)]
+        location_column: Option<String>,
+        #[arg(long)]
+        donor_column: Option<String>,
+        /// 确认入库，不加时只输出预览
+        #[arg(long)]
+        commit: bool,
+    },
+    /// 导出馆藏目录，格式可选 csv、jsonl、marc、marcxml
+    Export {
+        format: ExportFormat,
+        /// 输出文件，不指定时输出到标准输出
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// 为已有书目生成书名和作者的拼音，用于拼音搜索
+    BackfillPinyin {
+        /// 重新生成所有书目的拼音，不加时只处理还没有拼音的书目
+        #[arg(long)]
+        all: bool,
+    },
 }
 
 pub async fn serv() {
@@ -48,16 +96,71 @@ pub async fn serv() {
     info!("Starting up {}, {:?}", &args.config, pwd);
     let server_conf = parse_conf(&args.config).expect("解析配置文件失败");
 
+    let pg_pool = libraryms::backend::db::init(&server_conf.pg_dsn)
+        .await
+        .expect("连接数据库失败");
+    match args.command {
+        Some(Command::Migrate) => {
+            libraryms::backend::db::migrate(&pg_pool)
+                .await
+                .expect("数据库迁移失败");
+            info!("数据库迁移完成");
+            return;
+        }
+        Some(Command::Import {
+            file,
+            operator,
+            isbn_column,
+            barcode_column,
+            title_column,
+            location_column,
+            donor_column,
+            commit,
+        }) => {
+            let mapping = ColumnMapping {
+                isbn: isbn_column.unwrap_or_default(),
+                barcode: barcode_column.unwrap_or_default(),
+                title: title_column.unwrap_or_default(),
+                location: location_column.unwrap_or_default(),
+                donor: donor_column.unwrap_or_default(),
+            };
+            if let Err(e) = import(&pg_pool, &server_conf, &file, mapping, &operator, commit).await
+            {
+                error!("导入失败: {:#}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Export { format, output }) => {
+            let body = export::export(&pg_pool, format).await.expect("导出失败");
+            match output {
+                Some(path) => tokio::fs::write(&path, body).await.expect("写入文件失败"),
+                None => std::io::stdout().write_all(&body).expect("输出失败"),
+            }
+            return;
+        }
+        Some(Command::BackfillPinyin { all }) => {
+            let n = libraryms::backend::pinyin::backfill(&pg_pool, all)
+                .await
+                .expect("生成拼音失败");
+            info!("已为 {} 个书目生成拼音", n);
+            return;
+        }
+        None => {}
+    }
+    // 配置了 auto_migrate 时，启动服务前执行尚未执行的数据库迁移
+    if server_conf.auto_migrate {
+        libraryms::backend::db::migrate(&pg_pool)
+            .await
+            .expect("数据库迁移失败");
+    }
+
     // Setting this to None means we'll be using cargo-leptos and its env vars
     let conf = get_configuration(None).await.unwrap();
     let leptos_options = conf.leptos_options.clone();
     let addr = leptos_options.site_addr;
     let routes = generate_route_list(|cx| view! { cx, <BlogApp/> }).await;
 
-    let pg_pool = libraryms::backend::db::init(&server_conf.pg_dsn)
-        .await
-        .expect("连接数据库失败");
-
     let ldap_ident = libraryms::backend::ldap::init(
         &server_conf.ldap.url,
         &server_conf.ldap.base,
@@ -72,11 +175,32 @@ pub async fn serv() {
     )
     .await
     .unwrap();
-    let bms = libraryms::backend::books::init(&pg_pool, &server_conf.isbn_api_key)
+    let bms = libraryms::backend::books::init(&pg_pool, &server_conf)
         .await
         .expect("图书管理模块初始化失败");
     let a_ldap_ident = Arc::new(ldap_ident);
     let a_bms = Arc::new(bms);
+
+    // 定期处理超过取书期限的预约
+    let e_bms = a_bms.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = e_bms.expire_holds().await {
+                error!("处理过期预约失败: {:?}", e);
+            }
+        }
+    });
+    // 迁移后清空了拼音的书目在后台分批补齐，不阻塞服务启动
+    let p_pg_pool = pg_pool.clone();
+    tokio::spawn(async move {
+        match libraryms::backend::pinyin::backfill(&p_pg_pool, false).await {
+            Ok(0) => {}
+            Ok(n) => info!("已为 {} 个书目生成拼音", n),
+            Err(e) => error!("生成拼音失败: {:#}", e),
+        }
+    });
     let a_pg_pool = Arc::new(pg_pool);
     let l_ldap_ident = a_ldap_ident.clone();
     let l_bms = a_bms.clone();
@@ -89,10 +213,16 @@ pub async fn serv() {
     let mut app = Router::new()
         .route("/liveness", get(|| async { "I'm alive!" }))
         .route("/readiness", get(|| async { "I'm ready!" }))
+        .route("/admin/export/:format", get(export_handler))
         .route(
             "/api/*fn_name",
             get(server_fn_handler).post(server_fn_handler),
         )
+        // 手工编目上传的封面图片
+        .nest_service(
+            libraryms::backend::covers::COVER_PATH,
+            ServeDir::new(&server_conf.cover_dir),
+        )
         .leptos_routes_with_context(
             leptos_options.clone(),
             routes,
@@ -129,6 +259,92 @@ pub async fn serv() {
         .unwrap();
 }
 
+// 命令行批量入库，逐行输出处理结果
+async fn import(
+    pg_pool: &PgPool,
+    server_conf: &libraryms::backend::conf::Config,
+    file: &str,
+    mapping: ColumnMapping,
+    operator: &str,
+    commit: bool,
+) -> anyhow::Result<()> {
+    // 操作人写入操作记录，账号不存在时在读取文件之前就退出
+    let account = match get_account_by_id(pg_pool, operator).await {
+        Ok(account) => account,
+        Err(e) if matches!(e.downcast_ref(), Some(sqlx::Error::RowNotFound)) => {
+            anyhow::bail!("操作人账号 {} 不存在，请先用该账号登录一次", operator)
+        }
+        Err(e) => return Err(e),
+    };
+    info!("入库操作人: {} ({})", account.display_name, account.id);
+    let bytes = tokio::fs::read(file).await?;
+    let sheet = libraryms::backend::import::read_sheet(file, &bytes)?;
+    let mapping = if mapping.is_empty() {
+        ColumnMapping::detect(&sheet.headers)
+    } else {
+        mapping
+    };
+    info!("列映射: {:?}", mapping);
+    let rows = libraryms::backend::import::map_rows(&sheet, &mapping)?;
+    let bms = libraryms::backend::books::init(pg_pool, server_conf).await?;
+    let results = bms.import(&rows, operator, commit).await?;
+    for r in results.iter() {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            r.line, r.status, r.isbn, r.barcode, r.title, r.message
+        );
+    }
+    let importable = results.iter().filter(|r| r.status.importable()).count();
+    let stored = results
+        .iter()
+        .filter(|r| r.status == ImportStatus::Stored)
+        .count();
+    if commit {
+        info!("共 {} 行，入库 {} 行", results.len(), stored);
+    } else {
+        info!(
+            "共 {} 行，可以入库 {} 行，确认无误后加 --commit 入库",
+            results.len(),
+            importable
+        );
+    }
+    Ok(())
+}
+
+// 管理员下载馆藏目录
+async fn export_handler(
+    Extension(pool): Extension<Arc<PgPool>>,
+    Extension(server_conf): Extension<libraryms::backend::conf::Config>,
+    Path(format): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    match account_info_from_headers(&headers, &server_conf, &pool).await {
+        Some(a) if a.role == Role::Admin => {}
+        _ => return (StatusCode::FORBIDDEN, "Not admin".to_string()).into_response(),
+    }
+    let format: ExportFormat = match format.parse() {
+        Ok(f) => f,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match export::export(&pool, format).await {
+        Ok(body) => (
+            [
+                (header::CONTENT_TYPE, format.content_type().to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"catalog.{}\"", format.extension()),
+                ),
+            ],
+            body,
+        )
+            .into_response(),
+        Err(e) => {
+            error!("导出失败: {:#}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "导出失败".to_string()).into_response()
+        }
+    }
+}
+
 async fn server_fn_handler(
     Extension(pool): Extension<Arc<PgPool>>,
     Extension(bms): Extension<Arc<BookMS>>,